@@ -0,0 +1,336 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::source::{EntryKind, IndexEntry};
+use crate::{FileMetadata, IndexOptions, extension_of};
+
+// archives are listed without extracting anything to disk, their members become virtual
+// folders/files below the archive's path, flagged with in_archive
+
+pub const DEFAULT_MAX_SIZE: u64 = 256 * 1024 * 1024;
+// 1 only opens archives on disk, 2 also the archives inside those, ...
+pub const DEFAULT_MAX_DEPTH: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveLimits {
+    pub max_size: u64, // bigger archives stay plain files
+    pub max_depth: u32,
+}
+
+impl ArchiveLimits {
+    pub fn from_options(options: &IndexOptions) -> Self {
+        ArchiveLimits {
+            max_size: options.archive_max_size.unwrap_or(DEFAULT_MAX_SIZE),
+            max_depth: options.archive_max_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+        }
+    }
+}
+
+pub fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+// the archive at `path` as a folder, followed by everything in it, parents first
+pub fn list_archive(path: &Path, limits: &ArchiveLimits) -> Result<Vec<IndexEntry>, String> {
+    let kind = archive_kind(path).ok_or(format!("Not an archive: {}", path.display()))?;
+    let file = File::open(path).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let mut listing = Listing::new(path, 1, limits);
+    listing.read(kind, BufReader::new(file))?;
+    Ok(listing.entries)
+}
+
+struct Listing<'a> {
+    root: PathBuf,
+    depth: u32,
+    limits: &'a ArchiveLimits,
+    entries: Vec<IndexEntry>,
+    folders: HashSet<PathBuf>,
+}
+
+impl<'a> Listing<'a> {
+    fn new(root: &Path, depth: u32, limits: &'a ArchiveLimits) -> Self {
+        // the archive itself is on disk, unless it's nested in another archive
+        let mut root_entry = member(root.to_path_buf(), EntryKind::Folder, None, None);
+        root_entry.in_archive = depth > 1;
+        Listing {
+            root: root.to_path_buf(),
+            depth,
+            limits,
+            entries: vec![root_entry],
+            folders: HashSet::from([root.to_path_buf()]),
+        }
+    }
+
+    fn read<R: Read + Seek>(&mut self, kind: ArchiveKind, reader: R) -> Result<(), String> {
+        match kind {
+            ArchiveKind::Zip => self.read_zip(reader),
+            ArchiveKind::Tar => self.read_tar(reader),
+            ArchiveKind::TarGz => self.read_tar(flate2::read::GzDecoder::new(reader)),
+        }
+    }
+
+    fn read_zip<R: Read + Seek>(&mut self, reader: R) -> Result<(), String> {
+        let mut zip =
+            zip::ZipArchive::new(reader).map_err(|e| format!("Failed to read zip: {}", e))?;
+        for i in 0..zip.len() {
+            // the raw entry only has the header, nothing gets decompressed for listing
+            let (name, is_dir, size, modified) = {
+                let file = zip
+                    .by_index_raw(i)
+                    .map_err(|e| format!("Failed to read zip entry: {}", e))?;
+                let modified = file.last_modified().map(|t| {
+                    unix_time(
+                        t.year() as i64,
+                        t.month() as i64,
+                        t.day() as i64,
+                        t.hour() as i64 * 3600 + t.minute() as i64 * 60 + t.second() as i64,
+                    )
+                });
+                (
+                    file.name().to_string(),
+                    file.is_dir(),
+                    file.size(),
+                    modified,
+                )
+            };
+            let Some(path) = self.member_path(&name) else {
+                continue;
+            };
+            if is_dir {
+                self.add_folder(path);
+            } else if let Some(kind) = self.nested_kind(&path, size) {
+                let mut file = zip
+                    .by_index(i)
+                    .map_err(|e| format!("Failed to read zip entry: {}", e))?;
+                let mut bytes = Vec::with_capacity(size as usize);
+                file.read_to_end(&mut bytes)
+                    .map_err(|e| format!("Failed to read zip entry: {}", e))?;
+                self.add_nested(path, kind, bytes, size, modified);
+            } else {
+                self.add_file(path, size, modified);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_tar<R: Read>(&mut self, reader: R) -> Result<(), String> {
+        let mut tar = tar::Archive::new(reader);
+        let entries = tar
+            .entries()
+            .map_err(|e| format!("Failed to read tar: {}", e))?;
+        for entry in entries {
+            let mut entry = entry.map_err(|e| format!("Failed to read tar entry: {}", e))?;
+            let name = entry
+                .path()
+                .map_err(|e| format!("Failed to read tar entry: {}", e))?
+                .to_string_lossy()
+                .to_string();
+            let Some(path) = self.member_path(&name) else {
+                continue;
+            };
+            let entry_type = entry.header().entry_type();
+            let size = entry.size();
+            let modified = entry.header().mtime().ok().map(|t| t as i64);
+            if entry_type.is_dir() {
+                self.add_folder(path);
+            } else if !entry_type.is_file() {
+                // links, devices... aren't files anyone would guess
+                continue;
+            } else if let Some(kind) = self.nested_kind(&path, size) {
+                let mut bytes = Vec::with_capacity(size as usize);
+                entry
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| format!("Failed to read tar entry: {}", e))?;
+                self.add_nested(path, kind, bytes, size, modified);
+            } else {
+                self.add_file(path, size, modified);
+            }
+        }
+        Ok(())
+    }
+
+    // names are relative to the archive, anything trying to climb out of it is skipped
+    fn member_path(&self, name: &str) -> Option<PathBuf> {
+        let mut path = self.root.clone();
+        for component in name.split(['/', '\\']) {
+            match component {
+                "" | "." => {}
+                ".." => return None,
+                _ if component.ends_with(':') => return None,
+                _ => path.push(component),
+            }
+        }
+        (path != self.root).then_some(path)
+    }
+
+    fn nested_kind(&self, path: &Path, size: u64) -> Option<ArchiveKind> {
+        if self.depth >= self.limits.max_depth || size > self.limits.max_size {
+            return None;
+        }
+        archive_kind(path)
+    }
+
+    // archives don't always list their folders, so make sure every parent is there first
+    fn add_parents(&mut self, path: &Path) {
+        let missing: Vec<PathBuf> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|parent| !self.folders.contains(*parent))
+            .map(Path::to_path_buf)
+            .collect();
+        for parent in missing.into_iter().rev() {
+            self.folders.insert(parent.clone());
+            self.entries
+                .push(member(parent, EntryKind::Folder, None, None));
+        }
+    }
+
+    fn add_folder(&mut self, path: PathBuf) {
+        self.add_parents(&path);
+        if self.folders.insert(path.clone()) {
+            self.entries
+                .push(member(path, EntryKind::Folder, None, None));
+        }
+    }
+
+    fn add_file(&mut self, path: PathBuf, size: u64, modified: Option<i64>) {
+        self.add_parents(&path);
+        self.entries
+            .push(member(path, EntryKind::File, Some(size), modified));
+    }
+
+    // an archive inside the archive becomes a folder too, or stays a file if it can't be read
+    fn add_nested(
+        &mut self,
+        path: PathBuf,
+        kind: ArchiveKind,
+        bytes: Vec<u8>,
+        size: u64,
+        modified: Option<i64>,
+    ) {
+        let mut nested = Listing::new(&path, self.depth + 1, self.limits);
+        match nested.read(kind, Cursor::new(bytes)) {
+            Ok(()) => {
+                self.add_parents(&path);
+                self.folders.extend(nested.folders);
+                self.entries.extend(nested.entries);
+            }
+            Err(e) => {
+                eprintln!("Failed to read nested archive {}: {}", path.display(), e);
+                self.add_file(path, size, modified);
+            }
+        }
+    }
+}
+
+fn member(path: PathBuf, kind: EntryKind, size: Option<u64>, modified: Option<i64>) -> IndexEntry {
+    let mut entry = match kind {
+        EntryKind::Folder => IndexEntry::folder(path),
+        EntryKind::File => {
+            let extension = extension_of(&path);
+            IndexEntry::file(
+                path,
+                FileMetadata {
+                    size,
+                    modified,
+                    extension,
+                },
+            )
+        }
+    };
+    entry.in_archive = true;
+    entry
+}
+
+// zip timestamps are a local date and time, close enough to UTC for the game
+fn unix_time(year: i64, month: i64, day: i64, seconds: i64) -> i64 {
+    // days since 1970-01-01, from Howard Hinnant's days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    days * 86_400 + seconds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_tar(path: &Path, members: &[(&str, &[u8])]) {
+        let mut builder = tar::Builder::new(File::create(path).unwrap());
+        for (name, contents) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(0);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *contents).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    #[test]
+    fn only_members_are_in_the_archive() {
+        let dir = std::env::temp_dir().join(format!("guessfs-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let inner = dir.join("inner.tar");
+        write_tar(&inner, &[("deep.txt", b"deep")]);
+        let outer = dir.join("outer.tar");
+        write_tar(
+            &outer,
+            &[
+                ("docs/readme.txt", b"hello"),
+                ("inner.tar", &std::fs::read(&inner).unwrap()),
+            ],
+        );
+
+        let limits = ArchiveLimits {
+            max_size: DEFAULT_MAX_SIZE,
+            max_depth: DEFAULT_MAX_DEPTH,
+        };
+        let entries = list_archive(&outer, &limits).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let in_archive: Vec<(PathBuf, bool)> = entries
+            .into_iter()
+            .map(|entry| {
+                (
+                    entry.path.strip_prefix(&dir).unwrap().to_path_buf(),
+                    entry.in_archive,
+                )
+            })
+            .collect();
+        assert_eq!(
+            in_archive,
+            [
+                ("outer.tar", false),
+                ("outer.tar/docs", true),
+                ("outer.tar/docs/readme.txt", true),
+                ("outer.tar/inner.tar", true),
+                ("outer.tar/inner.tar/deep.txt", true),
+            ]
+            .map(|(path, in_archive)| (PathBuf::from(path), in_archive))
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use rand::Rng;
+use rusqlite::{Connection, Result as RusqliteResult};
+
+use crate::has_column;
+
+// how many times to redraw when a candidate ties with an entry already in the round
+const MAX_ATTEMPTS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    Deeper,   // folders, depth below the index root
+    Bigger,   // files, size in bytes
+    Older,    // files, last modified time (smaller is older)
+    Children, // folders, direct subfolders + files
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [Metric::Deeper, Metric::Bigger, Metric::Older, Metric::Children];
+
+    fn table_column(&self) -> (&'static str, &'static str) {
+        match self {
+            Metric::Deeper => ("folders", "depth"),
+            Metric::Bigger => ("files", "size"),
+            Metric::Older => ("files", "modified"),
+            Metric::Children => ("folders", "child_count"),
+        }
+    }
+
+    // for "older" the winner has the smallest value
+    pub fn lowest_wins(&self) -> bool {
+        *self == Metric::Older
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareEntry {
+    pub path: String,
+    pub value: i64,
+}
+
+pub fn init_entry_metadata(conn: &Connection) -> RusqliteResult<()> {
+    for (table, column) in [
+        ("folders", "depth"),
+        ("folders", "child_count"),
+        ("files", "depth"),
+    ] {
+        if !has_column(conn, table, column)? {
+            conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column} INTEGER"), [])?;
+        }
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_folder_depth ON folders (depth);",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_folder_child_count ON folders (child_count);",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_file_size ON files (size);", [])?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_file_modified ON files (modified);",
+        [],
+    )?;
+    Ok(())
+}
+
+// fill in depth and child counts, run at the end of indexing after parent ids are resolved
+pub fn build_entry_metadata(conn: &Connection) -> RusqliteResult<()> {
+    init_entry_metadata(conn)?;
+    conn.execute(
+        "WITH RECURSIVE tree (id, depth) AS (
+            SELECT id, 0 FROM folders WHERE parent_id IS NULL
+            UNION ALL
+            SELECT folders.id, tree.depth + 1 FROM folders JOIN tree ON folders.parent_id = tree.id
+        )
+        UPDATE folders SET depth = tree.depth FROM tree WHERE tree.id = folders.id",
+        [],
+    )?;
+    conn.execute(
+        "UPDATE files SET depth = folders.depth + 1 FROM folders WHERE folders.id = files.folder_id",
+        [],
+    )?;
+    conn.execute(
+        "UPDATE folders SET child_count =
+            (SELECT COUNT(*) FROM folders c WHERE c.parent_id = folders.id)
+            + (SELECT COUNT(*) FROM files f WHERE f.folder_id = folders.id)",
+        [],
+    )?;
+    Ok(())
+}
+
+// closeness goes from 0.0 (values far apart, easy) to 1.0 (values as close as possible, hard)
+// returns the allowed range around `value` for the other entries
+fn band(metric: Metric, value: i64, closeness: f64) -> (i64, i64) {
+    let closeness = closeness.clamp(0.0, 1.0);
+    match metric {
+        // depths are small numbers, so work in levels instead of ratios
+        Metric::Deeper => {
+            let spread = 1 + ((1.0 - closeness) * 6.0).round() as i64;
+            (value - spread, value + spread)
+        }
+        // the newest and oldest file can be decades apart, work in days
+        Metric::Older => {
+            let spread = 86_400 * (1.0 + (1.0 - closeness) * 3650.0) as i64;
+            (value - spread, value + spread)
+        }
+        _ => {
+            let ratio = 1.1 + (1.0 - closeness) * 20.0;
+            (
+                (value as f64 / ratio).floor() as i64,
+                (value as f64 * ratio).ceil() as i64,
+            )
+        }
+    }
+}
+
+fn pick_in_range<R: Rng>(
+    conn: &Connection,
+    metric: Metric,
+    range: Option<(i64, i64)>,
+    rng: &mut R,
+) -> Result<Option<CompareEntry>, String> {
+    let (table, column) = metric.table_column();
+    let filter = format!(
+        "{column} IS NOT NULL AND (?1 IS NULL OR {column} BETWEEN ?1 AND ?2)"
+    );
+    let (low, high) = match range {
+        Some((low, high)) => (Some(low), Some(high)),
+        None => (None, None),
+    };
+
+    let count: u64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM {table} WHERE {filter}"),
+            rusqlite::params![low, high],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to count entries: {}", e))?;
+    if count == 0 {
+        return Ok(None);
+    }
+
+    let offset = rng.random_range(0..count);
+    conn.query_row(
+        &format!(
+            "SELECT path, {column} FROM {table} WHERE {filter} ORDER BY path LIMIT 1 OFFSET ?3"
+        ),
+        rusqlite::params![low, high, offset],
+        |row| {
+            Ok(Some(CompareEntry {
+                path: row.get(0)?,
+                value: row.get(1)?,
+            }))
+        },
+    )
+    .map_err(|e| format!("Failed to get entry: {}", e))
+}
+
+// draw `count` entries with distinct values, all within the closeness band of the first one
+// falls back to the whole index when the band is too narrow to fill the round
+pub fn pick_round<R: Rng>(
+    conn: &Connection,
+    metric: Metric,
+    count: usize,
+    closeness: f64,
+    rng: &mut R,
+) -> Result<Vec<CompareEntry>, String> {
+    let first = pick_in_range(conn, metric, None, rng)?
+        .ok_or("No entries with this metric in DB, try re-indexing")?;
+    let range = band(metric, first.value, closeness);
+    let mut entries = vec![first];
+
+    for widened in [false, true] {
+        let mut attempts = 0;
+        while entries.len() < count && attempts < MAX_ATTEMPTS {
+            attempts += 1;
+            let range = if widened { None } else { Some(range) };
+            let Some(candidate) = pick_in_range(conn, metric, range, rng)? else {
+                break;
+            };
+            // ties would leave no right answer
+            if entries.iter().all(|e| e.value != candidate.value) {
+                entries.push(candidate);
+            }
+        }
+    }
+
+    if entries.len() < count {
+        return Err("Not enough distinct entries in DB for a round".to_string());
+    }
+    Ok(entries)
+}
+
+// the metrics with enough distinct values for a round of `count` entries, listings without
+// sizes or dates can't be played on bigger/older
+pub fn available_metrics(conn: &Connection, count: usize) -> Result<Vec<Metric>, String> {
+    let mut metrics = Vec::new();
+    for metric in Metric::ALL {
+        let (table, column) = metric.table_column();
+        let distinct: usize = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM
+                        (SELECT DISTINCT {column} FROM {table} WHERE {column} IS NOT NULL LIMIT ?1)"
+                ),
+                rusqlite::params![count],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count entries: {}", e))?;
+        if distinct >= count {
+            metrics.push(metric);
+        }
+    }
+    Ok(metrics)
+}
+
+// index of the winning entry for the metric
+pub fn winner(metric: Metric, entries: &[CompareEntry]) -> Option<usize> {
+    let values = entries.iter().enumerate();
+    if metric.lowest_wins() {
+        values.min_by_key(|(_, e)| e.value).map(|(i, _)| i)
+    } else {
+        values.max_by_key(|(_, e)| e.value).map(|(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // folders nested three deep, files without sizes or dates like a plain path listing
+    fn listing_index() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_db(&conn).unwrap();
+        for path in ["/index", "/index/a", "/index/a/b", "/index/a/b/c"] {
+            conn.execute("INSERT INTO folders (path) VALUES (?1)", [path])
+                .unwrap();
+        }
+        crate::resolve_parent_ids(&conn).unwrap();
+        for (i, folder) in ["/index", "/index/a", "/index/a/b"].iter().enumerate() {
+            for file in 0..=i {
+                conn.execute(
+                    "INSERT INTO files (path, folder_id)
+                     SELECT ?1 || '/file-' || ?2, id FROM folders WHERE path = ?1",
+                    rusqlite::params![folder, file],
+                )
+                .unwrap();
+            }
+        }
+        build_entry_metadata(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn metrics_need_values() {
+        let conn = listing_index();
+        assert_eq!(
+            available_metrics(&conn, 2).unwrap(),
+            [Metric::Deeper, Metric::Children]
+        );
+        // only four depths to tell apart
+        assert_eq!(available_metrics(&conn, 5).unwrap(), []);
+
+        conn.execute("UPDATE files SET size = id * 100, modified = id", [])
+            .unwrap();
+        assert_eq!(available_metrics(&conn, 2).unwrap(), Metric::ALL);
+    }
+
+    #[test]
+    fn old_indexes_get_depths_when_opened() {
+        let conn = listing_index();
+        let depths = |conn: &Connection| -> Vec<Option<i64>> {
+            let mut stmt = conn
+                .prepare("SELECT depth FROM folders UNION ALL SELECT depth FROM files")
+                .unwrap();
+            stmt.query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<RusqliteResult<_>>()
+                .unwrap()
+        };
+        let built = depths(&conn);
+
+        conn.execute("UPDATE folders SET depth = NULL, child_count = NULL", [])
+            .unwrap();
+        conn.execute("UPDATE files SET depth = NULL", []).unwrap();
+        crate::init_db(&conn).unwrap();
+        assert_eq!(depths(&conn), built);
+        assert!(built.iter().all(Option::is_some));
+    }
+}
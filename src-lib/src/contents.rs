@@ -0,0 +1,208 @@
+use rusqlite::{Connection, OptionalExtension, Result as RusqliteResult};
+
+use crate::GuessOutcome;
+
+// what a folder directly contains, used by the "guess what's inside" game type
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderContents {
+    pub file_count: u64,
+    pub total_size: u64, // in bytes
+    pub dominant_extension: Option<String>,
+    pub newest_file: Option<String>, // file name, not the full path
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentsGuess {
+    pub file_count: Option<u64>,
+    pub total_size: Option<u64>,
+    pub dominant_extension: Option<String>,
+    pub newest_file: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentsScore {
+    pub outcome: GuessOutcome,
+    pub file_count: GuessOutcome,
+    pub total_size: GuessOutcome,
+    pub dominant_extension: GuessOutcome,
+    pub newest_file: GuessOutcome,
+}
+
+pub fn init_folder_stats(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS folder_stats (
+            folder_id INTEGER PRIMARY KEY,
+            file_count INTEGER NOT NULL,
+            total_size INTEGER NOT NULL,
+            dominant_extension TEXT,
+            newest_file TEXT,
+            FOREIGN KEY (folder_id) REFERENCES folders(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// recompute folder_stats from the files table, run at the end of indexing
+// only counts a folder's direct files, subfolders have their own row
+pub fn build_folder_stats(conn: &Connection) -> RusqliteResult<usize> {
+    init_folder_stats(conn)?;
+    conn.execute("DELETE FROM folder_stats", [])?;
+    conn.execute(
+        "INSERT INTO folder_stats (folder_id, file_count, total_size, dominant_extension, newest_file)
+            SELECT
+                f.folder_id,
+                COUNT(*),
+                COALESCE(SUM(f.size), 0),
+                (SELECT extension FROM files e
+                    WHERE e.folder_id = f.folder_id AND e.extension IS NOT NULL
+                    GROUP BY extension ORDER BY COUNT(*) DESC, extension LIMIT 1),
+                (SELECT path FROM files n
+                    WHERE n.folder_id = f.folder_id AND n.modified IS NOT NULL
+                    ORDER BY n.modified DESC, n.path LIMIT 1)
+            FROM files f
+            GROUP BY f.folder_id",
+        [],
+    )
+}
+
+pub fn get_folder_contents(conn: &Connection, folder: &str) -> Result<FolderContents, String> {
+    let contents = conn
+        .query_row(
+            "SELECT s.file_count, s.total_size, s.dominant_extension, s.newest_file
+             FROM folder_stats s JOIN folders ON folders.id = s.folder_id
+             WHERE folders.path = ?1",
+            rusqlite::params![folder],
+            |row| {
+                let newest_path: Option<String> = row.get(3)?;
+                Ok(FolderContents {
+                    file_count: row.get(0)?,
+                    total_size: row.get(1)?,
+                    dominant_extension: row.get(2)?,
+                    newest_file: newest_path.map(|path| {
+                        std::path::Path::new(&path)
+                            .file_name()
+                            .map(|n| n.to_string_lossy().to_string())
+                            .unwrap_or(path)
+                    }),
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Failed to get folder contents: {}", e))?;
+
+    // folders without files don't get a row
+    Ok(contents.unwrap_or(FolderContents {
+        file_count: 0,
+        total_size: 0,
+        dominant_extension: None,
+        newest_file: None,
+    }))
+}
+
+// numbers are "close" when they're within (100 - close_sensitivity)% of the real value
+fn score_number(guess: Option<u64>, actual: u64, close_sensitivity: f64) -> GuessOutcome {
+    let Some(guess) = guess else {
+        return GuessOutcome::Incorrect;
+    };
+    if guess == actual {
+        return GuessOutcome::Correct;
+    }
+    let error = guess.abs_diff(actual) as f64 / actual.max(1) as f64 * 100.0;
+    if 100.0 - error >= close_sensitivity {
+        GuessOutcome::Close
+    } else {
+        GuessOutcome::Incorrect
+    }
+}
+
+fn score_name(guess: Option<&str>, actual: Option<&str>) -> GuessOutcome {
+    match (guess, actual) {
+        (Some(guess), Some(actual)) => {
+            let guess = guess.trim();
+            if guess.eq_ignore_ascii_case(actual) {
+                GuessOutcome::Correct
+            } else {
+                GuessOutcome::Incorrect
+            }
+        }
+        // nothing to find, so leaving it empty is right
+        (None, None) => GuessOutcome::Correct,
+        _ => GuessOutcome::Incorrect,
+    }
+}
+
+// every property correct is a correct answer, at least half of them correct or close is close
+pub fn score_contents_guess(
+    guess: &ContentsGuess,
+    actual: &FolderContents,
+    close_sensitivity: f64,
+) -> ContentsScore {
+    let file_count = score_number(guess.file_count, actual.file_count, close_sensitivity);
+    let total_size = score_number(guess.total_size, actual.total_size, close_sensitivity);
+    // "rs" and ".rs" are the same extension, but a dotfile's name keeps its dot
+    let dominant_extension = score_name(
+        guess
+            .dominant_extension
+            .as_deref()
+            .map(|extension| extension.trim().trim_start_matches('.')),
+        actual.dominant_extension.as_deref(),
+    );
+    let newest_file = score_name(guess.newest_file.as_deref(), actual.newest_file.as_deref());
+
+    let properties = [file_count, total_size, dominant_extension, newest_file];
+    let correct = properties
+        .iter()
+        .filter(|p| **p == GuessOutcome::Correct)
+        .count();
+    let close = properties
+        .iter()
+        .filter(|p| **p == GuessOutcome::Close)
+        .count();
+    let outcome = if correct == properties.len() {
+        GuessOutcome::Correct
+    } else if (correct + close) * 2 >= properties.len() {
+        GuessOutcome::Close
+    } else {
+        GuessOutcome::Incorrect
+    };
+
+    ContentsScore {
+        outcome,
+        file_count,
+        total_size,
+        dominant_extension,
+        newest_file,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dotfiles_keep_their_dot() {
+        let actual = FolderContents {
+            file_count: 3,
+            total_size: 1200,
+            dominant_extension: Some("toml".to_string()),
+            newest_file: Some(".bashrc".to_string()),
+        };
+        let guess = |extension: &str, newest: &str| ContentsGuess {
+            dominant_extension: Some(extension.to_string()),
+            newest_file: Some(newest.to_string()),
+            ..Default::default()
+        };
+
+        let score = score_contents_guess(&guess(".toml", " .bashrc"), &actual, 50.0);
+        assert_eq!(score.dominant_extension, GuessOutcome::Correct);
+        assert_eq!(score.newest_file, GuessOutcome::Correct);
+
+        let score = score_contents_guess(&guess("toml", "bashrc"), &actual, 50.0);
+        assert_eq!(score.dominant_extension, GuessOutcome::Correct);
+        assert_eq!(score.newest_file, GuessOutcome::Incorrect);
+    }
+}
@@ -1,15 +1,27 @@
+#[cfg(target_os = "windows")]
 use regex::Regex;
-use rusqlite::{Connection, Result as RusqliteResult};
+use rusqlite::{Connection, OptionalExtension, Result as RusqliteResult};
 use sha2::{Digest, Sha256};
+#[cfg(target_os = "windows")]
 use std::os::windows::ffi::OsStrExt;
 use std::path::Path;
+#[cfg(target_os = "windows")]
 use std::ptr;
 use std::{fs::create_dir_all, path::PathBuf};
 use std::collections::HashMap;
+// the volume and attribute checks only exist on windows, everything else builds anywhere
+#[cfg(target_os = "windows")]
 use winapi::um::fileapi::GetVolumeInformationW;
+#[cfg(target_os = "windows")]
 use winapi::um::winnt;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub mod archive;
+pub mod compare;
+pub mod contents;
+pub mod search;
+pub mod source;
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct IndexOptions {
     pub path: String,
 
@@ -28,6 +40,11 @@ pub struct IndexOptions {
     pub exclude_temporary: Option<bool>, // e.g. %TEMP%, etc.
     pub exclude_empty: Option<bool>,  // empty files/folders
     pub exclude_admin: Option<bool>,  // files not accessible by the current user
+
+    // zip/tar/tar.gz contents indexed as virtual folders, off unless asked for
+    pub index_archives: Option<bool>,
+    pub archive_max_size: Option<u64>,  // bytes, bigger archives aren't opened
+    pub archive_max_depth: Option<u32>, // archives inside archives, 1 = only the ones on disk
 }
 
 pub fn is_ntfs(path: &Path) -> bool {
@@ -72,10 +89,28 @@ pub fn is_ntfs(path: &Path) -> bool {
     }
     #[cfg(not(target_os = "windows"))]
     {
+        let _ = path;
         false
     }
 }
 
+// same identifier tauri keys its app data dir on, so the app and sidecar agree by default
+pub const APP_IDENTIFIER: &str = "me.jovannmc.guessfs";
+pub const DATA_DIR_ENV: &str = "GUESSFS_DATA_DIR";
+
+// where indexes, stats and presets live: an explicit dir (--data-dir) wins, then
+// $GUESSFS_DATA_DIR, then the platform data dir
+pub fn data_dir(explicit: Option<&Path>) -> Result<PathBuf, String> {
+    if let Some(dir) = explicit {
+        return Ok(dir.to_path_buf());
+    }
+    if let Some(dir) = std::env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    let base_dirs = directories::BaseDirs::new().ok_or("Could not get base dirs".to_string())?;
+    Ok(base_dirs.data_dir().join(APP_IDENTIFIER))
+}
+
 pub fn get_index_db_path(
     app_data_dir: &Path,
     directory_path_str: &str,
@@ -97,7 +132,9 @@ pub fn init_db(conn: &Connection) -> RusqliteResult<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS folders (
             id INTEGER PRIMARY KEY,
-            path TEXT UNIQUE NOT NULL
+            path TEXT UNIQUE NOT NULL,
+            parent_id INTEGER,
+            FOREIGN KEY (parent_id) REFERENCES folders(id)
         )",
         [],
     )?;
@@ -106,17 +143,324 @@ pub fn init_db(conn: &Connection) -> RusqliteResult<()> {
             id INTEGER PRIMARY KEY,
             path TEXT UNIQUE NOT NULL,
             folder_id INTEGER NOT NULL,
+            size INTEGER,
+            modified INTEGER,
+            extension TEXT,
             FOREIGN KEY (folder_id) REFERENCES folders(id)
         )",
         [],
     )?;
+    // indexes made before folders knew their parent
+    let migrate_parents = !has_column(conn, "folders", "parent_id")?;
+    if migrate_parents {
+        conn.execute("ALTER TABLE folders ADD COLUMN parent_id INTEGER", [])?;
+    }
+    // file metadata gets filled in on the next index, the sink updates rows that are already there
+    for (column, definition) in [
+        ("size", "INTEGER"),
+        ("modified", "INTEGER"),
+        ("extension", "TEXT"),
+    ] {
+        if !has_column(conn, "files", column)? {
+            conn.execute(
+                &format!("ALTER TABLE files ADD COLUMN {column} {definition}"),
+                [],
+            )?;
+        }
+    }
+    // MFT file record numbers, only known for NTFS indexes, used to follow the USN journal
+    for table in ["folders", "files"] {
+        if !has_column(conn, table, "frn")? {
+            conn.execute(&format!("ALTER TABLE {table} ADD COLUMN frn INTEGER"), [])?;
+        }
+        // entries listed from inside a zip/tar rather than found on disk
+        if !has_column(conn, table, "in_archive")? {
+            conn.execute(
+                &format!("ALTER TABLE {table} ADD COLUMN in_archive INTEGER NOT NULL DEFAULT 0"),
+                [],
+            )?;
+        }
+    }
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS index_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_folder_path ON folders (path);",
         [],
     )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_folder_parent_id ON folders (parent_id);",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_file_folder_id ON files (folder_id);",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_folder_frn ON folders (frn);", [])?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_file_frn ON files (frn);", [])?;
+    if migrate_parents {
+        resolve_parent_ids(conn)?;
+    }
+    search::init_search(conn)?;
+    contents::init_folder_stats(conn)?;
+    compare::init_entry_metadata(conn)?;
+    // indexes from before depths were stored have none, depth filters would match nothing
+    let missing_depth: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM folders WHERE depth IS NULL)",
+        [],
+        |row| row.get(0),
+    )?;
+    if missing_depth {
+        compare::build_entry_metadata(conn)?;
+    }
+    Ok(())
+}
+
+pub fn has_column(conn: &Connection, table: &str, column: &str) -> RusqliteResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+pub fn get_index_meta(conn: &Connection, key: &str) -> RusqliteResult<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM index_meta WHERE key = ?1",
+        rusqlite::params![key],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub fn set_index_meta(conn: &Connection, key: &str, value: &str) -> RusqliteResult<()> {
+    conn.execute(
+        "INSERT INTO index_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        rusqlite::params![key, value],
+    )?;
+    Ok(())
+}
+
+// drop every entry before a full rescan, indexing only ever adds so stale entries would stay otherwise
+pub fn clear_index(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("DELETE FROM files", [])?;
+    conn.execute("DELETE FROM folders", [])?;
+    conn.execute("DELETE FROM index_meta", [])?;
     Ok(())
 }
 
+// fill in parent_id for folders that were inserted before their parent (or before the column existed)
+// the index root keeps a NULL parent
+pub fn resolve_parent_ids(conn: &Connection) -> RusqliteResult<usize> {
+    let orphans = conn
+        .prepare("SELECT id, path FROM folders WHERE parent_id IS NULL")?
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<RusqliteResult<Vec<_>>>()?;
+
+    let mut find_stmt = conn.prepare("SELECT id FROM folders WHERE path = ?1")?;
+    let mut update_stmt = conn.prepare("UPDATE folders SET parent_id = ?1 WHERE id = ?2")?;
+    let mut resolved = 0;
+    for (id, path) in orphans {
+        let Some(parent) = Path::new(&path).parent().and_then(|p| p.to_str()) else {
+            continue;
+        };
+        if let Some(parent_id) = find_stmt
+            .query_row(rusqlite::params![parent], |row| row.get::<_, i64>(0))
+            .optional()?
+        {
+            update_stmt.execute(rusqlite::params![parent_id, id])?;
+            resolved += 1;
+        }
+    }
+    Ok(resolved)
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChildSort {
+    #[default]
+    NameAsc,
+    NameDesc,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListOptions {
+    pub offset: Option<u32>,
+    pub limit: Option<u32>,
+    pub sort: Option<ChildSort>,
+    pub filter: Option<String>, // case-insensitive substring of the entry name
+    pub folders_first: Option<bool>,
+    pub include_files: Option<bool>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChildEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub in_archive: bool, // listed from inside a zip/tar, can't be opened on disk
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChildrenPage {
+    pub folder: String,
+    pub parent: Option<String>, // None at the index root
+    pub total: u64,             // entries matching the filter, ignoring offset/limit
+    pub entries: Vec<ChildEntry>,
+}
+
+// list a folder's children straight from the index, the folder doesn't need to exist on disk anymore
+pub fn list_children(
+    conn: &Connection,
+    folder: &str,
+    options: &ListOptions,
+) -> Result<ChildrenPage, String> {
+    let (folder_id, parent_id): (i64, Option<i64>) = conn
+        .query_row(
+            "SELECT id, parent_id FROM folders WHERE path = ?1",
+            rusqlite::params![folder],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to find folder: {}", e))?
+        .ok_or(format!("Folder not in index: {}", folder))?;
+    let parent = match parent_id {
+        Some(id) => Some(
+            conn.query_row(
+                "SELECT path FROM folders WHERE id = ?1",
+                rusqlite::params![id],
+                |row| row.get::<_, String>(0),
+            )
+            .map_err(|e| format!("Failed to find parent folder: {}", e))?,
+        ),
+        None => None,
+    };
+
+    // children are "<folder><separator><name>", substr is 1-based
+    let name_start = if folder.ends_with(['\\', '/']) {
+        folder.chars().count() + 1
+    } else {
+        folder.chars().count() + 2
+    };
+    let pattern = format!(
+        "%{}%",
+        options
+            .filter
+            .as_deref()
+            .unwrap_or("")
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    let include_files = if options.include_files.unwrap_or(true) { 1 } else { 0 };
+
+    let children = "SELECT path, 1 AS is_dir, in_archive FROM folders
+            WHERE parent_id = ?1 AND substr(path, ?2) LIKE ?3 ESCAPE '\\'
+        UNION ALL
+        SELECT path, 0 AS is_dir, in_archive FROM files
+            WHERE ?4 = 1 AND folder_id = ?1 AND substr(path, ?2) LIKE ?3 ESCAPE '\\'";
+
+    let total: u64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM ({children})"),
+            rusqlite::params![folder_id, name_start, pattern, include_files],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to count children: {}", e))?;
+
+    let direction = match options.sort.unwrap_or_default() {
+        ChildSort::NameAsc => "ASC",
+        ChildSort::NameDesc => "DESC",
+    };
+    let folders_first = if options.folders_first.unwrap_or(true) {
+        "is_dir DESC, "
+    } else {
+        ""
+    };
+    let mut stmt = conn
+        .prepare(&format!(
+            "{children} ORDER BY {folders_first}path COLLATE NOCASE {direction} LIMIT ?5 OFFSET ?6"
+        ))
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let entries = stmt
+        .query_map(
+            rusqlite::params![
+                folder_id,
+                name_start,
+                pattern,
+                include_files,
+                options.limit.unwrap_or(100),
+                options.offset.unwrap_or(0)
+            ],
+            |row| {
+                let path: String = row.get(0)?;
+                let name = Path::new(&path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.clone());
+                Ok(ChildEntry {
+                    name,
+                    path,
+                    is_dir: row.get(1)?,
+                    in_archive: row.get(2)?,
+                })
+            },
+        )
+        .map_err(|e| format!("Failed to execute query: {}", e))?
+        .collect::<RusqliteResult<Vec<_>>>()
+        .map_err(|e| format!("Failed to get row: {}", e))?;
+
+    Ok(ChildrenPage {
+        folder: folder.to_string(),
+        parent,
+        total,
+        entries,
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FileMetadata {
+    pub size: Option<u64>,
+    pub modified: Option<i64>, // unix seconds
+    pub extension: Option<String>, // lowercase, without the dot
+}
+
+pub fn extension_of(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
+
+pub fn file_metadata(path: &Path) -> FileMetadata {
+    let extension = extension_of(path);
+    match path.metadata() {
+        Ok(metadata) => FileMetadata {
+            size: Some(metadata.len()),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64),
+            extension,
+        },
+        Err(_) => FileMetadata {
+            extension,
+            ..Default::default()
+        },
+    }
+}
+
 pub fn get_drive_letter(path_string: String) -> char {
     path_string
         .chars()
@@ -125,10 +469,13 @@ pub fn get_drive_letter(path_string: String) -> char {
         .expect("No drive letter found in path string")
 }
 
+// `on_disk` is false for paths that only name something elsewhere (a remote, a listing), those
+// only get the checks that look at the path itself
 pub fn should_exclude(
     path: &Path,
     options: IndexOptions,
     exclude_counts: &mut HashMap<&'static str, usize>,
+    on_disk: bool,
 ) -> bool {
     // windows specific checks
     #[cfg(target_os = "windows")]
@@ -137,7 +484,7 @@ pub fn should_exclude(
         use std::os::windows::fs::MetadataExt;
 
         // check if the user can access the file/folder
-        if options.exclude_admin == Some(true) {
+        if on_disk && options.exclude_admin == Some(true) {
             let can_access = if path.is_dir() {
                 path.read_dir().is_ok()
             } else {
@@ -183,7 +530,7 @@ pub fn should_exclude(
             }
         }
 
-        if let Ok(metadata) = path.metadata() {
+        if on_disk && let Ok(metadata) = path.metadata() {
             let attributes = metadata.file_attributes();
 
             // hidden files and folders
@@ -257,6 +604,7 @@ pub fn should_exclude(
     // linux/macOS specific checks
     #[cfg(not(target_os = "windows"))]
     {
+        let _ = on_disk;
         let path = Path::new(&path);
         if options.exclude_hidden == Some(true) {
             if let Some(name) = path.file_name() {
@@ -269,3 +617,44 @@ pub fn should_exclude(
         false
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuessOutcome {
+    Correct,
+    Close,
+    Incorrect,
+}
+
+// compare a guess to the answer component by component
+// close_sensitivity is the percentage of the answer's components the guess has to share (from the root) to count as "close"
+pub fn score_guess(guess: &str, answer: &str, close_sensitivity: f64) -> GuessOutcome {
+    let guess_components: Vec<String> = Path::new(guess.trim())
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+        .collect();
+    let answer_components: Vec<String> = Path::new(answer)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+        .collect();
+
+    if guess_components == answer_components {
+        return GuessOutcome::Correct;
+    }
+
+    let shared = guess_components
+        .iter()
+        .zip(answer_components.iter())
+        .take_while(|(g, a)| g == a)
+        .count();
+    if answer_components.is_empty() || shared == 0 {
+        return GuessOutcome::Incorrect;
+    }
+
+    let ratio = shared as f64 / answer_components.len() as f64 * 100.0;
+    if ratio >= close_sensitivity {
+        GuessOutcome::Close
+    } else {
+        GuessOutcome::Incorrect
+    }
+}
@@ -0,0 +1,305 @@
+use rusqlite::{Connection, OptionalExtension, Result as RusqliteResult};
+use std::collections::HashSet;
+use std::path::Path;
+
+// how many candidates FTS hands over for ranking, keeps each keystroke cheap on huge indexes
+const MAX_CANDIDATES: u32 = 300;
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchOptions {
+    pub limit: Option<u32>,
+    pub include_folders: Option<bool>,
+    pub include_files: Option<bool>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub path: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub in_archive: bool,
+    pub score: i64,
+}
+
+pub fn init_search(conn: &Connection) -> RusqliteResult<()> {
+    // trigram tokens give us substring matching, and partial matches for typos
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS search USING fts5(
+            path,
+            is_dir UNINDEXED,
+            tokenize = 'trigram'
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// rebuild the search table from folders/files, run at the end of indexing
+pub fn build_search_index(conn: &Connection) -> RusqliteResult<usize> {
+    init_search(conn)?;
+    conn.execute("DELETE FROM search", [])?;
+    conn.execute(
+        "INSERT INTO search (path, is_dir)
+            SELECT path, 1 FROM folders
+            UNION ALL
+            SELECT path, 0 FROM files",
+        [],
+    )
+}
+
+pub fn search_paths(
+    conn: &Connection,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<Vec<SearchResult>, String> {
+    let segments = split_segments(query);
+    if segments.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let include_folders = options.include_folders.unwrap_or(true);
+    let include_files = options.include_files.unwrap_or(true);
+    let kind_filter = match (include_folders, include_files) {
+        (true, true) => "",
+        (true, false) => "AND is_dir = 1",
+        (false, true) => "AND is_dir = 0",
+        (false, false) => return Ok(Vec::new()),
+    };
+
+    // every trigram of the query, ranked by bm25
+    let trigrams: Vec<String> = segments
+        .iter()
+        .flat_map(|segment| {
+            let chars: Vec<char> = segment.chars().collect();
+            chars
+                .windows(3)
+                .map(|w| format!("\"{}\"", w.iter().collect::<String>().replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let limit = options.limit.unwrap_or(20);
+
+    let candidates: Vec<(String, bool)> = if trigrams.is_empty() {
+        // too short for trigrams, fall back to a plain scan
+        let pattern = format!(
+            "%{}%",
+            segments
+                .last()
+                .unwrap()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT path, is_dir FROM search WHERE path LIKE ?1 ESCAPE '\\' {kind_filter} LIMIT ?2"
+            ))
+            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        stmt.query_map(rusqlite::params![pattern, MAX_CANDIDATES], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| format!("Failed to execute query: {}", e))?
+        .collect::<RusqliteResult<Vec<_>>>()
+        .map_err(|e| format!("Failed to get row: {}", e))?
+    } else {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT path, is_dir FROM search WHERE search MATCH ?1 {kind_filter}
+                 ORDER BY rank LIMIT ?2"
+            ))
+            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        let mut query = |expression: String| {
+            stmt.query_map(rusqlite::params![expression, MAX_CANDIDATES], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?))
+            })
+            .map_err(|e| format!("Failed to execute query: {}", e))?
+            .collect::<RusqliteResult<Vec<_>>>()
+            .map_err(|e| format!("Failed to get row: {}", e))
+        };
+
+        // paths with every trigram first, a typo breaks a few of them so only then settle for any
+        let mut candidates = query(trigrams.join(" AND "))?;
+        if candidates.len() < limit as usize {
+            let found: HashSet<String> = candidates.iter().map(|(path, _)| path.clone()).collect();
+            candidates.extend(
+                query(trigrams.join(" OR "))?
+                    .into_iter()
+                    .filter(|(path, _)| !found.contains(path)),
+            );
+        }
+        candidates
+    };
+
+    let mut results: Vec<SearchResult> = candidates
+        .into_iter()
+        .filter_map(|(path, is_dir)| {
+            let score = fuzzy_score(&segments, &path)?;
+            let name = Path::new(&path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.clone());
+            Some(SearchResult {
+                path,
+                name,
+                is_dir,
+                in_archive: false,
+                score,
+            })
+        })
+        .collect();
+    results.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+    results.truncate(limit as usize);
+
+    // the search table only has paths, the flag comes from the entry itself
+    for result in &mut results {
+        let table = if result.is_dir { "folders" } else { "files" };
+        result.in_archive = conn
+            .query_row(
+                &format!("SELECT in_archive FROM {table} WHERE path = ?1"),
+                rusqlite::params![result.path],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get row: {}", e))?
+            .unwrap_or(false);
+    }
+    Ok(results)
+}
+
+fn split_segments(query: &str) -> Vec<String> {
+    query
+        .split(['\\', '/'])
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+// score how well the query segments match the path's components
+// segments have to match components in order, the last one has to match the entry name itself
+// returns None when the path doesn't match at all
+pub fn fuzzy_score(segments: &[String], path: &str) -> Option<i64> {
+    let components: Vec<String> = path
+        .split(['\\', '/'])
+        .filter(|c| !c.is_empty())
+        .map(|c| c.to_lowercase())
+        .collect();
+    let (last_segment, parent_segments) = segments.split_last()?;
+    let (name, parents) = components.split_last()?;
+
+    // the name is what the player is typing, so it counts double
+    let mut score = segment_score(last_segment, name)? * 2;
+
+    // earlier segments can skip components, but not go backwards
+    let mut position = 0;
+    for segment in parent_segments {
+        let (offset, matched) = parents[position..]
+            .iter()
+            .enumerate()
+            .find_map(|(i, component)| segment_score(segment, component).map(|s| (i, s)))?;
+        score += matched;
+        position += offset + 1;
+    }
+
+    // prefer shallower paths when everything else is equal
+    Some(score - components.len() as i64)
+}
+
+fn segment_score(segment: &str, component: &str) -> Option<i64> {
+    if component == segment {
+        Some(100)
+    } else if component.starts_with(segment) {
+        Some(80)
+    } else if component.contains(segment) {
+        Some(60)
+    } else if is_subsequence(segment, component) {
+        Some(40)
+    } else {
+        // allow a typo per 4 characters typed, compared against the start of the component
+        let max_typos = segment.chars().count() / 4;
+        if max_typos == 0 {
+            return None;
+        }
+        let prefix: String = component.chars().take(segment.chars().count()).collect();
+        let distance = edit_distance(segment, &prefix);
+        (distance <= max_typos).then(|| (30 - 10 * distance as i64).max(5))
+    }
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current[j + 1] = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // decoys are "document" with a dash breaking two of its trigrams, short enough to beat
+    // the long real path in bm25
+    fn decoy_index(decoys: usize) -> (Connection, String) {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_db(&conn).unwrap();
+        conn.execute("INSERT INTO folders (path) VALUES ('/index')", [])
+            .unwrap();
+        let folder_id = conn.last_insert_rowid();
+        let target = format!("/index/{}/document.txt", "archive-".repeat(20));
+        let mut paths: Vec<String> = (0..decoys)
+            .map(|i| {
+                let split = 2 + i % 5;
+                format!(
+                    "/index/{}-{}-{i}.txt",
+                    &"document"[..split],
+                    &"document"[split..]
+                )
+            })
+            .collect();
+        paths.push(target.clone());
+        for path in paths {
+            conn.execute(
+                "INSERT INTO files (path, folder_id) VALUES (?1, ?2)",
+                rusqlite::params![path, folder_id],
+            )
+            .unwrap();
+        }
+        build_search_index(&conn).unwrap();
+        (conn, target)
+    }
+
+    fn search(conn: &Connection, query: &str) -> Vec<String> {
+        search_paths(conn, query, &SearchOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|result| result.path)
+            .collect()
+    }
+
+    #[test]
+    fn every_trigram_has_to_match() {
+        let (conn, target) = decoy_index(400);
+        assert_eq!(search(&conn, "document").first(), Some(&target));
+    }
+
+    #[test]
+    fn typos_fall_back_to_any_trigram() {
+        let (conn, target) = decoy_index(5);
+        assert!(search(&conn, "documnet").contains(&target));
+    }
+}
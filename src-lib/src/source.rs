@@ -0,0 +1,435 @@
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::archive::{ArchiveLimits, archive_kind, list_archive};
+use crate::{FileMetadata, IndexOptions, should_exclude};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Folder,
+    File,
+}
+
+// one folder or file as a source sees it, before any exclusion
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    pub parent: Option<PathBuf>, // None for the index root
+    pub metadata: FileMetadata,  // files only
+    pub frn: Option<u64>,        // NTFS file record number, for following the USN journal
+    pub in_archive: bool,        // listed from inside a zip/tar, not on disk
+}
+
+impl IndexEntry {
+    pub fn root(path: &Path) -> Self {
+        IndexEntry {
+            path: path.to_path_buf(),
+            kind: EntryKind::Folder,
+            parent: None,
+            metadata: FileMetadata::default(),
+            frn: None,
+            in_archive: false,
+        }
+    }
+
+    pub fn folder(path: PathBuf) -> Self {
+        IndexEntry {
+            parent: path.parent().map(Path::to_path_buf),
+            path,
+            kind: EntryKind::Folder,
+            metadata: FileMetadata::default(),
+            frn: None,
+            in_archive: false,
+        }
+    }
+
+    pub fn file(path: PathBuf, metadata: FileMetadata) -> Self {
+        IndexEntry {
+            parent: path.parent().map(Path::to_path_buf),
+            path,
+            kind: EntryKind::File,
+            metadata,
+            frn: None,
+            in_archive: false,
+        }
+    }
+
+    pub fn with_frn(mut self, frn: u64) -> Self {
+        self.frn = Some(frn);
+        self
+    }
+}
+
+// where the entries of an index come from: a tree walk, the MFT, ext4 inodes...
+// parents should come before their children, the sink creates any that don't
+pub trait IndexSource {
+    fn name(&self) -> &'static str;
+    fn entries(&mut self) -> Result<Box<dyn Iterator<Item = IndexEntry> + '_>, String>;
+    // false when the paths aren't on this machine, exclusion then can't look at the disk
+    fn on_disk(&self) -> bool {
+        true
+    }
+}
+
+// a source that hands out a fixed list, so a sink can be filled without a disk
+pub struct VecSource(pub Vec<IndexEntry>);
+
+impl IndexSource for VecSource {
+    fn name(&self) -> &'static str {
+        "vec"
+    }
+
+    fn entries(&mut self) -> Result<Box<dyn Iterator<Item = IndexEntry> + '_>, String> {
+        Ok(Box::new(self.0.drain(..)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SinkCounts {
+    pub folders_found: usize,
+    pub files_found: usize,
+    pub ignored: usize,
+    pub exists: usize, // already in the index
+}
+
+// the one place entries get excluded and written, whatever source they came from
+pub struct IndexSink<'a> {
+    conn: &'a Connection,
+    options: &'a IndexOptions,
+    pub counts: SinkCounts,
+    pub exclude_counts: HashMap<&'static str, usize>,
+    // build a map to query instead of querying the DB each time
+    folder_map: HashMap<PathBuf, i64>,
+    // anything below an excluded folder is excluded too
+    excluded: HashSet<PathBuf>,
+    on_disk: bool, // from the source being written
+}
+
+impl<'a> IndexSink<'a> {
+    pub fn new(conn: &'a Connection, options: &'a IndexOptions) -> Self {
+        IndexSink {
+            conn,
+            options,
+            counts: SinkCounts::default(),
+            exclude_counts: HashMap::new(),
+            folder_map: HashMap::new(),
+            excluded: HashSet::new(),
+            on_disk: true,
+        }
+    }
+
+    pub fn write_all(&mut self, source: &mut dyn IndexSource) -> Result<SinkCounts, String> {
+        self.on_disk = source.on_disk();
+        for entry in source.entries()? {
+            self.write(entry)?;
+        }
+        Ok(self.counts)
+    }
+
+    pub fn write(&mut self, entry: IndexEntry) -> Result<(), String> {
+        if self.is_excluded(&entry.path, entry.parent.as_deref(), entry.in_archive) {
+            self.counts.ignored += 1;
+            if entry.kind == EntryKind::Folder {
+                self.excluded.insert(entry.path);
+            }
+            return Ok(());
+        }
+        let Some(path) = entry.path.to_str() else {
+            eprintln!("Path not UTF-8: {:?}", entry.path);
+            return Ok(());
+        };
+
+        // an opened archive is written as the folder of its contents instead of a file
+        if entry.kind == EntryKind::File
+            && !entry.in_archive
+            && self.options.index_archives == Some(true)
+            && let Some(entries) = self.archive_entries(&entry)
+        {
+            for entry in entries {
+                self.write(entry)?;
+            }
+            return Ok(());
+        }
+
+        match entry.kind {
+            EntryKind::Folder => {
+                self.insert_folder(&entry.path, entry.frn, entry.in_archive)?;
+            }
+            // if user wants to index files, insert them too
+            EntryKind::File if self.options.index_files => {
+                let Some(parent) = entry.parent.as_deref() else {
+                    eprintln!("No parent folder for file: {path}");
+                    return Ok(());
+                };
+                let Some(folder_id) = self.folder_id(parent, entry.in_archive)? else {
+                    self.counts.ignored += 1;
+                    return Ok(());
+                };
+                let exists = self
+                    .conn
+                    .prepare_cached("SELECT 1 FROM files WHERE path = ?1")
+                    .and_then(|mut stmt| stmt.exists(rusqlite::params![path]))
+                    .map_err(|e| format!("Failed to find file: {}", e))?;
+                // rows that are already there (older indexes, earlier runs) get the metadata and
+                // FRN this source knows, without losing what it doesn't
+                self.conn
+                    .prepare_cached(
+                        "INSERT INTO files (path, folder_id, size, modified, extension, frn, in_archive)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                         ON CONFLICT(path) DO UPDATE SET
+                            size = COALESCE(excluded.size, size),
+                            modified = COALESCE(excluded.modified, modified),
+                            extension = COALESCE(excluded.extension, extension),
+                            frn = COALESCE(excluded.frn, frn)",
+                    )
+                    .and_then(|mut stmt| {
+                        stmt.execute(rusqlite::params![
+                            path,
+                            folder_id,
+                            entry.metadata.size,
+                            entry.metadata.modified,
+                            entry.metadata.extension,
+                            entry.frn,
+                            entry.in_archive
+                        ])
+                    })
+                    .map_err(|e| format!("Failed to insert file: {}", e))?;
+                if exists {
+                    self.counts.exists += 1;
+                } else {
+                    self.counts.files_found += 1;
+                }
+            }
+            EntryKind::File => {}
+        }
+        Ok(())
+    }
+
+    fn is_excluded(&mut self, path: &Path, parent: Option<&Path>, in_archive: bool) -> bool {
+        let parent_excluded = parent.is_some_and(|parent| self.excluded.contains(parent));
+        // archive members aren't on disk for should_exclude to check, they go with their archive
+        parent_excluded
+            || (!in_archive
+                && should_exclude(
+                    path,
+                    self.options.clone(),
+                    &mut self.exclude_counts,
+                    self.on_disk,
+                ))
+    }
+
+    // the archive's contents when it should be opened, None to keep it a plain file
+    fn archive_entries(&self, entry: &IndexEntry) -> Option<Vec<IndexEntry>> {
+        archive_kind(&entry.path)?;
+        let limits = ArchiveLimits::from_options(self.options);
+        // tree walks without metadata don't know the size yet
+        let size = match entry.metadata.size {
+            Some(size) => size,
+            None => std::fs::metadata(&entry.path).ok()?.len(),
+        };
+        if size > limits.max_size {
+            return None;
+        }
+        list_archive(&entry.path, &limits)
+            .inspect_err(|e| eprintln!("Failed to list archive {}: {e}", entry.path.display()))
+            .ok()
+    }
+
+    fn insert_folder(
+        &mut self,
+        path: &Path,
+        frn: Option<u64>,
+        in_archive: bool,
+    ) -> Result<i64, String> {
+        let path_str = path.to_str().ok_or(format!("Path not UTF-8: {:?}", path))?;
+        let existing = self.find_folder(path_str)?;
+        // the FRN can have changed since an earlier run
+        self.conn
+            .prepare_cached(
+                "INSERT INTO folders (path, frn, in_archive) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(path) DO UPDATE SET frn = COALESCE(excluded.frn, frn)",
+            )
+            .and_then(|mut stmt| stmt.execute(rusqlite::params![path_str, frn, in_archive]))
+            .map_err(|e| format!("Failed to insert folder: {}", e))?;
+        let id = match existing {
+            Some(id) => {
+                self.counts.exists += 1;
+                id
+            }
+            None => {
+                self.counts.folders_found += 1;
+                self.conn.last_insert_rowid()
+            }
+        };
+        self.folder_map.insert(path.to_path_buf(), id);
+        Ok(id)
+    }
+
+    fn find_folder(&self, path: &str) -> Result<Option<i64>, String> {
+        self.conn
+            .prepare_cached("SELECT id FROM folders WHERE path = ?1")
+            .and_then(|mut stmt| {
+                stmt.query_row(rusqlite::params![path], |row| row.get(0))
+                    .optional()
+            })
+            .map_err(|e| format!("Failed to find folder: {}", e))
+    }
+
+    // from the map, then the DB (folders from earlier runs), and created when a source
+    // hands out a file before its folder, None when the folder is excluded
+    fn folder_id(&mut self, path: &Path, in_archive: bool) -> Result<Option<i64>, String> {
+        if let Some(id) = self.folder_map.get(path) {
+            return Ok(Some(*id));
+        }
+        if self.excluded.contains(path) {
+            return Ok(None);
+        }
+        let path_str = path.to_str().ok_or(format!("Path not UTF-8: {:?}", path))?;
+        if let Some(id) = self.find_folder(path_str)? {
+            self.folder_map.insert(path.to_path_buf(), id);
+            return Ok(Some(id));
+        }
+        // excluded like it would have been if the source had handed it out first
+        if self.is_excluded(path, path.parent(), in_archive) {
+            self.counts.ignored += 1;
+            self.excluded.insert(path.to_path_buf());
+            return Ok(None);
+        }
+        self.insert_folder(path, None, in_archive).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> IndexOptions {
+        IndexOptions {
+            path: "/index".to_string(),
+            index_directories: true,
+            index_files: true,
+            ..Default::default()
+        }
+    }
+
+    fn index_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_db(&conn).unwrap();
+        conn
+    }
+
+    fn write(conn: &Connection, options: &IndexOptions, entries: Vec<IndexEntry>) -> SinkCounts {
+        IndexSink::new(conn, options)
+            .write_all(&mut VecSource(entries))
+            .unwrap()
+    }
+
+    #[test]
+    fn reindexing_fills_in_missing_metadata() {
+        let conn = index_db();
+        let options = options();
+        let root = PathBuf::from("/index");
+        let file = root.join("notes.txt");
+        // like an index from before file metadata was stored
+        write(
+            &conn,
+            &options,
+            vec![
+                IndexEntry::root(&root),
+                IndexEntry::file(file.clone(), FileMetadata::default()),
+            ],
+        );
+
+        let metadata = FileMetadata {
+            size: Some(42),
+            modified: Some(1_700_000_000),
+            extension: Some("txt".to_string()),
+        };
+        let counts = write(
+            &conn,
+            &options,
+            vec![
+                IndexEntry::root(&root).with_frn(5),
+                IndexEntry::file(file, metadata).with_frn(7),
+            ],
+        );
+        assert_eq!(
+            (counts.exists, counts.files_found, counts.folders_found),
+            (2, 0, 0)
+        );
+
+        let row: (i64, i64, String, i64) = conn
+            .query_row(
+                "SELECT size, modified, extension, frn FROM files WHERE path = '/index/notes.txt'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(row, (42, 1_700_000_000, "txt".to_string(), 7));
+        let folder_frn: i64 = conn
+            .query_row("SELECT frn FROM folders WHERE path = '/index'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(folder_frn, 5);
+
+        // a source without metadata doesn't wipe what's there
+        write(
+            &conn,
+            &options,
+            vec![IndexEntry::file(
+                root.join("notes.txt"),
+                FileMetadata::default(),
+            )],
+        );
+        let size: i64 = conn
+            .query_row("SELECT size FROM files", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(size, 42);
+    }
+
+    #[test]
+    fn folders_created_for_files_are_excluded_too() {
+        let conn = index_db();
+        let root = PathBuf::from("/index");
+        let cache = root.join(".cache");
+        let mut options = options();
+        // hidden is only a name check off windows, the path prefix works on windows without a disk
+        options.exclude_hidden = Some(true);
+        options.excluded_paths = Some(vec![cache.to_string_lossy().to_string()]);
+
+        // like an MFT read in FRN order, the files come before their folders
+        let mut sink = IndexSink::new(&conn, &options);
+        let counts = sink
+            .write_all(&mut VecSource(vec![
+                IndexEntry::root(&root),
+                IndexEntry::file(cache.join("a.txt"), FileMetadata::default()),
+                IndexEntry::file(cache.join("b.txt"), FileMetadata::default()),
+                IndexEntry::folder(cache.join("sub")),
+                IndexEntry::file(cache.join("sub").join("c.txt"), FileMetadata::default()),
+                IndexEntry::file(root.join("keep").join("d.txt"), FileMetadata::default()),
+            ]))
+            .unwrap();
+        assert_eq!(counts.ignored, 5);
+        assert_eq!((counts.folders_found, counts.files_found), (2, 1));
+        // the folder was checked once, everything in it went with it
+        assert_eq!(sink.exclude_counts.values().sum::<usize>(), 1);
+
+        let paths = |table: &str| -> Vec<String> {
+            conn.prepare(&format!("SELECT path FROM {table} ORDER BY path"))
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .map(|path| path.unwrap())
+                .collect()
+        };
+        let display = |path: PathBuf| path.to_string_lossy().to_string();
+        assert_eq!(
+            paths("folders"),
+            [display(root.clone()), display(root.join("keep"))]
+        );
+        assert_eq!(paths("files"), [display(root.join("keep").join("d.txt"))]);
+    }
+}
@@ -0,0 +1,520 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use clap::builder::PossibleValue;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command, ValueEnum, value_parser};
+use src_lib::IndexOptions;
+
+use crate::ScanOptions;
+use crate::listing::ListingFormat;
+
+// what the process exits with, so scripts can tell why it failed without reading the message
+// 2 is left to clap, for bad arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    Failed = 1,
+    NoIndex = 3,
+    Database = 4,
+    Source = 5, // the path, volume, image, listing... couldn't be read
+    Verify = 6, // verify found problems in the index
+    Elevation = 7,
+}
+
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Failure {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::new(FailureKind::Failed, message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl ValueEnum for OutputFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[OutputFormat::Text, OutputFormat::Json]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            OutputFormat::Text => PossibleValue::new("text"),
+            OutputFormat::Json => {
+                PossibleValue::new("json").help("One JSON object on stdout, progress on stderr")
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexKind {
+    Files,
+    Dirs,
+}
+
+impl ValueEnum for IndexKind {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[IndexKind::Files, IndexKind::Dirs]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            IndexKind::Files => PossibleValue::new("files"),
+            IndexKind::Dirs => PossibleValue::new("dirs"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExcludeKind {
+    Empty,
+    Temp,
+    Hidden,
+    System,
+    Privileged,
+}
+
+impl ValueEnum for ExcludeKind {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            ExcludeKind::Empty,
+            ExcludeKind::Temp,
+            ExcludeKind::Hidden,
+            ExcludeKind::System,
+            ExcludeKind::Privileged,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            ExcludeKind::Empty => PossibleValue::new("empty").help("Empty files and folders"),
+            ExcludeKind::Temp => PossibleValue::new("temp").help("Temporary files and folders"),
+            ExcludeKind::Hidden => PossibleValue::new("hidden").help("Hidden files and folders"),
+            ExcludeKind::System => PossibleValue::new("system").help("Protected system files"),
+            ExcludeKind::Privileged => {
+                PossibleValue::new("privileged").help("Anything the current user can't open")
+            }
+        })
+    }
+}
+
+// folders or files, for the commands that pick from one of the two
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    Dir,
+    File,
+}
+
+impl EntryType {
+    pub fn table(self) -> &'static str {
+        match self {
+            EntryType::Dir => "folders",
+            EntryType::File => "files",
+        }
+    }
+}
+
+impl ValueEnum for EntryType {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[EntryType::Dir, EntryType::File]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            EntryType::Dir => PossibleValue::new("dir").alias("folder"),
+            EntryType::File => PossibleValue::new("file"),
+        })
+    }
+}
+
+impl ValueEnum for ListingFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            ListingFormat::Paths,
+            ListingFormat::Find,
+            ListingFormat::Csv,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            ListingFormat::Paths => PossibleValue::new("paths")
+                .alias("dir")
+                .help("One path per line (dir /s /b, find)"),
+            ListingFormat::Find => {
+                PossibleValue::new("find").help("find -printf '%y\\t%s\\t%T@\\t%p\\n'")
+            }
+            ListingFormat::Csv => PossibleValue::new("csv").help("path,type,size,modified columns"),
+        })
+    }
+}
+
+fn path_arg() -> Arg {
+    Arg::new("path")
+        .long("path")
+        .value_parser(value_parser!(String))
+        .help("The indexed path, which index to use")
+        .required(true)
+}
+
+fn list_arg(id: &'static str, long: &'static str, help: &'static str) -> Arg {
+    Arg::new(id)
+        .long(long)
+        .value_parser(value_parser!(String))
+        .value_delimiter(',')
+        .action(ArgAction::Append)
+        .help(help)
+}
+
+// what to keep, shared by everything that writes an index
+fn index_args() -> Vec<Arg> {
+    vec![
+        path_arg().help("Path to index"),
+        Arg::new("index")
+            .long("index")
+            .value_parser(value_parser!(IndexKind))
+            .value_delimiter(',')
+            .action(ArgAction::Append)
+            .help("Index files and/or directories (comma-separated)")
+            .required(true),
+        list_arg("types", "types", "File types to index (comma-separated)"),
+        Arg::new("exclude")
+            .long("exclude")
+            .value_parser(value_parser!(ExcludeKind))
+            .value_delimiter(',')
+            .action(ArgAction::Append)
+            .help("Exclude common unwanted files (comma-separated)"),
+        Arg::new("exclude_regex")
+            .long("exclude-regex")
+            .value_parser(value_parser!(String))
+            .help("Exclude files or directories matching this regex pattern"),
+        list_arg(
+            "exclude_paths",
+            "exclude-paths",
+            "Exclude specific paths (comma-separated)",
+        ),
+        list_arg(
+            "exclude_files",
+            "exclude-files",
+            "Exclude specific files (comma-separated)",
+        ),
+        Arg::new("archives")
+            .long("archives")
+            .help("List the contents of zip/tar/tar.gz archives as virtual folders")
+            .action(ArgAction::SetTrue),
+        Arg::new("archive_max_size")
+            .long("archive-max-size")
+            .value_parser(value_parser!(u64))
+            .help("Don't open archives bigger than this many bytes")
+            .requires("archives"),
+        Arg::new("archive_max_depth")
+            .long("archive-max-depth")
+            .value_parser(value_parser!(u32))
+            .help("How many archives deep to look inside archives (1 = only archives on disk)")
+            .requires("archives"),
+        Arg::new("elevate")
+            .long("elevate")
+            .help("Run again as administrator, for reading the MFT")
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+pub fn build() -> Command {
+    Command::new("Indexer")
+        .version("1.0")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(Arg::new("data_dir").long("data-dir").value_parser(value_parser!(PathBuf)).help("Where the indexes are kept [default: $GUESSFS_DATA_DIR, then the platform data dir]").global(true))
+        .arg(Arg::new("output").long("output").value_parser(value_parser!(OutputFormat)).default_value("text").help("How to print the result").global(true))
+        .arg(Arg::new("quiet").long("quiet").short('q').help("Only print the result").action(ArgAction::SetTrue).global(true))
+        .subcommand(
+            Command::new("index")
+                .about("Index a path from scratch")
+                .args(index_args())
+                .arg(Arg::new("image").long("image").value_parser(value_parser!(String)).help("Read the MFT from this NTFS image file instead of the volume, paths are put under --path"))
+                .arg(Arg::new("legacy_mft").long("legacy-mft").help("Use the usn-journal-rs MFT reader (for comparing against the ntfs one)").action(ArgAction::SetTrue))
+                .arg(Arg::new("ext4").long("ext4").value_parser(value_parser!(String)).help("Read ext2/3/4 metadata from this block device or image file instead of walking the tree").conflicts_with("image"))
+                .arg(Arg::new("mount_point").long("mount-point").value_parser(value_parser!(String)).help("Where the --ext4 filesystem is mounted, to index a directory below it (defaults to --path)").requires("ext4"))
+                .arg(Arg::new("remote").long("remote").value_parser(value_parser!(String)).help("List this url through a remote client instead of walking --path, rows are put under --path (file:// only for now)").conflicts_with_all(["image", "ext4"])),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Build the index from a path listing instead of the disk, paths are put under --path")
+                .args(index_args())
+                .arg(Arg::new("file").value_parser(value_parser!(PathBuf)).help("The listing file").required(true))
+                .arg(Arg::new("format").long("format").value_parser(value_parser!(ListingFormat)).default_value("paths").help("How the listing is written")),
+        )
+        .subcommand(
+            Command::new("update")
+                .about("Apply changes from the USN journal to an existing MFT index")
+                .args(index_args()),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("Keep an MFT index up to date from the USN journal until stopped")
+                .args(index_args())
+                .arg(Arg::new("interval").long("interval").value_parser(value_parser!(u64).range(1..)).default_value("30").help("Seconds between updates")),
+        )
+        .subcommand(
+            Command::new("query")
+                .about("Fuzzy search the indexed paths")
+                .arg(path_arg())
+                .arg(Arg::new("text").value_parser(value_parser!(String)).help("What to search for").required(true))
+                .arg(Arg::new("kind").long("kind").value_parser(value_parser!(EntryType)).help("Only folders or only files"))
+                .arg(Arg::new("limit").long("limit").value_parser(value_parser!(u32)).help("How many results to show")),
+        )
+        .subcommand(
+            Command::new("random")
+                .about("Pick random entries from an index")
+                .arg(path_arg())
+                .arg(Arg::new("kind").long("kind").value_parser(value_parser!(EntryType)).default_value("dir").help("Pick folders or files"))
+                .arg(Arg::new("count").long("count").short('n').value_parser(value_parser!(u32)).default_value("1").help("How many to pick")),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Show what's in an index")
+                .arg(path_arg()),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List a folder's children from an index")
+                .arg(path_arg())
+                .arg(Arg::new("folder").value_parser(value_parser!(String)).help("The folder to list (defaults to --path)"))
+                .arg(Arg::new("files").long("files").help("Include files").action(ArgAction::SetTrue))
+                .arg(Arg::new("filter").long("filter").value_parser(value_parser!(String)).help("Only names containing this"))
+                .arg(Arg::new("offset").long("offset").value_parser(value_parser!(u32)).help("Entries to skip"))
+                .arg(Arg::new("limit").long("limit").value_parser(value_parser!(u32)).help("Entries to show")),
+        )
+        .subcommand(
+            Command::new("delete")
+                .about("Delete the index of a path")
+                .arg(path_arg()),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write an index out as CSV, in the format import reads")
+                .arg(path_arg())
+                .arg(Arg::new("out").long("out").value_parser(value_parser!(PathBuf)).help("The CSV file to write").required(true)),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check an index for corruption and broken links")
+                .arg(path_arg())
+                .arg(Arg::new("disk").long("disk").help("Also check the indexed paths still exist").action(ArgAction::SetTrue)),
+        )
+}
+
+// the command line again from what was parsed, without the args in `skip`, for rerunning the
+// command (elevated) exactly as clap understood it
+pub fn to_args(matches: &ArgMatches, skip: &[&str]) -> Vec<OsString> {
+    let command = build();
+    let (name, sub) = matches.subcommand().unwrap();
+    let mut args = Vec::new();
+    push_args(&command, matches, skip, &mut args);
+    args.push(name.into());
+    push_args(command.find_subcommand(name).unwrap(), sub, skip, &mut args);
+    args
+}
+
+fn push_args(command: &Command, matches: &ArgMatches, skip: &[&str], args: &mut Vec<OsString>) {
+    let mut positionals = Vec::new();
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        // defaults come back on their own
+        if skip.contains(&id) || matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let Some(long) = arg.get_long() else {
+            positionals.extend(
+                matches
+                    .get_raw(id)
+                    .into_iter()
+                    .flatten()
+                    .map(OsString::from),
+            );
+            continue;
+        };
+        if matches!(arg.get_action(), ArgAction::SetTrue) {
+            args.push(format!("--{long}").into());
+            continue;
+        }
+        // `--long=value` keeps a value starting with a dash a value
+        for value in matches.get_raw(id).into_iter().flatten() {
+            let mut arg = OsString::from(format!("--{long}="));
+            arg.push(value);
+            args.push(arg);
+        }
+    }
+    // after `--` so a value starting with a dash isn't read as a flag
+    if !positionals.is_empty() {
+        args.push("--".into());
+        args.extend(positionals);
+    }
+}
+
+fn strings(matches: &ArgMatches, id: &str) -> Option<Vec<String>> {
+    matches
+        .get_many::<String>(id)
+        .map(|values| values.cloned().collect())
+}
+
+pub fn index_options(matches: &ArgMatches) -> IndexOptions {
+    let index: Vec<IndexKind> = matches
+        .get_many::<IndexKind>("index")
+        .into_iter()
+        .flatten()
+        .copied()
+        .collect();
+    let exclude: Vec<ExcludeKind> = matches
+        .get_many::<ExcludeKind>("exclude")
+        .into_iter()
+        .flatten()
+        .copied()
+        .collect();
+    IndexOptions {
+        path: matches.get_one::<String>("path").unwrap().to_string(),
+        index_directories: index.contains(&IndexKind::Dirs),
+        index_files: index.contains(&IndexKind::Files),
+        file_types: strings(matches, "types"),
+        excluded_regex: matches.get_one::<String>("exclude_regex").map(String::from),
+        excluded_paths: Some(strings(matches, "exclude_paths").unwrap_or_default()),
+        excluded_files: Some(strings(matches, "exclude_files").unwrap_or_default()),
+        exclude_hidden: Some(exclude.contains(&ExcludeKind::Hidden)),
+        exclude_system: Some(exclude.contains(&ExcludeKind::System)),
+        exclude_temporary: Some(exclude.contains(&ExcludeKind::Temp)),
+        exclude_empty: Some(exclude.contains(&ExcludeKind::Empty)),
+        exclude_admin: Some(exclude.contains(&ExcludeKind::Privileged)),
+        index_archives: Some(matches.get_flag("archives")),
+        archive_max_size: matches.get_one::<u64>("archive_max_size").copied(),
+        archive_max_depth: matches.get_one::<u32>("archive_max_depth").copied(),
+    }
+}
+
+// only index and import have a choice of source, everything else walks or reads the MFT
+pub fn scan_options(matches: &ArgMatches) -> ScanOptions {
+    let has = |id: &str| matches.try_get_one::<String>(id).ok().flatten().cloned();
+    ScanOptions {
+        image: has("image"),
+        ext4: has("ext4"),
+        mount_point: has("mount_point"),
+        legacy_mft: matches
+            .try_get_one::<bool>("legacy_mft")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false),
+        remote: has("remote"),
+        listing: matches
+            .try_get_one::<PathBuf>("file")
+            .ok()
+            .flatten()
+            .map(|file| {
+                (
+                    file.clone(),
+                    *matches.get_one::<ListingFormat>("format").unwrap(),
+                )
+            }),
+        replace: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> ArgMatches {
+        build()
+            .try_get_matches_from(std::iter::once("src-sidecar").chain(args.iter().copied()))
+            .unwrap()
+    }
+
+    fn reparse(matches: &ArgMatches, skip: &[&str]) -> ArgMatches {
+        let args = to_args(matches, skip);
+        build()
+            .try_get_matches_from(std::iter::once(OsString::from("src-sidecar")).chain(args))
+            .unwrap()
+    }
+
+    #[test]
+    fn parsed_args_come_back_the_same() {
+        let matches = parse(&[
+            "--quiet",
+            "index",
+            "--path=C:\\Users\\me",
+            "--index",
+            "files,dirs",
+            "--exclude",
+            "hidden",
+            "--exclude=temp,empty",
+            "--exclude-regex=-backup$",
+            "--exclude-paths",
+            "C:\\a b,C:\\c",
+            "--elevate",
+            "--output",
+            "json",
+        ]);
+        let again = reparse(&matches, &["elevate"]);
+        let (_, sub) = matches.subcommand().unwrap();
+        let (name, sub_again) = again.subcommand().unwrap();
+        assert_eq!(name, "index");
+        assert_eq!(
+            format!("{:?}", index_options(sub_again)),
+            format!("{:?}", index_options(sub))
+        );
+        assert!(again.get_flag("quiet"));
+        assert_eq!(
+            again.get_one::<OutputFormat>("output"),
+            Some(&OutputFormat::Json)
+        );
+        assert!(!sub_again.get_flag("elevate"));
+    }
+
+    #[test]
+    fn positionals_and_defaults() {
+        let matches = parse(&[
+            "import",
+            "--path",
+            "/x",
+            "--index",
+            "files",
+            "--",
+            "-listing.txt",
+        ]);
+        let args = to_args(&matches, &[]);
+        // the default --format isn't repeated, the file goes after `--`
+        assert!(!args.contains(&OsString::from("--format")));
+        assert_eq!(
+            args[args.len() - 2..],
+            ["--", "-listing.txt"].map(OsString::from)
+        );
+
+        let again = reparse(&matches, &[]);
+        let (_, sub) = again.subcommand().unwrap();
+        assert_eq!(
+            sub.get_one::<PathBuf>("file"),
+            Some(&PathBuf::from("-listing.txt"))
+        );
+        let skipped = to_args(
+            &parse(&["--data-dir", "/d", "stats", "--path", "/x"]),
+            &["data_dir"],
+        );
+        assert_eq!(skipped, ["stats", "--path=/x"].map(OsString::from));
+    }
+}
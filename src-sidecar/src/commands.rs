@@ -0,0 +1,297 @@
+use std::io::Write;
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+
+use clap::ArgMatches;
+use rusqlite::Connection;
+use serde_json::json;
+use src_lib::ListOptions;
+use src_lib::search::SearchOptions;
+
+use crate::Report;
+use crate::cli::{EntryType, Failure, FailureKind};
+
+// the commands that read (or drop) an existing index, as opposed to building one
+
+pub fn run(name: &str, matches: &ArgMatches, app_data_dir: &Path) -> Result<Report, Failure> {
+    let path = matches.get_one::<String>("path").unwrap();
+    let db_path = src_lib::get_index_db_path(app_data_dir, path)?;
+    if !db_path.exists() {
+        return Err(Failure::new(
+            FailureKind::NoIndex,
+            format!("No index found for: {}", path),
+        ));
+    }
+    if name == "delete" {
+        return delete(&db_path, path);
+    }
+
+    let db = Connection::open(&db_path).map_err(|e| {
+        Failure::new(
+            FailureKind::Database,
+            format!("Failed to open database: {}", e),
+        )
+    })?;
+    // older indexes get their missing tables and columns here
+    crate::init_db(&db)?;
+    match name {
+        "query" => query(&db, matches),
+        "random" => random(&db, matches),
+        "stats" => stats(&db, &db_path, path),
+        "list" => list(&db, matches, path),
+        "export" => export(&db, matches.get_one::<PathBuf>("out").unwrap()),
+        "verify" => verify(&db, path, matches.get_flag("disk")),
+        _ => unreachable!("unknown subcommand {}", name),
+    }
+}
+
+fn query(db: &Connection, matches: &ArgMatches) -> Result<Report, Failure> {
+    let kind = matches.get_one::<EntryType>("kind").copied();
+    let options = SearchOptions {
+        limit: matches.get_one::<u32>("limit").copied(),
+        include_folders: kind.map(|kind| kind == EntryType::Dir),
+        include_files: kind.map(|kind| kind == EntryType::File),
+    };
+    // indexes made before search existed get it built on first use
+    let is_empty: bool = db
+        .query_row("SELECT NOT EXISTS (SELECT 1 FROM search)", [], |row| {
+            row.get(0)
+        })
+        .map_err(|e| format!("Failed to check search index: {}", e))?;
+    if is_empty {
+        src_lib::search::build_search_index(db)
+            .map_err(|e| format!("Failed to build search index: {}", e))?;
+    }
+
+    let text = matches.get_one::<String>("text").unwrap();
+    let results = src_lib::search::search_paths(db, text, &options)?;
+    let message = results
+        .iter()
+        .map(|result| result.path.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(Report::new(message, json!({ "results": results })))
+}
+
+fn random(db: &Connection, matches: &ArgMatches) -> Result<Report, Failure> {
+    let kind = *matches.get_one::<EntryType>("kind").unwrap();
+    let count = *matches.get_one::<u32>("count").unwrap();
+    let paths = db
+        .prepare(&format!(
+            "SELECT path FROM {} ORDER BY RANDOM() LIMIT ?1",
+            kind.table()
+        ))
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params![count], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|e| format!("Failed to pick random paths: {}", e))?;
+    if paths.is_empty() {
+        return Err(format!("No {} in the index", kind.table()).into());
+    }
+    Ok(Report::new(paths.join("\n"), json!({ "paths": paths })))
+}
+
+fn stats(db: &Connection, db_path: &Path, path: &str) -> Result<Report, Failure> {
+    let count = |sql: &str| -> Result<i64, String> {
+        db.query_row(sql, [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read index stats: {}", e))
+    };
+    let folders = count("SELECT COUNT(*) FROM folders")?;
+    let files = count("SELECT COUNT(*) FROM files")?;
+    let total_size = count("SELECT COALESCE(SUM(size), 0) FROM files")?;
+    let in_archives = count(
+        "SELECT (SELECT COUNT(*) FROM folders WHERE in_archive)
+              + (SELECT COUNT(*) FROM files WHERE in_archive)",
+    )?;
+    let db_size = std::fs::metadata(db_path).map(|m| m.len()).unwrap_or(0);
+    let follows_journal = src_lib::get_index_meta(db, crate::usn::META_JOURNAL_ID)
+        .map_err(|e| format!("Failed to read index metadata: {}", e))?
+        .is_some();
+
+    Ok(Report::new(
+        format!(
+            "{}\n{} folders, {} files ({} bytes), {} inside archives\nindex: {} ({} bytes){}",
+            path,
+            folders,
+            files,
+            total_size,
+            in_archives,
+            db_path.display(),
+            db_size,
+            if follows_journal {
+                ", follows the USN journal"
+            } else {
+                ""
+            }
+        ),
+        json!({
+            "path": path,
+            "folders": folders,
+            "files": files,
+            "total_size": total_size,
+            "in_archives": in_archives,
+            "database": db_path,
+            "database_size": db_size,
+            "follows_journal": follows_journal,
+        }),
+    ))
+}
+
+fn list(db: &Connection, matches: &ArgMatches, path: &str) -> Result<Report, Failure> {
+    let folder = matches
+        .get_one::<String>("folder")
+        .map(String::as_str)
+        .unwrap_or(path);
+    let options = ListOptions {
+        offset: matches.get_one::<u32>("offset").copied(),
+        limit: matches.get_one::<u32>("limit").copied(),
+        filter: matches.get_one::<String>("filter").cloned(),
+        folders_first: Some(true),
+        include_files: Some(matches.get_flag("files")),
+        ..Default::default()
+    };
+    let page = src_lib::list_children(db, folder, &options)?;
+    // folders end with a separator so they stand out from files
+    let message = page
+        .entries
+        .iter()
+        .map(|entry| match entry.is_dir {
+            true => format!("{}{}", entry.name, MAIN_SEPARATOR),
+            false => entry.name.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(Report::new(message, json!(page)))
+}
+
+fn delete(db_path: &Path, path: &str) -> Result<Report, Failure> {
+    std::fs::remove_file(db_path).map_err(|e| {
+        Failure::new(
+            FailureKind::Database,
+            format!("Failed to delete index: {}", e),
+        )
+    })?;
+    // sqlite's side files, only there if it didn't shut down cleanly
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let mut side_file = db_path.as_os_str().to_owned();
+        side_file.push(suffix);
+        let _ = std::fs::remove_file(side_file);
+    }
+    Ok(Report::new(
+        format!("Deleted index for: {}", path),
+        json!({ "path": path, "database": db_path }),
+    ))
+}
+
+// same columns as `import --format csv` reads
+fn export(db: &Connection, out: &Path) -> Result<Report, Failure> {
+    let file = std::fs::File::create(out).map_err(|e| format!("Failed to create file: {}", e))?;
+    let mut writer = std::io::BufWriter::new(file);
+    let write_error = |e: std::io::Error| format!("Failed to write export: {}", e);
+    writeln!(writer, "path,type,size,modified").map_err(write_error)?;
+
+    let mut stmt = db
+        .prepare(
+            "SELECT path, 'dir', NULL, NULL FROM folders
+             UNION ALL
+             SELECT path, 'file', size, modified FROM files
+             ORDER BY path",
+        )
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+            ))
+        })
+        .map_err(|e| format!("Failed to execute query: {}", e))?;
+    let mut exported = 0;
+    for row in rows {
+        let (path, kind, size, modified) = row.map_err(|e| format!("Failed to read row: {}", e))?;
+        let number = |n: Option<i64>| n.map(|n| n.to_string()).unwrap_or_default();
+        writeln!(
+            writer,
+            "\"{}\",{},{},{}",
+            path.replace('"', "\"\""),
+            kind,
+            number(size),
+            number(modified)
+        )
+        .map_err(write_error)?;
+        exported += 1;
+    }
+    writer.flush().map_err(write_error)?;
+
+    Ok(Report::new(
+        format!("Exported {} entries to {}", exported, out.display()),
+        json!({ "exported": exported, "out": out }),
+    ))
+}
+
+fn verify(db: &Connection, path: &str, disk: bool) -> Result<Report, Failure> {
+    let integrity: String = db
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to check integrity: {}", e))?;
+    let count = |sql: &str, params: &[&dyn rusqlite::ToSql]| -> Result<i64, String> {
+        db.query_row(sql, params, |row| row.get(0))
+            .map_err(|e| format!("Failed to verify index: {}", e))
+    };
+    // every folder but the root should have been linked to its parent
+    let orphan_folders = count(
+        "SELECT COUNT(*) FROM folders
+         WHERE path != ?1 AND (parent_id IS NULL OR parent_id NOT IN (SELECT id FROM folders))",
+        &[&path],
+    )?;
+    let orphan_files = count(
+        "SELECT COUNT(*) FROM files WHERE folder_id NOT IN (SELECT id FROM folders)",
+        &[],
+    )?;
+
+    // entries from archives, listings or images aren't expected on this disk
+    let mut missing = Vec::new();
+    if disk {
+        let mut stmt = db
+            .prepare(
+                "SELECT path FROM folders WHERE NOT in_archive
+                 UNION ALL
+                 SELECT path FROM files WHERE NOT in_archive",
+            )
+            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        let paths = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to execute query: {}", e))?;
+        for entry in paths {
+            let entry = entry.map_err(|e| format!("Failed to read row: {}", e))?;
+            if !Path::new(&entry).exists() {
+                missing.push(entry);
+            }
+        }
+    }
+
+    let mut problems = Vec::new();
+    if integrity != "ok" {
+        problems.push(format!("database corrupted: {}", integrity));
+    }
+    if orphan_folders > 0 {
+        problems.push(format!("{} folders without a parent", orphan_folders));
+    }
+    if orphan_files > 0 {
+        problems.push(format!("{} files without a folder", orphan_files));
+    }
+    if !missing.is_empty() {
+        for entry in missing.iter().take(10) {
+            progress!("Missing: {}", entry);
+        }
+        problems.push(format!("{} paths no longer on disk", missing.len()));
+    }
+    if !problems.is_empty() {
+        return Err(Failure::new(FailureKind::Verify, problems.join(", ")));
+    }
+    Ok(Report::new(
+        format!("Index for {} is fine", path),
+        json!({ "path": path, "checked_disk": disk }),
+    ))
+}
@@ -0,0 +1,663 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// reads ext2/3/4 metadata straight off a block device or image, the Linux counterpart of mft.rs
+// the inode tables are read group by group in one pass, then only the directory blocks, so a
+// cold cache doesn't mean one seek per file like a tree walk does
+
+pub const ROOT_INO: u32 = 2;
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const EXT4_MAGIC: u16 = 0xEF53;
+
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+const INCOMPAT_64BIT: u32 = 0x0080;
+const RO_COMPAT_GDT_CSUM: u32 = 0x0010;
+const RO_COMPAT_METADATA_CSUM: u32 = 0x0400;
+
+const BG_INODE_UNINIT: u16 = 0x0001;
+const EXTENTS_FL: u32 = 0x0008_0000;
+const INLINE_DATA_FL: u32 = 0x1000_0000;
+const EXTENT_MAGIC: u16 = 0xF30A;
+// ext4 never builds extent trees deeper than this
+const MAX_EXTENT_DEPTH: u16 = 5;
+// extents longer than this are preallocated but unwritten, they read back as zeros
+const MAX_INIT_EXTENT_LEN: u16 = 32_768;
+
+const S_IFMT: u16 = 0xF000;
+const S_IFDIR: u16 = 0x4000;
+
+// everything here comes from the image, a broken or hostile one gets an error instead of a panic
+fn bytes_at<const N: usize>(buf: &[u8], offset: usize) -> Result<[u8; N], String> {
+    buf.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            format!(
+                "Buffer too short: {} bytes, needed {}",
+                buf.len(),
+                offset + N
+            )
+        })
+}
+
+fn u16_at(buf: &[u8], offset: usize) -> Result<u16, String> {
+    Ok(u16::from_le_bytes(bytes_at(buf, offset)?))
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(bytes_at(buf, offset)?))
+}
+
+fn read_at<T: Read + Seek>(fs: &mut T, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    // grows with what's actually read, so a made up length can't allocate more than the image has
+    let mut buf = Vec::new();
+    fs.seek(SeekFrom::Start(offset))
+        .and_then(|_| fs.take(len as u64).read_to_end(&mut buf))
+        .map_err(|e| format!("Failed to read {} bytes at {}: {}", len, offset, e))?;
+    if buf.len() != len {
+        return Err(format!(
+            "Image ends before {} bytes at {} could be read",
+            len, offset
+        ));
+    }
+    Ok(buf)
+}
+
+struct SuperBlock {
+    block_size: u64,
+    inodes_count: u32,
+    inodes_per_group: u32,
+    inode_size: usize,
+    first_data_block: u64,
+    desc_size: usize,
+    filetype: bool,
+    // bg_flags and bg_itable_unused can only be trusted when the group descriptors are checksummed
+    trust_unused: bool,
+}
+
+impl SuperBlock {
+    fn read<T: Read + Seek>(fs: &mut T) -> Result<Self, String> {
+        let sb = read_at(fs, SUPERBLOCK_OFFSET, 1024)?;
+        if u16_at(&sb, 56)? != EXT4_MAGIC {
+            return Err("Not an ext2/3/4 filesystem".to_string());
+        }
+        let incompat = u32_at(&sb, 96)?;
+        let ro_compat = u32_at(&sb, 100)?;
+        let is_64bit = incompat & INCOMPAT_64BIT != 0;
+
+        // 1 KiB up to 64 KiB
+        let log_block_size = u32_at(&sb, 24)?;
+        if log_block_size > 6 {
+            return Err(format!(
+                "Unsupported block size: 1024 << {}",
+                log_block_size
+            ));
+        }
+        let block_size = 1024 << log_block_size;
+        // the inode bitmap of a group is a single block
+        let inodes_per_group = u32_at(&sb, 40)?;
+        if inodes_per_group == 0 || inodes_per_group as u64 > block_size * 8 {
+            return Err(format!("Bad inodes per group: {}", inodes_per_group));
+        }
+        // revision 0 filesystems always have 128 byte inodes
+        let inode_size = if u32_at(&sb, 76)? == 0 {
+            128
+        } else {
+            u16_at(&sb, 88)? as usize
+        };
+        if inode_size < 128 || !inode_size.is_power_of_two() || inode_size as u64 > block_size {
+            return Err(format!("Bad inode size: {}", inode_size));
+        }
+        let desc_size = if is_64bit {
+            u16_at(&sb, 254)? as usize
+        } else {
+            32
+        };
+        if desc_size < 32 || !desc_size.is_power_of_two() || desc_size as u64 > block_size {
+            return Err(format!("Bad group descriptor size: {}", desc_size));
+        }
+
+        Ok(SuperBlock {
+            block_size,
+            inodes_count: u32_at(&sb, 0)?,
+            inodes_per_group,
+            inode_size,
+            first_data_block: u32_at(&sb, 20)? as u64,
+            desc_size,
+            filetype: incompat & INCOMPAT_FILETYPE != 0,
+            trust_unused: ro_compat & (RO_COMPAT_GDT_CSUM | RO_COMPAT_METADATA_CSUM) != 0,
+        })
+    }
+
+    fn groups(&self) -> u32 {
+        self.inodes_count.div_ceil(self.inodes_per_group)
+    }
+
+    fn read_block<T: Read + Seek>(&self, fs: &mut T, block: u64) -> Result<Vec<u8>, String> {
+        let offset = block
+            .checked_mul(self.block_size)
+            .ok_or_else(|| format!("Block number out of range: {}", block))?;
+        read_at(fs, offset, self.block_size as usize)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ext4Inode {
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: i64, // unix secs
+}
+
+// what's needed to find a directory's blocks in the second pass
+struct DirBlocks {
+    flags: u32,
+    size: u64,
+    block: [u8; 60],
+}
+
+#[derive(Default)]
+pub struct Ext4Table {
+    pub inodes: HashMap<u32, Ext4Inode>,
+    children: HashMap<u32, Vec<(String, u32)>>,
+    pub skipped_dirs: usize, // directories that couldn't be read
+}
+
+impl Ext4Table {
+    pub fn read<T: Read + Seek>(fs: &mut T) -> Result<Self, String> {
+        let sb = SuperBlock::read(fs)?;
+        let mut table = Ext4Table::default();
+        let mut dirs = Vec::new();
+
+        // the group descriptors follow the superblock's block
+        let gdt_offset = (sb.first_data_block + 1) * sb.block_size;
+        let gdt = read_at(fs, gdt_offset, sb.groups() as usize * sb.desc_size)?;
+        for (group, desc) in (0..sb.groups()).zip(gdt.chunks_exact(sb.desc_size)) {
+            let mut inode_table = u32_at(desc, 8)? as u64;
+            let mut unused = u16_at(desc, 28)? as u32;
+            if sb.desc_size >= 64 {
+                inode_table |= (u32_at(desc, 40)? as u64) << 32;
+                unused |= (u16_at(desc, 50)? as u32) << 16;
+            }
+            let used = if !sb.trust_unused {
+                sb.inodes_per_group
+            } else if u16_at(desc, 18)? & BG_INODE_UNINIT != 0 {
+                0
+            } else {
+                sb.inodes_per_group.saturating_sub(unused)
+            };
+            if used == 0 {
+                continue;
+            }
+
+            let inode_table = inode_table
+                .checked_mul(sb.block_size)
+                .ok_or_else(|| format!("Inode table out of range in group {}", group))?;
+            let inodes = read_at(fs, inode_table, used as usize * sb.inode_size)?;
+            for (index, raw) in inodes.chunks_exact(sb.inode_size).enumerate() {
+                // the last group can have room for more inodes than the filesystem has
+                let ino = group as u64 * sb.inodes_per_group as u64 + index as u64 + 1;
+                if ino > sb.inodes_count as u64 {
+                    break;
+                }
+                let ino = ino as u32;
+                let mode = u16_at(raw, 0)?;
+                let links = u16_at(raw, 26)?;
+                let deleted = u32_at(raw, 20)? != 0;
+                if mode == 0 || links == 0 || deleted {
+                    continue;
+                }
+
+                let is_dir = mode & S_IFMT == S_IFDIR;
+                let size = u32_at(raw, 4)? as u64 | (u32_at(raw, 108)? as u64) << 32;
+                if is_dir {
+                    dirs.push((
+                        ino,
+                        DirBlocks {
+                            flags: u32_at(raw, 32)?,
+                            size,
+                            block: bytes_at(raw, 40)?,
+                        },
+                    ));
+                }
+                table.inodes.insert(
+                    ino,
+                    Ext4Inode {
+                        is_dir,
+                        size,
+                        modified: u32_at(raw, 16)? as i64,
+                    },
+                );
+            }
+        }
+
+        for (ino, dir) in dirs {
+            match read_dir(fs, &sb, &dir) {
+                Some(entries) => {
+                    table.children.insert(ino, entries);
+                }
+                None => table.skipped_dirs += 1,
+            }
+        }
+        Ok(table)
+    }
+
+    // follow directory names down from the filesystem root
+    pub fn find_dir(&self, components: &[&str]) -> Option<u32> {
+        components.iter().try_fold(ROOT_INO, |parent, component| {
+            self.children
+                .get(&parent)?
+                .iter()
+                .find(|(name, ino)| {
+                    name == component && self.inodes.get(ino).is_some_and(|inode| inode.is_dir)
+                })
+                .map(|(_, ino)| *ino)
+        })
+    }
+
+    // every inode below the directory `base`, with its full path under `root` (the path of `base`)
+    // parents always come before their children, hard linked files show up once per link
+    pub fn paths(&self, base: u32, root: &Path) -> Vec<(PathBuf, &Ext4Inode)> {
+        let mut paths = Vec::new();
+        let mut visited = HashSet::from([base]);
+        let mut stack = vec![(base, root.to_path_buf())];
+        while let Some((dir, dir_path)) = stack.pop() {
+            for (name, ino) in self.children.get(&dir).into_iter().flatten() {
+                let Some(inode) = self.inodes.get(ino) else {
+                    continue;
+                };
+                let path = dir_path.join(name);
+                // a directory can only be reached once, corrupted trees could loop otherwise
+                if inode.is_dir {
+                    if !visited.insert(*ino) {
+                        continue;
+                    }
+                    stack.push((*ino, path.clone()));
+                }
+                paths.push((path, inode));
+            }
+        }
+        paths
+    }
+}
+
+// a directory's (name, inode) entries, None when its blocks can't be read
+fn read_dir<T: Read + Seek>(
+    fs: &mut T,
+    sb: &SuperBlock,
+    dir: &DirBlocks,
+) -> Option<Vec<(String, u32)>> {
+    if dir.flags & INLINE_DATA_FL != 0 {
+        // small directories live in i_block after their parent's inode number, entries that
+        // overflow into the system.data xattr aren't read
+        let mut entries = Vec::new();
+        parse_entries(&dir.block[4..], sb.filetype, &mut entries).ok()?;
+        return Some(entries);
+    }
+    let count = dir.size.div_ceil(sb.block_size);
+    let mut blocks = Vec::new();
+    if dir.flags & EXTENTS_FL != 0 {
+        extent_blocks(fs, sb, &dir.block, MAX_EXTENT_DEPTH, count, &mut blocks).ok()?;
+    } else {
+        // 12 direct blocks, then single, double and triple indirect ones
+        for (i, chunk) in dir.block.chunks_exact(4).enumerate() {
+            let block = u32_at(chunk, 0).ok()? as u64;
+            let depth = i.saturating_sub(11) as u32;
+            mapped_blocks(fs, sb, block, depth, count, &mut blocks).ok()?;
+        }
+    }
+    blocks.truncate(count as usize);
+
+    let mut entries = Vec::new();
+    for block in blocks {
+        let buf = sb.read_block(fs, block).ok()?;
+        parse_entries(&buf, sb.filetype, &mut entries).ok()?;
+    }
+    Some(entries)
+}
+
+// `max_depth` goes down by one per level, a tree that claims otherwise (or loops) is rejected
+fn extent_blocks<T: Read + Seek>(
+    fs: &mut T,
+    sb: &SuperBlock,
+    node: &[u8],
+    max_depth: u16,
+    count: u64,
+    blocks: &mut Vec<u64>,
+) -> Result<(), String> {
+    if u16_at(node, 0)? != EXTENT_MAGIC {
+        return Err("Bad extent header".to_string());
+    }
+    let entries = u16_at(node, 2)? as usize;
+    let depth = u16_at(node, 6)?;
+    if depth > max_depth {
+        return Err(format!("Extent tree too deep: {}", depth));
+    }
+    for entry in node
+        .get(12..)
+        .unwrap_or_default()
+        .chunks_exact(12)
+        .take(entries)
+    {
+        if blocks.len() as u64 >= count {
+            break;
+        }
+        if depth == 0 {
+            let len = u16_at(entry, 4)?;
+            if len > MAX_INIT_EXTENT_LEN {
+                continue;
+            }
+            let start = (u16_at(entry, 6)? as u64) << 32 | u32_at(entry, 8)? as u64;
+            blocks.extend(start..start + len as u64);
+        } else {
+            let leaf = (u16_at(entry, 8)? as u64) << 32 | u32_at(entry, 4)? as u64;
+            let child = sb.read_block(fs, leaf)?;
+            extent_blocks(fs, sb, &child, depth - 1, count, blocks)?;
+        }
+    }
+    Ok(())
+}
+
+// old style block maps, `depth` levels of indirect blocks above the data blocks
+fn mapped_blocks<T: Read + Seek>(
+    fs: &mut T,
+    sb: &SuperBlock,
+    block: u64,
+    depth: u32,
+    count: u64,
+    blocks: &mut Vec<u64>,
+) -> Result<(), String> {
+    if block == 0 || blocks.len() as u64 >= count {
+        return Ok(());
+    }
+    if depth == 0 {
+        blocks.push(block);
+        return Ok(());
+    }
+    let pointers = sb.read_block(fs, block)?;
+    for pointer in pointers.chunks_exact(4) {
+        mapped_blocks(fs, sb, u32_at(pointer, 0)? as u64, depth - 1, count, blocks)?;
+    }
+    Ok(())
+}
+
+// linear directory entries, hashed directories keep theirs the same way in the leaf blocks
+fn parse_entries(
+    buf: &[u8],
+    filetype: bool,
+    entries: &mut Vec<(String, u32)>,
+) -> Result<(), String> {
+    let mut offset = 0;
+    while offset + 8 <= buf.len() {
+        let ino = u32_at(buf, offset)?;
+        let rec_len = u16_at(buf, offset + 4)? as usize;
+        let name_len = if filetype {
+            bytes_at::<1>(buf, offset + 6)?[0] as usize
+        } else {
+            u16_at(buf, offset + 6)? as usize
+        };
+        // a bad entry ends the block, whatever came before it is kept
+        let Some(name) = buf.get(offset + 8..offset + 8 + name_len) else {
+            break;
+        };
+        if rec_len < 8 {
+            break;
+        }
+        if ino != 0 && name != b"." && name != b".." {
+            entries.push((String::from_utf8_lossy(name).to_string(), ino));
+        }
+        offset += rec_len;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    use jwalk::WalkDir;
+
+    pub(crate) fn testdata(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(name)
+    }
+
+    // the tree the fixture images hold, `many` has enough entries to need indirect blocks on ext2
+    pub(crate) fn make_tree(root: &Path) {
+        for dir in ["docs/notes/2024", "photos", "empty", "many", "a/b/c/d/e"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let files: [(&str, &[u8]); 4] = [
+            ("docs/readme.txt", b"hello\n"),
+            ("docs/notes/2024/plan.md", &[b'x'; 3000]),
+            ("photos/ü-ñ.jpg", b"0123456789"),
+            ("a/b/c/d/e/f.txt", b"deep\n"),
+        ];
+        for (path, contents) in files {
+            std::fs::write(root.join(path), contents).unwrap();
+        }
+        for i in 0..1000 {
+            std::fs::write(root.join(format!("many/file-{i:04}.txt")), b"").unwrap();
+        }
+    }
+
+    pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("guessfs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // (path below the root, is_dir, size for files), sorted
+    type Listing = Vec<(PathBuf, bool, Option<u64>)>;
+
+    fn walk(root: &Path) -> Listing {
+        let mut listing: Listing = WalkDir::new(root)
+            .skip_hidden(false)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.depth > 0)
+            .map(|entry| {
+                let is_dir = entry.file_type.is_dir();
+                let size = (!is_dir).then(|| entry.metadata().unwrap().len());
+                (
+                    entry.path().strip_prefix(root).unwrap().to_path_buf(),
+                    is_dir,
+                    size,
+                )
+            })
+            .collect();
+        listing.sort();
+        listing
+    }
+
+    fn read_image(image: &Path, root: &Path) -> Listing {
+        let table = Ext4Table::read(&mut File::open(image).unwrap()).unwrap();
+        assert_eq!(table.skipped_dirs, 0);
+        let base = table.find_dir(&[]).unwrap();
+        let mut listing: Listing = table
+            .paths(base, root)
+            .into_iter()
+            .map(|(path, inode)| {
+                let size = (!inode.is_dir).then_some(inode.size);
+                (
+                    path.strip_prefix(root).unwrap().to_path_buf(),
+                    inode.is_dir,
+                    size,
+                )
+            })
+            // mke2fs makes it, it isn't part of the tree
+            .filter(|(path, _, _)| !path.starts_with("lost+found"))
+            .collect();
+        listing.sort();
+        listing
+    }
+
+    #[test]
+    fn images_match_a_walk_of_the_same_tree() {
+        let root = scratch_dir("ext4-tree");
+        make_tree(&root);
+        let walked = walk(&root);
+        assert_eq!(walked.len(), 1015);
+
+        // ext4 has extents and hashed directories, ext2 block maps with indirect blocks
+        for image in ["ext4.img", "ext2.img"] {
+            assert_eq!(read_image(&testdata(image), &root), walked, "{}", image);
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    // rewrites testdata/*.img from make_tree, needs mke2fs:
+    // cargo test -p src-sidecar write_ext4_fixtures -- --ignored
+    #[test]
+    #[ignore]
+    fn write_ext4_fixtures() {
+        let root = scratch_dir("ext4-fixture");
+        make_tree(&root);
+        for fs_type in ["ext4", "ext2"] {
+            let image = testdata(&format!("{fs_type}.img"));
+            let _ = std::fs::remove_file(&image);
+            let status = std::process::Command::new("mke2fs")
+                .args(["-q", "-t", fs_type, "-b", "1024", "-N", "1200", "-m", "0"])
+                .args(["-E", "root_owner=0:0", "-U", "clear", "-d"])
+                .arg(&root)
+                .arg(&image)
+                .arg("1024")
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn broken_superblocks_are_errors() {
+        let image = std::fs::read(testdata("ext4.img")).unwrap();
+        let read = |image: &[u8]| Ext4Table::read(&mut Cursor::new(image)).err();
+        let patched = |offset: usize, bytes: &[u8]| {
+            let mut image = image.clone();
+            image[1024 + offset..][..bytes.len()].copy_from_slice(bytes);
+            image
+        };
+
+        assert!(read(&image).is_none());
+        assert!(read(&patched(40, &0u32.to_le_bytes())).is_some()); // inodes per group
+        assert!(read(&patched(88, &0u16.to_le_bytes())).is_some()); // inode size
+        assert!(read(&patched(24, &30u32.to_le_bytes())).is_some()); // block size
+        assert!(read(&patched(56, &0u16.to_le_bytes())).is_some()); // magic
+        assert!(read(&image[..1500]).is_some());
+        assert!(read(&image[..64 * 1024]).is_some());
+    }
+
+    fn extent_node(depth: u16, entries: &[(u32, u16, u32)]) -> Vec<u8> {
+        let mut node = Vec::new();
+        node.extend(EXTENT_MAGIC.to_le_bytes());
+        node.extend((entries.len() as u16).to_le_bytes());
+        node.extend(4u16.to_le_bytes()); // max entries
+        node.extend(depth.to_le_bytes());
+        node.extend(0u32.to_le_bytes()); // generation
+        for (first, len, block) in entries {
+            node.extend(first.to_le_bytes());
+            if depth == 0 {
+                node.extend(len.to_le_bytes());
+                node.extend(0u16.to_le_bytes());
+                node.extend(block.to_le_bytes());
+            } else {
+                node.extend(block.to_le_bytes());
+                node.extend(0u16.to_le_bytes());
+                node.extend(0u16.to_le_bytes());
+            }
+        }
+        node.resize(60, 0);
+        node
+    }
+
+    #[test]
+    fn extent_trees_are_bounded() {
+        let sb = SuperBlock {
+            block_size: 1024,
+            inodes_count: 16,
+            inodes_per_group: 16,
+            inode_size: 128,
+            first_data_block: 1,
+            desc_size: 32,
+            filetype: true,
+            trust_unused: false,
+        };
+        // block 1 is an index node pointing at itself
+        let mut image = vec![0u8; 4 * 1024];
+        image[1024..1024 + 60].copy_from_slice(&extent_node(1, &[(0, 0, 1)]));
+        let mut fs = Cursor::new(image);
+        let mut blocks = Vec::new();
+
+        let leaf = extent_node(0, &[(0, 2, 7), (2, 1, 9)]);
+        extent_blocks(&mut fs, &sb, &leaf, MAX_EXTENT_DEPTH, 3, &mut blocks).unwrap();
+        assert_eq!(blocks, [7, 8, 9]);
+
+        let looping = extent_node(1, &[(0, 0, 1)]);
+        assert!(extent_blocks(&mut fs, &sb, &looping, MAX_EXTENT_DEPTH, 10, &mut blocks).is_err());
+        let too_deep = extent_node(6, &[(0, 0, 1)]);
+        assert!(extent_blocks(&mut fs, &sb, &too_deep, MAX_EXTENT_DEPTH, 10, &mut blocks).is_err());
+        let beyond_image = extent_node(1, &[(0, 0, u32::MAX)]);
+        assert!(
+            extent_blocks(
+                &mut fs,
+                &sb,
+                &beyond_image,
+                MAX_EXTENT_DEPTH,
+                10,
+                &mut blocks
+            )
+            .is_err()
+        );
+    }
+
+    // the ext4 reader against a jwalk of the same mounted volume, needs read access to the device:
+    // GUESSFS_EXT4_DEVICE=/dev/sda2 GUESSFS_EXT4_MOUNT=/ \
+    //     cargo test --release -p src-sidecar compare_with_jwalk -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn compare_with_jwalk() {
+        use std::time::Instant;
+
+        let device = std::env::var("GUESSFS_EXT4_DEVICE").unwrap();
+        let mount = PathBuf::from(std::env::var("GUESSFS_EXT4_MOUNT").unwrap_or("/".to_string()));
+
+        let start_time = Instant::now();
+        let table = Ext4Table::read(&mut File::open(&device).unwrap()).unwrap();
+        let base = table.find_dir(&[]).unwrap();
+        let ours: HashSet<PathBuf> = table
+            .paths(base, &mount)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        let ours_time = start_time.elapsed();
+
+        let start_time = Instant::now();
+        // other filesystems mounted below show up as paths only from jwalk
+        let theirs: HashSet<PathBuf> = WalkDir::new(&mount)
+            .skip_hidden(false)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.depth > 0)
+            .map(|entry| entry.path())
+            .collect();
+        let theirs_time = start_time.elapsed();
+
+        println!(
+            "ext4: {} paths in {:.3?} ({} unreadable directories), jwalk: {} paths in {:.3?}",
+            ours.len(),
+            ours_time,
+            table.skipped_dirs,
+            theirs.len(),
+            theirs_time
+        );
+        println!(
+            "{} paths only from ext4, {} only from jwalk",
+            ours.difference(&theirs).count(),
+            theirs.difference(&ours).count()
+        );
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+// path listings made somewhere else, for indexing trees that can't be reached from here
+// paths: one per line, `dir /s /b` output or `find DIR` output, folders are the paths
+//        something else is below, or that end with a separator, `\` and `/` both separate
+// find:  `find DIR -printf '%y\t%s\t%T@\t%p\n'`, type/size/mtime/path
+// csv:   a header row naming the columns, `path` is required, `type` (dir/file), `size`
+//        and `modified` (unix secs) are used when there
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListingFormat {
+    Paths,
+    Find,
+    Csv,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListedEntry {
+    pub path: PathBuf,
+    pub is_dir: Option<bool>, // None when the listing doesn't say
+    pub size: Option<u64>,
+    pub modified: Option<i64>, // unix secs
+}
+
+impl ListedEntry {
+    fn new(path: &str) -> Self {
+        ListedEntry {
+            path: PathBuf::from(path),
+            is_dir: path.ends_with(['/', '\\']).then_some(true),
+            size: None,
+            modified: None,
+        }
+    }
+}
+
+// `dir > file` from PowerShell writes UTF-16, cmd and everything else UTF-8
+pub fn decode(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+pub fn parse(text: &str, format: ListingFormat) -> Result<Vec<ListedEntry>, String> {
+    let lines = text.lines().filter(|line| !line.trim().is_empty());
+    match format {
+        // `dir /s /b` writes `C:\...`, which is one long name anywhere but on windows
+        ListingFormat::Paths => Ok(lines
+            .map(|line| ListedEntry::new(&line.trim().replace('\\', "/")))
+            .collect()),
+        ListingFormat::Find => lines.map(parse_find_line).collect(),
+        ListingFormat::Csv => parse_csv(lines),
+    }
+}
+
+fn parse_find_line(line: &str) -> Result<ListedEntry, String> {
+    let mut fields = line.splitn(4, '\t');
+    let (Some(kind), Some(size), Some(modified), Some(path)) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err(format!("Expected type, size, mtime and path: {}", line));
+    };
+    let mut entry = ListedEntry::new(path);
+    entry.is_dir = Some(kind == "d");
+    entry.size = size.parse().ok();
+    // %T@ has a fractional part
+    entry.modified = modified.parse::<f64>().ok().map(|t| t as i64);
+    Ok(entry)
+}
+
+fn parse_csv<'a>(mut lines: impl Iterator<Item = &'a str>) -> Result<Vec<ListedEntry>, String> {
+    let header = csv_fields(lines.next().ok_or("Empty CSV")?);
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|column| column.trim().eq_ignore_ascii_case(name))
+    };
+    let path_column = column("path").ok_or("CSV has no path column")?;
+    let (type_column, size_column, modified_column) =
+        (column("type"), column("size"), column("modified"));
+
+    let mut entries = Vec::new();
+    for line in lines {
+        let fields = csv_fields(line);
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|i| fields.get(i))
+                .map(|field| field.trim())
+                .filter(|field| !field.is_empty())
+        };
+        let Some(path) = field(Some(path_column)) else {
+            eprintln!("No path in CSV line: {}", line);
+            continue;
+        };
+        let mut entry = ListedEntry::new(path);
+        if let Some(kind) = field(type_column) {
+            entry.is_dir = Some(matches!(
+                kind.to_ascii_lowercase().as_str(),
+                "d" | "dir" | "directory" | "folder"
+            ));
+        }
+        entry.size = field(size_column).and_then(|size| size.parse().ok());
+        entry.modified = field(modified_column).and_then(|t| t.parse().ok());
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// fields can be quoted to hold commas, "" inside quotes is a quote
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => field.push(c),
+        }
+    }
+    fields
+}
+
+// `/a` and `C:/a` are both absolute, whichever OS the listing was made on
+fn is_absolute(path: &Path) -> bool {
+    path.has_root()
+        || path.components().next().is_some_and(|c| match c {
+            Component::Prefix(_) => true,
+            Component::Normal(name) => {
+                let name = name.as_encoded_bytes();
+                name.len() == 2 && name[0].is_ascii_alphabetic() && name[1] == b':'
+            }
+            _ => false,
+        })
+}
+
+// the deepest folder every path is in
+fn common_folder<'a>(paths: impl Iterator<Item = &'a Path>) -> Option<PathBuf> {
+    paths
+        .map(|path| path.parent().unwrap_or(path))
+        .fold(None, |common: Option<PathBuf>, folder| {
+            Some(match common {
+                None => folder.to_path_buf(),
+                Some(common) => common
+                    .components()
+                    .zip(folder.components())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a)
+                    .collect(),
+            })
+        })
+}
+
+// relative paths (`find .`) are put under `root`, so are absolute ones when they're below it
+// already, an absolute listing from somewhere else (another machine, another OS) is rebased:
+// the folder all of it is in becomes `root`, paths going up with `..` are left out
+// folders the listing left out are added, and parents are sorted before their children
+// returns the entries, how many were left out and the folder a rebased listing was in
+pub fn resolve(
+    entries: Vec<ListedEntry>,
+    root: &Path,
+) -> (Vec<ListedEntry>, usize, Option<PathBuf>) {
+    let mut outside = 0;
+    let entries: Vec<ListedEntry> = entries
+        .into_iter()
+        .filter_map(|mut entry| {
+            if entry.path.components().any(|c| c == Component::ParentDir) {
+                outside += 1;
+                return None;
+            }
+            // `./a` has the `.` in it
+            entry.path = entry
+                .path
+                .components()
+                .filter(|c| *c != Component::CurDir)
+                .collect();
+            Some(entry)
+        })
+        .collect();
+
+    let foreign = entries
+        .iter()
+        .any(|entry| is_absolute(&entry.path) && !entry.path.starts_with(root));
+    let listing_root = if foreign {
+        common_folder(
+            entries
+                .iter()
+                .map(|entry| entry.path.as_path())
+                .filter(|path| is_absolute(path)),
+        )
+    } else {
+        None
+    };
+
+    let mut resolved: Vec<ListedEntry> = Vec::new();
+    for mut entry in entries {
+        if !is_absolute(&entry.path) {
+            entry.path = root.join(&entry.path);
+        } else if let Some(listing_root) = &listing_root {
+            let Ok(relative) = entry.path.strip_prefix(listing_root) else {
+                outside += 1;
+                continue;
+            };
+            entry.path = root.join(relative);
+        }
+        if entry.path != root {
+            resolved.push(entry);
+        }
+    }
+
+    // anything with something below it is a folder, whether it was listed or not
+    let mut folders = HashSet::new();
+    for entry in &resolved {
+        for parent in entry.path.ancestors().skip(1) {
+            if parent == root || !folders.insert(parent.to_path_buf()) {
+                break;
+            }
+        }
+    }
+    let listed: HashSet<PathBuf> = resolved.iter().map(|entry| entry.path.clone()).collect();
+    for folder in &folders {
+        if !listed.contains(folder) {
+            resolved.push(ListedEntry {
+                path: folder.clone(),
+                is_dir: Some(true),
+                size: None,
+                modified: None,
+            });
+        }
+    }
+    for entry in &mut resolved {
+        if folders.contains(&entry.path) {
+            entry.is_dir = Some(true);
+        }
+    }
+
+    // paths compare by component, so a folder always sorts before what's in it
+    resolved.sort_by(|a, b| a.path.cmp(&b.path));
+    resolved.dedup_by(|a, b| a.path == b.path);
+    (resolved, outside, listing_root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Resolved = (Vec<(PathBuf, Option<bool>)>, usize, Option<PathBuf>);
+
+    fn resolved(text: &str, format: ListingFormat, root: &str) -> Resolved {
+        let (entries, outside, listing_root) =
+            resolve(parse(text, format).unwrap(), Path::new(root));
+        let entries = entries
+            .into_iter()
+            .map(|entry| (entry.path, entry.is_dir))
+            .collect();
+        (entries, outside, listing_root)
+    }
+
+    fn entries(expected: &[(&str, Option<bool>)]) -> Vec<(PathBuf, Option<bool>)> {
+        expected
+            .iter()
+            .map(|(path, is_dir)| (PathBuf::from(path), *is_dir))
+            .collect()
+    }
+
+    #[test]
+    fn dir_listings_from_windows_are_rebased() {
+        let text = "C:\\Users\\me\\Docs\\a.txt\r\n\
+                    C:\\Users\\me\\Docs\\photos\r\n\
+                    C:\\Users\\me\\Docs\\photos\\cat.jpg\r\n";
+        let (entries_found, outside, listing_root) = resolved(text, ListingFormat::Paths, "/index");
+        assert_eq!(
+            entries_found,
+            entries(&[
+                ("/index/a.txt", None),
+                ("/index/photos", Some(true)),
+                ("/index/photos/cat.jpg", None),
+            ])
+        );
+        assert_eq!(outside, 0);
+        assert_eq!(listing_root, Some(PathBuf::from("C:/Users/me/Docs")));
+    }
+
+    #[test]
+    fn listings_from_other_machines_are_rebased() {
+        let text = "/home/me/docs/readme.txt\n/home/me/docs/src/main.rs\n";
+        let (entries_found, _, listing_root) = resolved(text, ListingFormat::Paths, "/index");
+        assert_eq!(
+            entries_found,
+            entries(&[
+                ("/index/readme.txt", None),
+                ("/index/src", Some(true)),
+                ("/index/src/main.rs", None),
+            ])
+        );
+        assert_eq!(listing_root, Some(PathBuf::from("/home/me/docs")));
+    }
+
+    #[test]
+    fn listings_below_the_root_stay_put() {
+        let text = "f\t1\t0\t/srv/data/a\nf\t2\t0\t/srv/data/b/c\n";
+        let (entries_found, outside, listing_root) =
+            resolved(text, ListingFormat::Find, "/srv/data");
+        assert_eq!(
+            entries_found,
+            entries(&[
+                ("/srv/data/a", Some(false)),
+                ("/srv/data/b", Some(true)),
+                ("/srv/data/b/c", Some(false)),
+            ])
+        );
+        assert_eq!((outside, listing_root), (0, None));
+    }
+
+    #[test]
+    fn relative_listings_go_under_the_root() {
+        let text = "./a/b.txt\n../escape.txt\n.\n";
+        let (entries_found, outside, listing_root) = resolved(text, ListingFormat::Paths, "/index");
+        assert_eq!(
+            entries_found,
+            entries(&[("/index/a", Some(true)), ("/index/a/b.txt", None)])
+        );
+        assert_eq!((outside, listing_root), (1, None));
+    }
+}
@@ -1,383 +1,258 @@
-use std::{collections::HashMap, path::Path, time::Instant};
+use std::sync::atomic::{AtomicU8, Ordering};
 
-use clap::{Arg, ArgAction, Command, value_parser};
-use directories::BaseDirs;
-use jwalk::WalkDir;
+// progress goes to stdout for text output, stderr when stdout is for the JSON result,
+// and nowhere with --quiet
+static PROGRESS: AtomicU8 = AtomicU8::new(PROGRESS_STDOUT);
+const PROGRESS_STDOUT: u8 = 0;
+const PROGRESS_STDERR: u8 = 1;
+const PROGRESS_OFF: u8 = 2;
+
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        match crate::PROGRESS.load(std::sync::atomic::Ordering::Relaxed) {
+            crate::PROGRESS_STDOUT => println!($($arg)*),
+            crate::PROGRESS_STDERR => eprintln!($($arg)*),
+            _ => {}
+        }
+    };
+}
+
+mod cli;
+mod commands;
+mod ext4;
+mod listing;
+mod mft;
+mod remote;
+mod sources;
+mod usn;
+
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use cli::{Failure, FailureKind, OutputFormat};
+use listing::ListingFormat;
 use runas::Command as RunasCommand;
 use rusqlite::Connection;
+use serde_json::json;
+use sources::{Ext4Source, ListingSource, MftSource, RemoteSource, WalkSource};
+use src_lib::source::{IndexSink, IndexSource, SinkCounts};
 use src_lib::{IndexOptions, get_drive_letter};
-use usn_journal_rs::{mft::Mft, path::MftPathResolver, volume::Volume};
 
-fn main() {
-    let matches = Command::new("Indexer")
-        .version("1.0")
-        .arg(Arg::new("path").long("path").value_parser(value_parser!(String)).help("Path to index").required(true))
-        .arg(Arg::new("index").long("index").value_parser(value_parser!(String)).help("Index files and/or directories (comma-separated) - files/dirs)").required(true))
-        .arg(Arg::new("types").long("types").value_parser(value_parser!(String)).help("File types to index (comma-separated)"))
-        .arg(Arg::new("exclude").long("exclude").value_parser(value_parser!(String)).help("Exclude common unwanted files (comma-separated) - empty, temp, hidden, system, privileged"))
-        .arg(Arg::new("exclude_regex").long("exclude-regex").value_parser(value_parser!(String)).help("Exclude files or directories matching this regex pattern"))
-        .arg(Arg::new("exclude_paths").long("exclude-paths").value_parser(value_parser!(String)).help("Exclude specific paths (comma-separated)"))
-        .arg(Arg::new("exclude_files").long("exclude-files").value_parser(value_parser!(String)).help("Exclude specific files (comma-separated)"))
-        .arg(Arg::new("elevate").long("elevate").help("Request elevation").action(ArgAction::SetTrue))
-        .get_matches();
-
-    if matches.get_flag("elevate") {
-        // get path of executable and rerun self as admin
-        let path = std::env::current_exe().expect("Could not get current executable path");
-        let mut command = RunasCommand::new(&path);
-        let args = std::env::args().skip(1).collect::<Vec<_>>();
-        // REMOVE --elevate before running new instance
-        // ...i learned this the hard way
-        let args: Vec<String> = args
-            .into_iter()
-            .filter(|arg| arg != "--elevate")
-            .collect();
-        command.args(&args);
-        command
-            .status()
-            .expect("Failed to execute elevated command");
-        std::process::exit(0); // exit current process
-    }
+// what a command prints when it succeeds, `message` for text output and `data` for JSON
+pub struct Report {
+    message: String,
+    data: serde_json::Value,
+}
 
-    println!("path: {}", matches.get_one::<String>("path").unwrap());
-    println!("indexing: {}", matches.get_one::<String>("index").unwrap());
-    if let Some(types) = matches.get_one::<String>("types") {
-        println!("types: {}", types);
-    }
-    if let Some(exclude_regex) = matches.get_one::<String>("exclude_regex") {
-        println!("exclude_regex: {}", exclude_regex);
+impl Report {
+    fn new(message: impl Into<String>, data: serde_json::Value) -> Self {
+        Report {
+            message: message.into(),
+            data,
+        }
     }
-    if let Some(exclude_paths) = matches.get_one::<String>("exclude_paths") {
-        println!("exclude_paths: {}", exclude_paths);
+}
+
+fn main() {
+    let matches = cli::build().get_matches();
+    let output = *matches.get_one::<OutputFormat>("output").unwrap();
+    let progress = if matches.get_flag("quiet") {
+        PROGRESS_OFF
+    } else if output == OutputFormat::Json {
+        PROGRESS_STDERR
+    } else {
+        PROGRESS_STDOUT
+    };
+    PROGRESS.store(progress, Ordering::Relaxed);
+
+    let result = run(&matches);
+    let code = match &result {
+        Ok(_) => 0,
+        Err(failure) => failure.kind as i32,
+    };
+    match (output, result) {
+        (OutputFormat::Text, Ok(report)) => {
+            if !report.message.is_empty() {
+                println!("{}", report.message)
+            }
+        }
+        (OutputFormat::Text, Err(failure)) => eprintln!("Error: {}", failure.message),
+        (OutputFormat::Json, Ok(report)) => {
+            let mut data = report.data;
+            if let Some(object) = data.as_object_mut() {
+                object.insert("ok".to_string(), json!(true));
+            }
+            println!("{}", data)
+        }
+        (OutputFormat::Json, Err(failure)) => println!(
+            "{}",
+            json!({ "ok": false, "error": failure.message, "code": failure.kind as i32 })
+        ),
     }
-    if let Some(exclude_files) = matches.get_one::<String>("exclude_files") {
-        println!("exclude_files: {}", exclude_files);
+    std::process::exit(code);
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<Report, Failure> {
+    let app_data_dir =
+        src_lib::data_dir(matches.get_one::<PathBuf>("data_dir").map(PathBuf::as_path))?;
+
+    let (name, sub) = matches.subcommand().unwrap();
+    if matches!(name, "index" | "import" | "update" | "watch") {
+        progress!("app data dir: {}", &app_data_dir.display());
     }
-    if let Some(exclude) = matches.get_one::<String>("exclude") {
-        println!("exclude: {}", exclude);
+    if sub.try_get_one::<bool>("elevate").ok().flatten() == Some(&true) {
+        return elevate(matches, &app_data_dir);
     }
-    if matches.get_flag("elevate") {
-        println!("elevate: true");
+    match name {
+        "index" | "import" => start_indexing(
+            &app_data_dir,
+            cli::index_options(sub),
+            cli::scan_options(sub),
+        ),
+        "update" => update_index(
+            &app_data_dir,
+            cli::index_options(sub),
+            ScanOptions::default(),
+        ),
+        "watch" => watch_index(
+            &app_data_dir,
+            cli::index_options(sub),
+            Duration::from_secs(*sub.get_one::<u64>("interval").unwrap()),
+        ),
+        _ => commands::run(name, sub, &app_data_dir),
     }
+}
 
-    let basedirs = BaseDirs::new().expect("Could not get base dirs");
-    let app_data_dir = basedirs.data_dir().join("me.jovannmc.guessfs");
-    println!("app data dir: {}", &app_data_dir.display());
-    let index_options = IndexOptions {
-        path: matches.get_one::<String>("path").unwrap().to_string(),
-        index_directories: matches
-            .get_one::<String>("index")
-            .map(|s| s.split(',').any(|x| x == "dirs"))
-            .unwrap_or(false),
-        index_files: matches
-            .get_one::<String>("index")
-            .map(|s| s.split(',').any(|x| x == "files"))
-            .unwrap_or(false),
-        file_types: matches
-            .get_one::<String>("types")
-            .map(|s| Some(s.split(',').map(String::from).collect::<Vec<String>>()))
-            .unwrap_or(None),
-        excluded_regex: matches.get_one::<String>("exclude_regex").map(String::from),
-        excluded_paths: matches
-            .get_one::<String>("exclude_paths")
-            .map(|s| s.split(',').map(String::from).collect())
-            .map_or_else(|| Some(Vec::new()), |v| Some(v)),
-        excluded_files: matches
-            .get_one::<String>("exclude_files")
-            .map(|s| s.split(',').map(String::from).collect())
-            .map_or_else(|| Some(Vec::new()), |v| Some(v)),
-        exclude_hidden: matches
-            .get_one::<String>("exclude")
-            .map_or(Some(false), |s| Some(s.split(',').any(|x| x == "hidden"))),
-        exclude_system: matches
-            .get_one::<String>("exclude")
-            .map_or(Some(false), |s| Some(s.split(',').any(|x| x == "system"))),
-        exclude_temporary: matches
-            .get_one::<String>("exclude")
-            .map_or(Some(false), |s| Some(s.split(',').any(|x| x == "temp"))),
-        exclude_empty: matches
-            .get_one::<String>("exclude")
-            .map_or(Some(false), |s| Some(s.split(',').any(|x| x == "empty"))),
-        exclude_admin: matches
-            .get_one::<String>("exclude")
-            .map_or(Some(false), |s| {
-                Some(s.split(',').any(|x| x == "privileged"))
-            }),
-    };
+// reruns the same command as admin and passes its exit code on
+fn elevate(matches: &clap::ArgMatches, app_data_dir: &Path) -> Result<Report, Failure> {
+    let path = std::env::current_exe().map_err(|e| {
+        Failure::new(
+            FailureKind::Elevation,
+            format!("Could not get current executable path: {}", e),
+        )
+    })?;
+    // without --elevate, or the new instance elevates again
+    // the elevated instance can be another user with another data dir (and env), so pin ours
+    let mut args: Vec<OsString> = vec!["--data-dir".into(), app_data_dir.into()];
+    args.extend(cli::to_args(matches, &["elevate", "data_dir"]));
+    let status = RunasCommand::new(&path)
+        .args(&args)
+        .status()
+        .map_err(|e| Failure::new(FailureKind::Elevation, format!("Failed to elevate: {}", e)))?;
+    std::process::exit(status.code().unwrap_or(FailureKind::Elevation as i32));
+}
 
-    start_indexing(&app_data_dir, index_options)
-        .map(|result| println!("{}", result))
-        .unwrap_or_else(|err| eprintln!("Error: {}", err));
+// how to get at the entries, as opposed to IndexOptions which decides which ones to keep
+#[derive(Default)]
+pub struct ScanOptions {
+    image: Option<String>, // NTFS image to read the MFT from
+    ext4: Option<String>,  // ext2/3/4 block device or image
+    mount_point: Option<String>,
+    legacy_mft: bool,
+    remote: Option<String>, // url listed through a RemoteClient
+    listing: Option<(PathBuf, ListingFormat)>, // from the import subcommand
+    replace: bool, // drop what's indexed, in the same transaction so a failed rescan keeps it
 }
 
-fn start_indexing(app_data_dir: &Path, index_options: IndexOptions) -> Result<String, String> {
-    let mut exclude_counts: HashMap<&'static str, usize> = HashMap::new();
+fn start_indexing(
+    app_data_dir: &Path,
+    index_options: IndexOptions,
+    scan_options: ScanOptions,
+) -> Result<Report, Failure> {
+    let path = Path::new(&index_options.path);
+    // with an image, a remote or a listing the path is only a prefix, it doesn't have to exist here
+    let from_image = scan_options.image.is_some()
+        || scan_options.ext4.is_some()
+        || scan_options.remote.is_some()
+        || scan_options.listing.is_some();
+    if !from_image && !path.is_dir() {
+        return Err(Failure::new(
+            FailureKind::Source,
+            format!("Path is not a directory: {}", index_options.path),
+        ));
+    }
 
     let db_path = src_lib::get_index_db_path(app_data_dir, &index_options.path)?;
+    // opening creates the file, so check first
+    let is_new = !db_path.exists();
 
-    let mut db =
-        Connection::open(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    let mut db = Connection::open(&db_path).map_err(|e| {
+        Failure::new(
+            FailureKind::Database,
+            format!("Failed to open database: {}", e),
+        )
+    })?;
 
-    if !db_path.exists() {
-        src_lib::init_db(&db).map_err(|e| format!("Failed to initialize database: {}", e))?;
-        println!("New database created at: {}", db_path.display());
+    init_db(&db)?;
+    if is_new {
+        progress!("New database created at: {}", db_path.display());
     } else {
-        src_lib::init_db(&db).map_err(|e| format!("Failed to initialize database: {}", e))?;
-        println!("Database already exists at: {}", db_path.display());
+        progress!("Database already exists at: {}", db_path.display());
     }
 
-    let path = Path::new(&index_options.path);
-    if !path.is_dir() {
-        return Err(format!("Path is not a directory: {}", index_options.path));
-    }
+    let transaction = db.transaction().map_err(|e| {
+        Failure::new(
+            FailureKind::Database,
+            format!("Failed to begin transaction: {}", e),
+        )
+    })?;
 
-    let transaction = db
-        .transaction()
-        .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+    if scan_options.replace {
+        src_lib::clear_index(&transaction).map_err(|e| {
+            Failure::new(
+                FailureKind::Database,
+                format!("Failed to clear index: {}", e),
+            )
+        })?;
+    }
 
     let start_time = Instant::now();
-    let mut folders_found = 0;
-    let mut files_found = 0;
-    let mut ignored = 0;
-    let mut exists = 0;
 
-    let is_root = path.components().count() == 2; // check if the path is a root directory (e.g., C:\) - C: and \ counts as two components
-    let is_ntfs = src_lib::is_ntfs(&path);
-
-    // Non-NTFS filesystem / not Windows
-    // also check if the path is not a root directory, because with MFT we can only index the entire root
-    if !is_ntfs || (is_ntfs && !is_root) {
-        println!("Not using NTFS MFT for path: {}", index_options.path);
-        // prepare folder and file insert statements
-        let mut folder_stmt = transaction
-            .prepare("INSERT OR IGNORE INTO folders (path) VALUES (?1)")
-            .unwrap();
-        let mut file_stmt = transaction
-            .prepare("INSERT OR IGNORE INTO files (path, folder_id) VALUES (?1, ?2)")
-            .unwrap();
-        // build a map to query instead of querying the DB each time
-        let mut folder_map = std::collections::HashMap::new();
-        for entry in WalkDir::new(path) {
-            match entry {
-                Ok(entry) => {
-                    // check if needed to be excluded
-                    if src_lib::should_exclude(
-                        &entry.path(),
-                        index_options.clone(),
-                        &mut exclude_counts,
-                    ) {
-                        ignored += 1;
-                        continue;
-                    }
-
-                    // if directory, insert
-                    if entry.file_type().is_dir() {
-                        if let Some(path) = entry.path().to_str() {
-                            let rows = folder_stmt.execute(rusqlite::params![path]).unwrap();
-                            if rows > 0 {
-                                folders_found += 1
-                            } else {
-                                // Already exists in DB
-                                exists += 1;
-                            }
-                            if !folder_map.contains_key(path) {
-                                let id: i64 = transaction
-                                    .query_row(
-                                        "SELECT id FROM folders WHERE path = ?1",
-                                        rusqlite::params![path],
-                                        |row| row.get(0),
-                                    )
-                                    .unwrap();
-                                folder_map.insert(path.to_string(), id);
-                            }
-                        } else {
-                            eprintln!("Path not UTF-8: {:?}", entry.path());
-                        }
-                    // if user wants to index files, insert them too
-                    } else if index_options.index_files {
-                        if let Some(path) = entry.path().to_str() {
-                            if let Some(parent) =
-                                std::path::Path::new(path).parent().and_then(|p| p.to_str())
-                            {
-                                if let Some(folder_id) = folder_map.get(parent) {
-                                    let rows = file_stmt
-                                        .execute(rusqlite::params![path, folder_id])
-                                        .unwrap();
-                                    if rows > 0 {
-                                        files_found += 1
-                                    } else {
-                                        // Already exists in DB
-                                        exists += 1;
-                                    }
-                                } else {
-                                    // if not in map, fetch from DB
-                                    if let Ok(folder_id) = transaction.query_row(
-                                        "SELECT id FROM folders WHERE path = ?1",
-                                        rusqlite::params![parent],
-                                        |row| row.get(0),
-                                    ) {
-                                        folder_map.insert(parent.to_string(), folder_id);
-                                        let rows = file_stmt
-                                            .execute(rusqlite::params![path, &folder_id])
-                                            .unwrap();
-                                        if rows > 0 {
-                                            files_found += 1
-                                        } else {
-                                            // Already exists in DB
-                                            exists += 1;
-                                        }
-                                    } else {
-                                        // not found, create parent folder in DB
-                                        let rows =
-                                            folder_stmt.execute(rusqlite::params![parent]).unwrap();
-                                        if rows > 0 {
-                                            folders_found += 1
-                                        } else {
-                                            // Already exists in DB
-                                            exists += 1;
-                                        }
-                                    }
-                                }
-                            } else {
-                                eprintln!("No parent folder for file: {path}");
-                            }
-                        } else {
-                            eprintln!("Path not UTF-8: {:?}", entry.path());
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error reading directory: {e}");
-                }
-            }
-        }
-    // NTFS filessystem
-    // TODO: ok the way we're accessing MFT is so god damn slow (even slower than jwalk!!) w/ the crate being used, we need to switch.
-    // TODO: at least, it doesn't use as much memory
-    } else {
-        #[cfg(target_os = "windows")]
-        println!("Using NTFS MFT for path: {}", index_options.path);
-
-        // scan MFT and insert folders/files into the database
-        let drive_letter = get_drive_letter(index_options.path.clone());
-        let volume = Volume::from_drive_letter(drive_letter).unwrap();
-        let mft = Mft::new(volume);
-        let mut path_resolver = MftPathResolver::new(&mft);
-
-        let mut folder_stmt = transaction
-            .prepare("INSERT OR IGNORE INTO folders (path) VALUES (?1)")
-            .unwrap();
-        let mut file_stmt = transaction
-            .prepare("INSERT OR IGNORE INTO files (path, folder_id) VALUES (?1, ?2)")
-            .unwrap();
-        let mut folder_map = HashMap::new();
-
-        println!("Starting MFT scan...");
-        for entry in mft.iter() {
-            // try to find the path if it exists
-            match path_resolver.resolve_path(&entry) {
-                Some(path_buf) => {
-                    // check if needed to be excluded
-                    if src_lib::should_exclude(
-                        &path_buf,
-                        index_options.clone(),
-                        &mut exclude_counts,
-                    ) {
-                        ignored += 1;
-                        continue;
-                    }
-
-                    let path_str = path_buf.to_str().unwrap_or("<invalid utf8>");
-                    // if directory, insert
-                    if entry.is_dir() {
-                        let rows = folder_stmt.execute(rusqlite::params![path_str]).unwrap();
-                        if rows > 0 {
-                            folders_found += 1
-                        } else {
-                            // Already exists in DB
-                            exists += 1;
-                        }
-                        if !folder_map.contains_key(path_str) {
-                            let id: i64 = transaction
-                                .query_row(
-                                    "SELECT id FROM folders WHERE path = ?1",
-                                    rusqlite::params![path_str],
-                                    |row| row.get(0),
-                                )
-                                .unwrap();
-                            folder_map.insert(path_str.to_string(), id);
-                        }
-                    // if user wants to index files, insert them too
-                    } else if index_options.index_files {
-                        if let Some(parent) = std::path::Path::new(path_str)
-                            .parent()
-                            .and_then(|p| p.to_str())
-                        {
-                            let folder_id = if let Some(folder_id) = folder_map.get(parent) {
-                                *folder_id
-                            } else {
-                                // find parent folder in DB
-                                match transaction.query_row(
-                                    "SELECT id FROM folders WHERE path = ?1",
-                                    rusqlite::params![parent],
-                                    |row| row.get(0),
-                                ) {
-                                    Ok(folder_id) => {
-                                        folder_map.insert(parent.to_string(), folder_id);
-                                        folder_id
-                                    }
-                                    Err(_) => {
-                                        // create parent folder in DB if it doesn't exist
-                                        let rows =
-                                            folder_stmt.execute(rusqlite::params![parent]).unwrap();
-                                        if rows > 0 {
-                                            folders_found += 1
-                                        } else {
-                                            // Already exists in DB
-                                            exists += 1;
-                                        }
-                                        let folder_id: i64 = transaction
-                                            .query_row(
-                                                "SELECT id FROM folders WHERE path = ?1",
-                                                rusqlite::params![parent],
-                                                |row| row.get(0),
-                                            )
-                                            .unwrap();
-                                        folder_map.insert(parent.to_string(), folder_id);
-                                        folder_id
-                                    }
-                                }
-                            };
-                            let rows = file_stmt
-                                .execute(rusqlite::params![path_str, &folder_id])
-                                .unwrap();
-                            if rows > 0 {
-                                files_found += 1
-                            } else {
-                                // Already exists in DB
-                                exists += 1;
-                            }
-                        } else {
-                            eprintln!("No parent folder for file: {path_str}");
-                        }
-                    }
-                }
-                None => {
-                    continue;
-                }
-            }
-        }
+    let (mut source, journal) = open_source(&index_options, &scan_options)
+        .map_err(|e| Failure::new(FailureKind::Source, e))?;
+
+    progress!("Reading entries from {}", source.name());
+    let mut sink = IndexSink::new(&transaction, &index_options);
+    // sources hand out their entries without failing, what can fail is writing them
+    let counts = sink
+        .write_all(source.as_mut())
+        .map_err(|e| Failure::new(FailureKind::Database, e))?;
+    let exclude_counts = std::mem::take(&mut sink.exclude_counts);
+    let SinkCounts {
+        folders_found,
+        files_found,
+        ignored,
+        exists,
+    } = counts;
+
+    build_derived(&transaction)?;
+    if let Some(journal) = journal {
+        save_journal_position(&transaction, journal.journal_id, journal.next_usn)?;
     }
+
     let duration = start_time.elapsed();
 
-    transaction
-        .commit()
-        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
-    println!(
+    transaction.commit().map_err(|e| {
+        Failure::new(
+            FailureKind::Database,
+            format!("Failed to commit transaction: {}", e),
+        )
+    })?;
+    progress!(
         "Indexing completed in {:.3?} ({} folders, {} files, {} ignored, {} already exists)",
-        duration, folders_found, files_found, ignored, exists
+        duration,
+        folders_found,
+        files_found,
+        ignored,
+        exists
     );
-    println!(
+    progress!(
         "Excluded counts: {}",
         exclude_counts
             .iter()
@@ -386,8 +261,240 @@ fn start_indexing(app_data_dir: &Path, index_options: IndexOptions) -> Result<St
             .join(", ")
     );
 
-    Ok(format!(
-        "Indexed {} new folders and {} new files in {:.3?} ({} ignored, {} already exists)",
-        folders_found, files_found, duration, ignored, exists
+    Ok(Report::new(
+        format!(
+            "Indexed {} new folders and {} new files in {:.3?} ({} ignored, {} already exists)",
+            folders_found, files_found, duration, ignored, exists
+        ),
+        json!({
+            "folders_found": folders_found,
+            "files_found": files_found,
+            "ignored": ignored,
+            "exists": exists,
+            "excluded": exclude_counts,
+            "seconds": duration.as_secs_f64(),
+        }),
+    ))
+}
+
+// picks where the entries come from, along with where the USN journal was when the scan
+// started so changes made during it get replayed by update
+fn open_source(
+    index_options: &IndexOptions,
+    scan_options: &ScanOptions,
+) -> Result<(Box<dyn IndexSource>, Option<usn::JournalData>), String> {
+    let path = Path::new(&index_options.path);
+    let is_root = path.components().count() == 2; // check if the path is a root directory (e.g., C:\) - C: and \ counts as two components
+    let is_ntfs = src_lib::is_ntfs(path);
+
+    // the usn-journal-rs reader can only index the entire root, the ntfs one can start anywhere
+    let use_mft =
+        scan_options.image.is_some() || (is_ntfs && (is_root || !scan_options.legacy_mft));
+    let mut journal = None;
+
+    let source: Box<dyn IndexSource> = if let Some((file, format)) = &scan_options.listing {
+        progress!(
+            "Importing {} for path: {}",
+            file.display(),
+            index_options.path
+        );
+        Box::new(ListingSource::read(file, *format, path)?)
+    } else if let Some(url) = &scan_options.remote {
+        progress!("Listing {} for path: {}", url, index_options.path);
+        Box::new(RemoteSource::new(remote::client_for(url)?, path)?)
+    } else if let Some(device) = &scan_options.ext4 {
+        // ext2/3/4 read straight from the device
+        progress!(
+            "Using ext4 metadata from {} for path: {}",
+            device,
+            index_options.path
+        );
+        let mut file = File::open(device).map_err(|e| format!("Failed to open device: {}", e))?;
+        let mount_point = scan_options
+            .mount_point
+            .as_deref()
+            .unwrap_or(&index_options.path);
+        Box::new(Ext4Source::read(&mut file, path, Path::new(mount_point))?)
+    } else if !use_mft {
+        // Non-NTFS filesystem / not Windows
+        progress!("Not using NTFS MFT for path: {}", index_options.path);
+        Box::new(WalkSource::new(path, index_options.index_files))
+    } else if !scan_options.legacy_mft {
+        // NTFS filesystem, read with the ntfs crate
+        progress!("Using NTFS MFT for path: {}", index_options.path);
+        match &scan_options.image {
+            // an image is indexed whole under the path
+            Some(image) => {
+                let file = File::open(image).map_err(|e| format!("Failed to open image: {}", e))?;
+                Box::new(MftSource::read(&mut BufReader::new(file), path, &[])?)
+            }
+            None => {
+                let drive_letter = get_drive_letter(index_options.path.clone());
+                journal = usn::query_journal(drive_letter)
+                    .inspect_err(|e| eprintln!("No USN journal, update won't work: {e}"))
+                    .ok();
+                let volume = File::open(format!(r"\\.\{}:", drive_letter))
+                    .map_err(|e| format!("Failed to open volume: {}", e))?;
+                // C:\ and \ are the first two components, the rest are names below the root
+                let components: Vec<&str> = path
+                    .components()
+                    .skip(2)
+                    .filter_map(|c| c.as_os_str().to_str())
+                    .collect();
+                Box::new(MftSource::read(
+                    &mut mft::SectorReader::new(volume, 512),
+                    path,
+                    &components,
+                )?)
+            }
+        }
+    } else {
+        // usn-journal-rs only builds on windows
+        #[cfg(not(target_os = "windows"))]
+        return Err("The usn-journal-rs MFT reader only works on Windows".to_string());
+        #[cfg(target_os = "windows")]
+        {
+            progress!("Using NTFS MFT for path: {}", index_options.path);
+            let drive_letter = get_drive_letter(index_options.path.clone());
+            Box::new(sources::LegacyMftSource::open(drive_letter)?)
+        }
+    };
+    Ok((source, journal))
+}
+
+fn init_db(conn: &Connection) -> Result<(), Failure> {
+    src_lib::init_db(conn).map_err(|e| {
+        Failure::new(
+            FailureKind::Database,
+            format!("Failed to initialize database: {}", e),
+        )
+    })
+}
+
+// for the rusqlite errors that end up in the exit code as database failures
+fn database_failure(what: &str) -> impl Fn(rusqlite::Error) -> Failure + '_ {
+    move |e| Failure::new(FailureKind::Database, format!("Failed to {}: {}", what, e))
+}
+
+// everything computed from the folders and files, rebuilt after they change
+fn build_derived(conn: &Connection) -> Result<(), Failure> {
+    // link folders to their parents so the index can be browsed without touching the disk
+    let linked =
+        src_lib::resolve_parent_ids(conn).map_err(database_failure("resolve parent folders"))?;
+    progress!("Linked {} folders to their parent", linked);
+
+    src_lib::compare::build_entry_metadata(conn)
+        .map_err(database_failure("build entry metadata"))?;
+
+    let searchable = src_lib::search::build_search_index(conn)
+        .map_err(database_failure("build search index"))?;
+    progress!("Built search index with {} entries", searchable);
+
+    let summarized = src_lib::contents::build_folder_stats(conn)
+        .map_err(database_failure("build folder stats"))?;
+    progress!("Summarized contents of {} folders", summarized);
+    Ok(())
+}
+
+fn save_journal_position(conn: &Connection, journal_id: u64, next_usn: i64) -> Result<(), Failure> {
+    src_lib::set_index_meta(conn, usn::META_JOURNAL_ID, &journal_id.to_string())
+        .and_then(|_| src_lib::set_index_meta(conn, usn::META_NEXT_USN, &next_usn.to_string()))
+        .map_err(database_failure("save USN journal position"))
+}
+
+// bring an MFT index up to date from the USN journal, or index again from scratch when the
+// journal can't say what changed (it was recreated, or the changes were already dropped from it)
+fn update_index(
+    app_data_dir: &Path,
+    index_options: IndexOptions,
+    scan_options: ScanOptions,
+) -> Result<Report, Failure> {
+    let db_path = src_lib::get_index_db_path(app_data_dir, &index_options.path)?;
+    if !db_path.exists() {
+        progress!("No index yet for path: {}", index_options.path);
+        return start_indexing(app_data_dir, index_options, scan_options);
+    }
+    let mut db = Connection::open(&db_path).map_err(|e| {
+        Failure::new(
+            FailureKind::Database,
+            format!("Failed to open database: {}", e),
+        )
+    })?;
+    init_db(&db)?;
+
+    let drive_letter = get_drive_letter(index_options.path.clone());
+    let journal =
+        usn::query_journal(drive_letter).map_err(|e| Failure::new(FailureKind::Source, e))?;
+    let read_meta = |key| {
+        src_lib::get_index_meta(&db, key)
+            .map_err(|e| format!("Failed to read index metadata: {}", e))
+    };
+    let stored_id = read_meta(usn::META_JOURNAL_ID)?.and_then(|id| id.parse::<u64>().ok());
+    let stored_usn = read_meta(usn::META_NEXT_USN)?.and_then(|usn| usn.parse::<i64>().ok());
+    let start_usn = match (stored_id, stored_usn) {
+        (Some(id), Some(usn)) if id == journal.journal_id && usn >= journal.first_usn => usn,
+        _ => {
+            progress!(
+                "USN journal changed or was truncated, rescanning: {}",
+                index_options.path
+            );
+            drop(db);
+            let scan_options = ScanOptions {
+                replace: true,
+                ..scan_options
+            };
+            return start_indexing(app_data_dir, index_options, scan_options);
+        }
+    };
+
+    let start_time = Instant::now();
+    let (next_usn, records) = usn::read_journal(drive_letter, &journal, start_usn)
+        .map_err(|e| Failure::new(FailureKind::Source, e))?;
+    progress!("Read {} USN records since {}", records.len(), start_usn);
+
+    let transaction = db.transaction().map_err(|e| {
+        Failure::new(
+            FailureKind::Database,
+            format!("Failed to begin transaction: {}", e),
+        )
+    })?;
+    let mut exclude_counts: HashMap<&'static str, usize> = HashMap::new();
+    let counts = usn::apply(&transaction, &index_options, &records, &mut exclude_counts)
+        .map_err(|e| Failure::new(FailureKind::Database, e))?;
+    build_derived(&transaction)?;
+    save_journal_position(&transaction, journal.journal_id, next_usn)?;
+    transaction.commit().map_err(|e| {
+        Failure::new(
+            FailureKind::Database,
+            format!("Failed to commit transaction: {}", e),
+        )
+    })?;
+
+    let duration = start_time.elapsed();
+    Ok(Report::new(
+        format!(
+            "Updated index in {:.3?} ({} created, {} deleted, {} moved, {} ignored)",
+            duration, counts.created, counts.deleted, counts.moved, counts.ignored
+        ),
+        json!({
+            "created": counts.created,
+            "deleted": counts.deleted,
+            "moved": counts.moved,
+            "ignored": counts.ignored,
+            "seconds": duration.as_secs_f64(),
+        }),
     ))
 }
+
+// update over and over, until killed or an update fails
+fn watch_index(
+    app_data_dir: &Path,
+    index_options: IndexOptions,
+    interval: Duration,
+) -> Result<Report, Failure> {
+    loop {
+        let report = update_index(app_data_dir, index_options.clone(), ScanOptions::default())?;
+        progress!("{}", report.message);
+        std::thread::sleep(interval);
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use ntfs::structured_values::NtfsFileNamespace;
+use ntfs::{KnownNtfsFileRecordNumber, Ntfs, NtfsFile, NtfsFileFlags};
+
+// reads the MFT straight off an NTFS volume or image with the `ntfs` crate
+// every file record is read exactly once into an FRN -> (parent, name) table, paths are built
+// from that table afterwards without going back to the disk
+
+pub const ROOT_FRN: u64 = KnownNtfsFileRecordNumber::RootDirectory as u64;
+// records below this are NTFS metafiles ($MFT, $LogFile, ...) or reserved, except for the root
+const FIRST_USER_FRN: u64 = 24;
+// seconds between 1601-01-01 (NTFS timestamps) and 1970-01-01
+const NT_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+#[derive(Debug, Clone)]
+pub struct MftRecord {
+    pub frn: u64,
+    pub parent: u64,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: Option<u64>,
+    pub modified: Option<i64>, // unix secs
+}
+
+// raw volumes (\\.\C:) only allow reads of whole, aligned sectors, this does the aligning
+pub struct SectorReader<R: Read + Seek> {
+    inner: R,
+    sector_size: usize,
+    position: u64,
+    buffer: Vec<u8>,
+    buffer_start: u64,
+    buffer_len: usize,
+}
+
+impl<R: Read + Seek> SectorReader<R> {
+    pub fn new(inner: R, sector_size: usize) -> Self {
+        SectorReader {
+            inner,
+            sector_size,
+            position: 0,
+            // 64 KiB worth of sectors per read
+            buffer: vec![0; sector_size * (65_536 / sector_size).max(1)],
+            buffer_start: 0,
+            buffer_len: 0,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for SectorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let buffered = self.buffer_start..self.buffer_start + self.buffer_len as u64;
+        if !buffered.contains(&self.position) {
+            self.buffer_start = self.position - self.position % self.sector_size as u64;
+            self.inner.seek(SeekFrom::Start(self.buffer_start))?;
+            self.buffer_len = self.inner.read(&mut self.buffer)?;
+            if self.buffer_len == 0 {
+                return Ok(0);
+            }
+        }
+
+        let offset = (self.position - self.buffer_start) as usize;
+        let count = buf.len().min(self.buffer_len.saturating_sub(offset));
+        buf[..count].copy_from_slice(&self.buffer[offset..offset + count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for SectorReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(delta) => {
+                self.position.checked_add_signed(delta).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start")
+                })?
+            }
+            SeekFrom::End(delta) => {
+                let end = self.inner.seek(SeekFrom::End(0))?;
+                end.checked_add_signed(delta).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start")
+                })?
+            }
+        };
+        Ok(self.position)
+    }
+}
+
+// streams every in-use file record of the MFT, in FRN order
+pub struct MftRecords<'a, T: Read + Seek> {
+    fs: &'a mut T,
+    ntfs: Ntfs,
+    next: u64,
+    count: u64,
+    pub unreadable: usize, // in-use records the ntfs crate couldn't read
+}
+
+impl<'a, T: Read + Seek> MftRecords<'a, T> {
+    pub fn new(fs: &'a mut T) -> Result<Self, String> {
+        let mut ntfs = Ntfs::new(fs).map_err(|e| format!("Not an NTFS volume: {}", e))?;
+        ntfs.read_upcase_table(fs)
+            .map_err(|e| format!("Failed to read upcase table: {}", e))?;
+
+        // the MFT describes itself in record 0, its data size gives the number of records
+        let mft_size = {
+            let mft = ntfs
+                .file(fs, KnownNtfsFileRecordNumber::MFT as u64)
+                .map_err(|e| format!("Failed to read $MFT: {}", e))?;
+            let data = mft
+                .data(fs, "")
+                .ok_or("$MFT has no data")?
+                .map_err(|e| format!("Failed to read $MFT data: {}", e))?;
+            data.to_attribute()
+                .map_err(|e| format!("Failed to read $MFT size: {}", e))?
+                .value_length()
+        };
+        let count = mft_size / ntfs.file_record_size() as u64;
+
+        Ok(MftRecords {
+            fs,
+            ntfs,
+            next: FIRST_USER_FRN,
+            count,
+            unreadable: 0,
+        })
+    }
+
+    fn read_record(&mut self, frn: u64) -> Option<MftRecord> {
+        // ntfs reads a record in one go, one that straddles two MFT fragments fails
+        let Ok(file) = self.ntfs.file(self.fs, frn) else {
+            self.unreadable += 1;
+            return None;
+        };
+        if !file.flags().contains(NtfsFileFlags::IN_USE) {
+            return None;
+        }
+        // extension records and DOS-only names have no name of their own worth indexing
+        let name = [
+            NtfsFileNamespace::Win32AndDos,
+            NtfsFileNamespace::Win32,
+            NtfsFileNamespace::Posix,
+        ]
+        .into_iter()
+        .find_map(|namespace| file.name(self.fs, Some(namespace), None)?.ok())?;
+
+        let is_dir = file.is_directory();
+        Some(MftRecord {
+            frn,
+            parent: name.parent_directory_reference().file_record_number(),
+            name: name.name().to_string_lossy(),
+            is_dir,
+            size: if is_dir {
+                None
+            } else {
+                Some(data_size(&file, self.fs).unwrap_or(name.data_size()))
+            },
+            modified: file
+                .info()
+                .ok()
+                .map(|info| info.modification_time().nt_timestamp())
+                .map(|t| (t / 10_000_000) as i64 - NT_EPOCH_OFFSET),
+        })
+    }
+}
+
+fn data_size<T: Read + Seek>(file: &NtfsFile, fs: &mut T) -> Option<u64> {
+    let item = file.data(fs, "")?.ok()?;
+    Some(item.to_attribute().ok()?.value_length())
+}
+
+impl<T: Read + Seek> Iterator for MftRecords<'_, T> {
+    type Item = MftRecord;
+
+    fn next(&mut self) -> Option<MftRecord> {
+        while self.next < self.count {
+            let frn = self.next;
+            self.next += 1;
+            if let Some(record) = self.read_record(frn) {
+                return Some(record);
+            }
+        }
+        None
+    }
+}
+
+// the single pass: everything needed to build paths, keyed by FRN
+#[derive(Default)]
+pub struct MftTable {
+    pub records: HashMap<u64, MftRecord>,
+    pub unreadable: usize, // records that couldn't be read, their entries are missing
+}
+
+impl MftTable {
+    pub fn read<T: Read + Seek>(fs: &mut T) -> Result<Self, String> {
+        let mut table = MftTable::default();
+        let mut records = MftRecords::new(fs)?;
+        for record in records.by_ref() {
+            table.records.insert(record.frn, record);
+        }
+        table.unreadable = records.unreadable;
+        Ok(table)
+    }
+
+    // follow directory names down from the volume root, NTFS names are case-insensitive
+    pub fn find_dir(&self, components: &[&str]) -> Option<u64> {
+        components.iter().try_fold(ROOT_FRN, |parent, component| {
+            let component = component.to_lowercase();
+            self.records
+                .values()
+                .find(|record| {
+                    record.is_dir
+                        && record.parent == parent
+                        && record.name.to_lowercase() == component
+                })
+                .map(|record| record.frn)
+        })
+    }
+
+    // walk up the parent chain until `base` or a directory that's already resolved
+    // records that never reach `base` (outside of it, metafiles, orphans, cycles) have no path,
+    // and neither does anything below them, so their chains go into `outside`
+    fn resolve(
+        &self,
+        frn: u64,
+        base: u64,
+        root: &Path,
+        cache: &mut HashMap<u64, PathBuf>,
+        outside: &mut HashSet<u64>,
+    ) -> Option<PathBuf> {
+        let mut chain = Vec::new();
+        let mut current = frn;
+        let found = loop {
+            if current == base {
+                break Some(root.to_path_buf());
+            }
+            if let Some(path) = cache.get(&current) {
+                break Some(path.clone());
+            }
+            // no real tree is this deep, but a corrupted one can loop forever
+            if current == ROOT_FRN || outside.contains(&current) || chain.len() > 4096 {
+                break None;
+            }
+            let Some(record) = self.records.get(&current) else {
+                break None;
+            };
+            chain.push(record);
+            current = record.parent;
+        };
+        let Some(mut path) = found else {
+            outside.extend(chain.iter().map(|record| record.frn));
+            return None;
+        };
+
+        for record in chain.into_iter().rev() {
+            path.push(&record.name);
+            if record.is_dir {
+                cache.insert(record.frn, path.clone());
+            }
+        }
+        Some(path)
+    }
+
+    // every record below the directory `base`, with its full path under `root` (the path of `base`)
+    // parents always come before their children
+    pub fn paths(&self, base: u64, root: &Path) -> Vec<(PathBuf, &MftRecord)> {
+        let mut cache = HashMap::new();
+        let mut outside = HashSet::new();
+        let mut paths: Vec<_> = self
+            .records
+            .values()
+            .filter(|record| record.frn != base)
+            .filter_map(|record| {
+                let path = self.resolve(record.frn, base, root, &mut cache, &mut outside)?;
+                Some((path, record))
+            })
+            .collect();
+        paths.sort_by(|(a, _), (b, _)| a.cmp(b));
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    // testfs1 from the ntfs crate, see testdata/README.md for how it was made
+    fn image() -> File {
+        File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/ntfs.img")).unwrap()
+    }
+
+    #[test]
+    fn reads_the_image_tree() {
+        let table = MftTable::read(&mut image()).unwrap();
+        // 512 byte clusters and a fragmented MFT, the record of many_subdirs/187 straddles two fragments
+        assert_eq!(table.unreadable, 1);
+
+        let root = Path::new("/ntfs");
+        let paths = table.paths(ROOT_FRN, root);
+        let relative = |path: &PathBuf| path.strip_prefix(root).unwrap().to_path_buf();
+        let files: Vec<_> = paths
+            .iter()
+            .filter(|(_, record)| !record.is_dir)
+            .map(|(path, record)| (relative(path), record.size))
+            .collect();
+        let expected_files = [
+            ("1000-bytes-file", 1000),
+            ("empty-file", 0),
+            ("file-with-12345", 5),
+            ("sparse-file", 500_005),
+        ]
+        .map(|(name, size)| (PathBuf::from(name), Some(size)));
+        assert_eq!(files, expected_files);
+
+        // metafiles ($MFT, $Extend/$Quota...) aren't part of the tree
+        let dirs: HashSet<_> = paths
+            .iter()
+            .filter(|(_, record)| record.is_dir)
+            .map(|(path, _)| relative(path))
+            .collect();
+        let mut expected_dirs: HashSet<_> = (1..=512)
+            .filter(|i| *i != 187)
+            .map(|i| Path::new("many_subdirs").join(i.to_string()))
+            .collect();
+        expected_dirs.insert(PathBuf::from("many_subdirs"));
+        assert_eq!(dirs, expected_dirs);
+    }
+
+    #[test]
+    fn paths_start_at_any_directory() {
+        let table = MftTable::read(&mut SectorReader::new(image(), 512)).unwrap();
+        let base = table.find_dir(&["MANY_SUBDIRS"]).unwrap();
+        let paths = table.paths(base, Path::new("/many"));
+        assert_eq!(paths.len(), 511);
+        assert!(paths.iter().all(|(_, record)| record.parent == base));
+        assert_eq!(paths[0].0, Path::new("/many/1"));
+
+        assert_eq!(table.find_dir(&["many_subdirs", "missing"]), None);
+        assert_eq!(table.find_dir(&["empty-file"]), None);
+    }
+
+    #[test]
+    fn chains_outside_the_base_are_walked_once() {
+        let record = |frn, parent, name: &str, is_dir| MftRecord {
+            frn,
+            parent,
+            name: name.to_string(),
+            is_dir,
+            size: None,
+            modified: None,
+        };
+        let mut table = MftTable::default();
+        for entry in [
+            record(100, ROOT_FRN, "base", true),
+            record(101, 100, "inside.txt", false),
+            record(200, ROOT_FRN, "other", true),
+            record(201, 200, "deeper", true),
+            record(202, 201, "outside.txt", false),
+        ] {
+            table.records.insert(entry.frn, entry);
+        }
+
+        let (mut cache, mut outside) = (HashMap::new(), HashSet::new());
+        let root = Path::new("/base");
+        assert!(
+            table
+                .resolve(202, 100, root, &mut cache, &mut outside)
+                .is_none()
+        );
+        assert_eq!(outside, HashSet::from([200, 201, 202]));
+        // the next record up there stops at the first known directory
+        table
+            .records
+            .insert(203, record(203, 201, "more.txt", false));
+        assert!(
+            table
+                .resolve(203, 100, root, &mut cache, &mut outside)
+                .is_none()
+        );
+        assert_eq!(outside.len(), 4);
+        assert_eq!(
+            table.resolve(101, 100, root, &mut cache, &mut outside),
+            Some(PathBuf::from("/base/inside.txt"))
+        );
+    }
+
+    #[test]
+    fn rejects_other_images() {
+        let ext4 = File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/ext4.img"));
+        assert!(MftTable::read(&mut ext4.unwrap()).is_err());
+        let mut truncated = std::io::Cursor::new(
+            std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/ntfs.img")).unwrap(),
+        );
+        truncated.get_mut().truncate(16 * 1024);
+        assert!(MftTable::read(&mut truncated).is_err());
+    }
+
+    // the ntfs reader against the usn-journal-rs one on a live volume, needs admin:
+    // cargo test --release -p src-sidecar compare_with_usn_journal_rs -- --ignored --nocapture
+    #[cfg(target_os = "windows")]
+    #[test]
+    #[ignore]
+    fn compare_with_usn_journal_rs() {
+        use std::time::Instant;
+        use usn_journal_rs::{mft::Mft, path::MftPathResolver, volume::Volume};
+
+        let start_time = Instant::now();
+        let volume = File::open(r"\\.\C:").unwrap();
+        let table = MftTable::read(&mut SectorReader::new(volume, 512)).unwrap();
+        let ours: HashSet<PathBuf> = table
+            .paths(ROOT_FRN, Path::new(r"C:\"))
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        let ours_time = start_time.elapsed();
+
+        let start_time = Instant::now();
+        let mft = Mft::new(Volume::from_drive_letter('C').unwrap());
+        let mut resolver = MftPathResolver::new(&mft);
+        let theirs: HashSet<PathBuf> = mft
+            .iter()
+            .filter_map(|entry| resolver.resolve_path(&entry))
+            .collect();
+        let theirs_time = start_time.elapsed();
+
+        println!(
+            "ntfs: {} paths in {:.3?} ({} unreadable), usn-journal-rs: {} paths in {:.3?}",
+            ours.len(),
+            ours_time,
+            table.unreadable,
+            theirs.len(),
+            theirs_time
+        );
+        println!(
+            "{} paths only from ntfs, {} only from usn-journal-rs",
+            ours.difference(&theirs).count(),
+            theirs.difference(&ours).count()
+        );
+    }
+}
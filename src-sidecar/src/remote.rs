@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+// directory listings from something that isn't mounted, an SFTP/SMB share, WebDAV, an S3 bucket...
+// a client only has to list one directory at a time, RemoteSource does the walking
+
+#[derive(Debug, Clone)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: Option<u64>,
+    pub modified: Option<i64>, // unix secs
+}
+
+pub trait RemoteClient {
+    fn name(&self) -> &'static str;
+    // `dir` is relative to the client's root, '/' separated, "" for the root itself
+    fn list(&mut self, dir: &str) -> Result<Vec<RemoteEntry>, String>;
+}
+
+// a local directory behind the client interface, stands in for a server when testing
+pub struct LocalClient {
+    root: PathBuf,
+}
+
+impl LocalClient {
+    pub fn new(root: PathBuf) -> Self {
+        LocalClient { root }
+    }
+}
+
+impl RemoteClient for LocalClient {
+    fn name(&self) -> &'static str {
+        "local directory"
+    }
+
+    fn list(&mut self, dir: &str) -> Result<Vec<RemoteEntry>, String> {
+        let path = dir
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .fold(self.root.clone(), |path, name| path.join(name));
+        let mut entries = Vec::new();
+        for entry in
+            std::fs::read_dir(&path).map_err(|e| format!("Failed to list {}: {}", dir, e))?
+        {
+            let entry = entry.map_err(|e| format!("Failed to list {}: {}", dir, e))?;
+            let Some(name) = entry.file_name().to_str().map(String::from) else {
+                eprintln!("Name not UTF-8: {:?}", entry.path());
+                continue;
+            };
+            let metadata = entry
+                .metadata()
+                .map_err(|e| format!("Failed to read metadata: {}", e))?;
+            entries.push(RemoteEntry {
+                name,
+                is_dir: metadata.is_dir(),
+                size: metadata.is_file().then_some(metadata.len()),
+                modified: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+// picks the client from the url's scheme
+pub fn client_for(url: &str) -> Result<Box<dyn RemoteClient>, String> {
+    let (scheme, rest) = url.split_once("://").ok_or(format!("Not a url: {}", url))?;
+    match scheme.to_ascii_lowercase().as_str() {
+        "file" => Ok(Box::new(LocalClient::new(PathBuf::from(rest)))),
+        // TODO: sftp, smb, webdav and s3 clients
+        _ => Err(format!("No client for {}:// urls yet", scheme)),
+    }
+}
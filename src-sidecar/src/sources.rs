@@ -0,0 +1,523 @@
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use jwalk::WalkDir;
+use src_lib::FileMetadata;
+use src_lib::source::{IndexEntry, IndexSource};
+#[cfg(target_os = "windows")]
+use usn_journal_rs::{mft::Mft, path::MftPathResolver, volume::Volume};
+
+use crate::ext4::Ext4Table;
+use crate::listing::{self, ListedEntry, ListingFormat};
+use crate::mft::MftTable;
+use crate::remote::{RemoteClient, RemoteEntry};
+
+// the IndexSource implementations, everything after reading the entries is done by src_lib's IndexSink
+
+fn metadata_of(path: &Path, size: Option<u64>, modified: Option<i64>) -> FileMetadata {
+    FileMetadata {
+        size,
+        modified,
+        extension: src_lib::extension_of(path),
+    }
+}
+
+// a plain tree walk, works everywhere but needs a stat per file for its metadata
+pub struct WalkSource {
+    root: PathBuf,
+    with_metadata: bool, // no point in the stat when files aren't indexed
+}
+
+impl WalkSource {
+    pub fn new(root: &Path, with_metadata: bool) -> Self {
+        WalkSource {
+            root: root.to_path_buf(),
+            with_metadata,
+        }
+    }
+}
+
+impl IndexSource for WalkSource {
+    fn name(&self) -> &'static str {
+        "tree walk"
+    }
+
+    fn entries(&mut self) -> Result<Box<dyn Iterator<Item = IndexEntry> + '_>, String> {
+        let with_metadata = self.with_metadata;
+        let entries = WalkDir::new(&self.root)
+            .into_iter()
+            .filter_map(move |entry| {
+                let entry = entry
+                    .inspect_err(|e| eprintln!("Error reading directory: {e}"))
+                    .ok()?;
+                let path = entry.path();
+                Some(if entry.depth == 0 {
+                    IndexEntry::root(&path)
+                } else if entry.file_type().is_dir() {
+                    IndexEntry::folder(path)
+                } else if with_metadata {
+                    let metadata = src_lib::file_metadata(&path);
+                    IndexEntry::file(path, metadata)
+                } else {
+                    let metadata = metadata_of(&path, None, None);
+                    IndexEntry::file(path, metadata)
+                })
+            });
+        Ok(Box::new(entries))
+    }
+}
+
+// the MFT read with the ntfs crate, from a volume or an image
+pub struct MftSource {
+    table: MftTable,
+    base: u64,
+    root: PathBuf,
+}
+
+impl MftSource {
+    // `components` lead from the volume root to the indexed directory, `root` is its path
+    pub fn read<T: Read + Seek>(
+        fs: &mut T,
+        root: &Path,
+        components: &[&str],
+    ) -> Result<Self, String> {
+        let start_time = Instant::now();
+        let table = MftTable::read(fs)?;
+        progress!(
+            "Read {} MFT records in {:.3?} ({} records couldn't be read)",
+            table.records.len(),
+            start_time.elapsed(),
+            table.unreadable
+        );
+        let base = table
+            .find_dir(components)
+            .ok_or_else(|| format!("Directory not found in MFT: {}", root.display()))?;
+        Ok(MftSource {
+            table,
+            base,
+            root: root.to_path_buf(),
+        })
+    }
+}
+
+impl IndexSource for MftSource {
+    fn name(&self) -> &'static str {
+        "NTFS MFT"
+    }
+
+    fn entries(&mut self) -> Result<Box<dyn Iterator<Item = IndexEntry> + '_>, String> {
+        // the MFT paths start below the root, so add the root itself first
+        let root = IndexEntry::root(&self.root).with_frn(self.base);
+        let entries = self
+            .table
+            .paths(self.base, &self.root)
+            .into_iter()
+            .map(|(path, record)| {
+                let entry = if record.is_dir {
+                    IndexEntry::folder(path)
+                } else {
+                    let metadata = metadata_of(&path, record.size, record.modified);
+                    IndexEntry::file(path, metadata)
+                };
+                entry.with_frn(record.frn)
+            });
+        Ok(Box::new(std::iter::once(root).chain(entries)))
+    }
+}
+
+// the usn-journal-rs reader, kept around to benchmark against, only does whole volumes
+// TODO: ok the way we're accessing MFT is so god damn slow (even slower than jwalk!!) w/ the crate being used, we need to switch.
+// TODO: at least, it doesn't use as much memory
+#[cfg(target_os = "windows")]
+pub struct LegacyMftSource {
+    mft: Mft,
+}
+
+#[cfg(target_os = "windows")]
+impl LegacyMftSource {
+    pub fn open(drive_letter: char) -> Result<Self, String> {
+        let volume = Volume::from_drive_letter(drive_letter)
+            .map_err(|e| format!("Failed to open volume: {}", e))?;
+        Ok(LegacyMftSource {
+            mft: Mft::new(volume),
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl IndexSource for LegacyMftSource {
+    fn name(&self) -> &'static str {
+        "NTFS MFT (usn-journal-rs)"
+    }
+
+    fn entries(&mut self) -> Result<Box<dyn Iterator<Item = IndexEntry> + '_>, String> {
+        let mft = &self.mft;
+        let mut path_resolver = MftPathResolver::new(mft);
+        progress!("Starting MFT scan...");
+        // records come in FRN order, parents that show up late get created by the sink
+        let entries = mft.iter().filter_map(move |entry| {
+            let path = path_resolver.resolve_path(&entry)?;
+            Some(if entry.is_dir() {
+                IndexEntry::folder(path)
+            } else {
+                let metadata = src_lib::file_metadata(&path);
+                IndexEntry::file(path, metadata)
+            })
+        });
+        Ok(Box::new(entries))
+    }
+}
+
+// ext2/3/4 metadata read from a block device or image
+pub struct Ext4Source {
+    table: Ext4Table,
+    base: u32,
+    root: PathBuf,
+}
+
+impl Ext4Source {
+    // `root` is the indexed directory, somewhere below where the filesystem is mounted
+    pub fn read<T: Read + Seek>(
+        fs: &mut T,
+        root: &Path,
+        mount_point: &Path,
+    ) -> Result<Self, String> {
+        let start_time = Instant::now();
+        let table = Ext4Table::read(fs)?;
+        progress!(
+            "Read {} inodes in {:.3?} ({} directories couldn't be read)",
+            table.inodes.len(),
+            start_time.elapsed(),
+            table.skipped_dirs
+        );
+
+        let components: Vec<&str> = root
+            .strip_prefix(mount_point)
+            .map_err(|_| format!("{} is not below the mount point", root.display()))?
+            .components()
+            .filter_map(|c| c.as_os_str().to_str())
+            .collect();
+        let base = table
+            .find_dir(&components)
+            .ok_or_else(|| format!("Directory not found in filesystem: {}", root.display()))?;
+        Ok(Ext4Source {
+            table,
+            base,
+            root: root.to_path_buf(),
+        })
+    }
+}
+
+impl IndexSource for Ext4Source {
+    fn name(&self) -> &'static str {
+        "ext4 inodes"
+    }
+
+    fn entries(&mut self) -> Result<Box<dyn Iterator<Item = IndexEntry> + '_>, String> {
+        let root = IndexEntry::root(&self.root);
+        let entries = self
+            .table
+            .paths(self.base, &self.root)
+            .into_iter()
+            .map(|(path, inode)| {
+                if inode.is_dir {
+                    IndexEntry::folder(path)
+                } else {
+                    let metadata = metadata_of(&path, Some(inode.size), Some(inode.modified));
+                    IndexEntry::file(path, metadata)
+                }
+            });
+        Ok(Box::new(std::iter::once(root).chain(entries)))
+    }
+}
+
+// a directory listed through a RemoteClient, one request per folder, rows end up under `root`
+// like they would for a tree walk of the same directory
+pub struct RemoteSource {
+    client: Box<dyn RemoteClient>,
+    root: PathBuf,
+    listing: Vec<RemoteEntry>, // the root's, listed up front
+}
+
+impl RemoteSource {
+    // the root failing to list is an error here, before anything is written, anything below
+    // it is only skipped
+    pub fn new(mut client: Box<dyn RemoteClient>, root: &Path) -> Result<Self, String> {
+        let listing = client.list("")?;
+        Ok(RemoteSource {
+            client,
+            root: root.to_path_buf(),
+            listing,
+        })
+    }
+}
+
+impl IndexSource for RemoteSource {
+    fn name(&self) -> &'static str {
+        self.client.name()
+    }
+
+    fn on_disk(&self) -> bool {
+        false
+    }
+
+    fn entries(&mut self) -> Result<Box<dyn Iterator<Item = IndexEntry> + '_>, String> {
+        let root = IndexEntry::root(&self.root);
+        let listing = std::mem::take(&mut self.listing);
+        let mut pending = vec![(String::new(), self.root.clone(), listing)];
+        let client = &mut self.client;
+        let entries = std::iter::from_fn(move || {
+            loop {
+                let (dir, dir_path, listing) = pending.last_mut()?;
+                let Some(remote) = listing.pop() else {
+                    pending.pop();
+                    continue;
+                };
+                // jwalk skips dot files by default, skipping them here too keeps the rows the same
+                if remote.name.starts_with('.') {
+                    continue;
+                }
+                let path = dir_path.join(&remote.name);
+                if !remote.is_dir {
+                    let metadata = metadata_of(&path, remote.size, remote.modified);
+                    return Some(IndexEntry::file(path, metadata));
+                }
+                let child = if dir.is_empty() {
+                    remote.name
+                } else {
+                    format!("{}/{}", dir, remote.name)
+                };
+                match client.list(&child) {
+                    Ok(listing) => pending.push((child, path.clone(), listing)),
+                    Err(e) => eprintln!("Error reading directory: {e}"),
+                }
+                return Some(IndexEntry::folder(path));
+            }
+        });
+        Ok(Box::new(std::iter::once(root).chain(entries)))
+    }
+}
+
+// a listing made somewhere else, nothing below `root` has to exist here
+pub struct ListingSource {
+    entries: Vec<ListedEntry>,
+    root: PathBuf,
+}
+
+impl ListingSource {
+    pub fn read(file: &Path, format: ListingFormat, root: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(file).map_err(|e| format!("Failed to read listing: {}", e))?;
+        let entries = listing::parse(&listing::decode(&bytes), format)?;
+        let listed = entries.len();
+        let (entries, outside, listing_root) = listing::resolve(entries, root);
+        progress!(
+            "Read {} listed paths, {} entries with the missing folders added ({} outside the listing)",
+            listed,
+            entries.len(),
+            outside
+        );
+        if let Some(listing_root) = listing_root {
+            progress!(
+                "Listed paths are in {}, indexing them under {}",
+                listing_root.display(),
+                root.display()
+            );
+        }
+        Ok(ListingSource {
+            entries,
+            root: root.to_path_buf(),
+        })
+    }
+}
+
+impl IndexSource for ListingSource {
+    fn name(&self) -> &'static str {
+        "path listing"
+    }
+
+    fn on_disk(&self) -> bool {
+        false
+    }
+
+    fn entries(&mut self) -> Result<Box<dyn Iterator<Item = IndexEntry> + '_>, String> {
+        let root = IndexEntry::root(&self.root);
+        let entries = self.entries.drain(..).map(|entry| {
+            if entry.is_dir == Some(true) {
+                IndexEntry::folder(entry.path)
+            } else {
+                let metadata = metadata_of(&entry.path, entry.size, entry.modified);
+                IndexEntry::file(entry.path, metadata)
+            }
+        });
+        Ok(Box::new(std::iter::once(root).chain(entries)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    use rusqlite::Connection;
+    use src_lib::IndexOptions;
+    use src_lib::source::IndexSink;
+
+    use crate::ext4::tests::{make_tree, scratch_dir, testdata};
+
+    fn options(root: &Path) -> IndexOptions {
+        IndexOptions {
+            path: root.to_string_lossy().to_string(),
+            index_directories: true,
+            index_files: true,
+            ..Default::default()
+        }
+    }
+
+    fn index(source: &mut dyn IndexSource, root: &Path) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        src_lib::init_db(&conn).unwrap();
+        let options = options(root);
+        IndexSink::new(&conn, &options).write_all(source).unwrap();
+        conn
+    }
+
+    // folders, and files with their size, relative to `root`
+    type Rows = (Vec<PathBuf>, Vec<(PathBuf, Option<u64>)>);
+
+    fn rows(conn: &Connection, root: &Path) -> Rows {
+        let relative = |path: String| Path::new(&path).strip_prefix(root).unwrap().to_path_buf();
+        let folders = conn
+            .prepare("SELECT path FROM folders ORDER BY path")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|path| relative(path.unwrap()))
+            .collect();
+        let files = conn
+            .prepare("SELECT path, size FROM files ORDER BY path")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|row| {
+                let (path, size) = row.unwrap();
+                (relative(path), size)
+            })
+            .collect();
+        (folders, files)
+    }
+
+    #[test]
+    fn walk_and_ext4_give_the_same_rows() {
+        let root = scratch_dir("sources-tree");
+        make_tree(&root);
+        let walked = rows(&index(&mut WalkSource::new(&root, true), &root), &root);
+        assert_eq!((walked.0.len(), walked.1.len()), (12, 1004));
+
+        // the image was made from the same tree, as if it were mounted at `root`
+        let mut image = File::open(testdata("ext4.img")).unwrap();
+        let mut source = Ext4Source::read(&mut image, &root, &root).unwrap();
+        let (folders, files) = rows(&index(&mut source, &root), &root);
+        let folders: Vec<_> = folders
+            .into_iter()
+            .filter(|path| !path.starts_with("lost+found"))
+            .collect();
+        assert_eq!((folders, files), walked);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn remote_gives_the_same_rows_as_a_walk() {
+        let root = scratch_dir("sources-remote");
+        make_tree(&root);
+        let walked = index(&mut WalkSource::new(&root, true), &root);
+
+        let client = Box::new(crate::remote::LocalClient::new(root.clone()));
+        let mut source = RemoteSource::new(client, &root).unwrap();
+        assert!(!source.on_disk());
+        let listed = index(&mut source, &root);
+        assert_eq!(rows(&listed, &root), rows(&walked, &root));
+
+        // everything the walk stats comes from the client's listing
+        let metadata = |conn: &Connection| -> Vec<(String, Option<i64>, Option<String>)> {
+            conn.prepare("SELECT path, modified, extension FROM files ORDER BY path")
+                .unwrap()
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .unwrap()
+                .map(|row| row.unwrap())
+                .collect()
+        };
+        assert_eq!(metadata(&listed), metadata(&walked));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn ext4_indexes_below_the_mount_point() {
+        let mount_point = Path::new("/mnt/image");
+        let root = mount_point.join("docs");
+        let mut image = File::open(testdata("ext4.img")).unwrap();
+        let mut source = Ext4Source::read(&mut image, &root, mount_point).unwrap();
+        let (folders, files) = rows(&index(&mut source, &root), &root);
+        assert_eq!(folders, ["", "notes", "notes/2024"].map(PathBuf::from));
+        assert_eq!(
+            files,
+            [("notes/2024/plan.md", 3000), ("readme.txt", 6)]
+                .map(|(path, size)| (PathBuf::from(path), Some(size)))
+        );
+
+        let missing = mount_point.join("missing");
+        assert!(Ext4Source::read(&mut image, &missing, mount_point).is_err());
+        assert!(Ext4Source::read(&mut image, Path::new("/elsewhere"), mount_point).is_err());
+    }
+
+    #[test]
+    fn mft_rows_keep_their_frns() {
+        let root = Path::new("/ntfs");
+        let mut image = File::open(testdata("ntfs.img")).unwrap();
+        let conn = index(&mut MftSource::read(&mut image, root, &[]).unwrap(), root);
+        let (folders, files) = rows(&conn, root);
+        // the root, many_subdirs and 511 of its 512 subdirs, see mft::tests
+        assert_eq!((folders.len(), files.len()), (513, 4));
+        assert_eq!(files[0], (PathBuf::from("1000-bytes-file"), Some(1000)));
+
+        let without_frn: i64 = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM folders WHERE frn IS NULL)
+                    + (SELECT COUNT(*) FROM files WHERE frn IS NULL)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(without_frn, 0);
+
+        let root = Path::new("/subdirs");
+        let mut source = MftSource::read(&mut image, root, &["Many_Subdirs"]).unwrap();
+        let (folders, files) = rows(&index(&mut source, root), root);
+        assert_eq!((folders.len(), files.len()), (512, 0));
+    }
+
+    #[test]
+    fn listings_need_nothing_on_disk() {
+        let dir = scratch_dir("sources-listing");
+        let file = dir.join("listing.txt");
+        std::fs::write(
+            &file,
+            "f\t6\t1700000000.25\t./docs/readme.txt\n\
+             d\t4096\t1700000000\t./empty\n\
+             f\t3000\t1700000000\t./docs/notes/2024/plan.md\n",
+        )
+        .unwrap();
+
+        let root = Path::new("/listed");
+        let mut source = ListingSource::read(&file, ListingFormat::Find, root).unwrap();
+        let (folders, files) = rows(&index(&mut source, root), root);
+        let folders_expected = ["", "docs", "docs/notes", "docs/notes/2024", "empty"];
+        assert_eq!(folders, folders_expected.map(PathBuf::from));
+        assert_eq!(
+            files,
+            [("docs/notes/2024/plan.md", 3000), ("docs/readme.txt", 6)]
+                .map(|(path, size)| (PathBuf::from(path), Some(size)))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
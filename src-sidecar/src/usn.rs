@@ -0,0 +1,791 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{MAIN_SEPARATOR, Path};
+
+use rusqlite::{Connection, OptionalExtension};
+use src_lib::IndexOptions;
+
+// follows the NTFS change journal so an MFT index can be brought up to date without a rescan
+// the parsing only works on byte buffers, the Windows side just fills them with DeviceIoControl
+
+// reason flags, see USN_RECORD_V2 in winioctl.h
+pub const USN_REASON_FILE_CREATE: u32 = 0x0000_0100;
+pub const USN_REASON_FILE_DELETE: u32 = 0x0000_0200;
+pub const USN_REASON_RENAME_NEW_NAME: u32 = 0x0000_2000;
+#[cfg(any(target_os = "windows", test))]
+pub const USN_REASON_CLOSE: u32 = 0x8000_0000;
+const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+
+// index_meta keys
+pub const META_JOURNAL_ID: &str = "usn_journal_id";
+pub const META_NEXT_USN: &str = "usn_next";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalData {
+    pub journal_id: u64,
+    pub first_usn: i64, // anything older has been dropped from the journal
+    pub next_usn: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsnRecord {
+    pub frn: u64,
+    pub parent: u64,
+    pub usn: i64,
+    pub reason: u32,
+    pub attributes: u32,
+    pub name: String,
+}
+
+impl UsnRecord {
+    pub fn is_dir(&self) -> bool {
+        self.attributes & FILE_ATTRIBUTE_DIRECTORY != 0
+    }
+}
+
+// the parsers below are only needed by the Windows volume reader and the tests
+#[cfg(any(target_os = "windows", test))]
+fn bytes_at<const N: usize>(buf: &[u8], offset: usize) -> Result<[u8; N], String> {
+    buf.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            format!(
+                "Buffer too short: {} bytes, needed {}",
+                buf.len(),
+                offset + N
+            )
+        })
+}
+
+#[cfg(any(target_os = "windows", test))]
+fn u16_at(buf: &[u8], offset: usize) -> Result<u16, String> {
+    Ok(u16::from_le_bytes(bytes_at(buf, offset)?))
+}
+
+#[cfg(any(target_os = "windows", test))]
+fn u32_at(buf: &[u8], offset: usize) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(bytes_at(buf, offset)?))
+}
+
+#[cfg(any(target_os = "windows", test))]
+fn u64_at(buf: &[u8], offset: usize) -> Result<u64, String> {
+    Ok(u64::from_le_bytes(bytes_at(buf, offset)?))
+}
+
+#[cfg(any(target_os = "windows", test))]
+fn i64_at(buf: &[u8], offset: usize) -> Result<i64, String> {
+    Ok(i64::from_le_bytes(bytes_at(buf, offset)?))
+}
+
+// file references carry a sequence number in the top 16 bits, the MFT reader only knows the record number
+#[cfg(any(target_os = "windows", test))]
+fn record_number(frn: u64) -> u64 {
+    frn & 0x0000_FFFF_FFFF_FFFF
+}
+
+// output of FSCTL_QUERY_USN_JOURNAL, every version of USN_JOURNAL_DATA starts the same way
+#[cfg(any(target_os = "windows", test))]
+pub fn parse_journal_data(buf: &[u8]) -> Result<JournalData, String> {
+    Ok(JournalData {
+        journal_id: u64_at(buf, 0)?,
+        first_usn: i64_at(buf, 8)?,
+        next_usn: i64_at(buf, 16)?,
+    })
+}
+
+// a single USN_RECORD_V2 or USN_RECORD_V3, V3 only has wider (128-bit) file references
+#[cfg(any(target_os = "windows", test))]
+pub fn parse_record(buf: &[u8]) -> Result<UsnRecord, String> {
+    let length = u32_at(buf, 0)? as usize;
+    let buf = buf
+        .get(..length)
+        .ok_or_else(|| format!("USN record is cut off: {} of {} bytes", buf.len(), length))?;
+    let (frn, parent, rest) = match u16_at(buf, 4)? {
+        2 => (u64_at(buf, 8)?, u64_at(buf, 16)?, 24),
+        3 => (u64_at(buf, 8)?, u64_at(buf, 24)?, 40),
+        version => return Err(format!("Unsupported USN record version: {}", version)),
+    };
+
+    let name_length = u16_at(buf, rest + 32)? as usize;
+    let name_offset = u16_at(buf, rest + 34)? as usize;
+    let name = buf
+        .get(name_offset..name_offset + name_length)
+        .ok_or("USN record name is out of bounds")?
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect::<Vec<_>>();
+
+    Ok(UsnRecord {
+        frn: record_number(frn),
+        parent: record_number(parent),
+        usn: i64_at(buf, rest)?,
+        reason: u32_at(buf, rest + 16)?,
+        attributes: u32_at(buf, rest + 28)?,
+        name: String::from_utf16_lossy(&name),
+    })
+}
+
+// output of FSCTL_READ_USN_JOURNAL: the USN to continue from, then the records back to back
+#[cfg(any(target_os = "windows", test))]
+pub fn parse_records(buf: &[u8]) -> Result<(i64, Vec<UsnRecord>), String> {
+    let next_usn = i64_at(buf, 0)?;
+    let mut records = Vec::new();
+    let mut offset = 8;
+    while offset < buf.len() {
+        let length = u32_at(buf, offset)? as usize;
+        if length == 0 {
+            return Err(format!("Empty USN record at offset {}", offset));
+        }
+        records.push(parse_record(&buf[offset..])?);
+        offset += length;
+    }
+    Ok((next_usn, records))
+}
+
+#[cfg(target_os = "windows")]
+mod volume {
+    use std::fs::File;
+    use std::os::windows::io::AsRawHandle;
+    use std::ptr;
+
+    use winapi::ctypes::c_void;
+    use winapi::um::ioapiset::DeviceIoControl;
+
+    use super::*;
+
+    const FSCTL_QUERY_USN_JOURNAL: u32 = 0x0009_00f4;
+    const FSCTL_READ_USN_JOURNAL: u32 = 0x0009_00bb;
+
+    // READ_USN_JOURNAL_DATA_V0
+    #[repr(C)]
+    struct ReadJournalData {
+        start_usn: i64,
+        reason_mask: u32,
+        return_only_on_close: u32,
+        timeout: u64,
+        bytes_to_wait_for: u64,
+        journal_id: u64,
+    }
+
+    fn open(drive_letter: char) -> Result<File, String> {
+        File::open(format!(r"\\.\{}:", drive_letter))
+            .map_err(|e| format!("Failed to open volume: {}", e))
+    }
+
+    fn control<T>(
+        file: &File,
+        code: u32,
+        input: Option<&T>,
+        output: &mut [u8],
+    ) -> Result<usize, String> {
+        let mut returned = 0u32;
+        let (input, input_size) = match input {
+            Some(input) => (input as *const T as *mut c_void, size_of::<T>() as u32),
+            None => (ptr::null_mut(), 0),
+        };
+        let ok = unsafe {
+            DeviceIoControl(
+                file.as_raw_handle() as _,
+                code,
+                input,
+                input_size,
+                output.as_mut_ptr() as *mut c_void,
+                output.len() as u32,
+                &mut returned,
+                ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        Ok(returned as usize)
+    }
+
+    pub fn query_journal(drive_letter: char) -> Result<JournalData, String> {
+        let file = open(drive_letter)?;
+        let mut buf = [0u8; 80];
+        let len = control::<()>(&file, FSCTL_QUERY_USN_JOURNAL, None, &mut buf)
+            .map_err(|e| format!("Failed to query USN journal: {}", e))?;
+        parse_journal_data(&buf[..len])
+    }
+
+    pub fn read_journal(
+        drive_letter: char,
+        journal: &JournalData,
+        start_usn: i64,
+    ) -> Result<(i64, Vec<UsnRecord>), String> {
+        let file = open(drive_letter)?;
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut request = ReadJournalData {
+            start_usn,
+            reason_mask: USN_REASON_FILE_CREATE
+                | USN_REASON_FILE_DELETE
+                | USN_REASON_RENAME_NEW_NAME
+                | USN_REASON_CLOSE,
+            // the close record has every reason the file collected, so there's one per change
+            return_only_on_close: 1,
+            timeout: 0,
+            bytes_to_wait_for: 0, // don't wait for new records
+            journal_id: journal.journal_id,
+        };
+        let mut records = Vec::new();
+        while request.start_usn < journal.next_usn {
+            let len = control(&file, FSCTL_READ_USN_JOURNAL, Some(&request), &mut buf)
+                .map_err(|e| format!("Failed to read USN journal: {}", e))?;
+            let (next_usn, batch) = parse_records(&buf[..len])?;
+            request.start_usn = next_usn;
+            if batch.is_empty() {
+                break;
+            }
+            records.extend(batch);
+        }
+        Ok((request.start_usn, records))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod volume {
+    use super::*;
+
+    pub fn query_journal(_drive_letter: char) -> Result<JournalData, String> {
+        Err("The USN journal is only available on Windows".to_string())
+    }
+
+    pub fn read_journal(
+        _drive_letter: char,
+        _journal: &JournalData,
+        _start_usn: i64,
+    ) -> Result<(i64, Vec<UsnRecord>), String> {
+        Err("The USN journal is only available on Windows".to_string())
+    }
+}
+
+pub use volume::{query_journal, read_journal};
+
+#[derive(Debug, Default)]
+pub struct UpdateCounts {
+    pub created: usize,
+    pub deleted: usize,
+    pub moved: usize,
+    pub ignored: usize,
+}
+
+fn table(is_dir: bool) -> &'static str {
+    if is_dir { "folders" } else { "files" }
+}
+
+fn path_of(conn: &Connection, table: &str, frn: u64) -> Result<Option<(i64, String)>, String> {
+    conn.query_row(
+        &format!("SELECT id, path FROM {table} WHERE frn = ?1"),
+        rusqlite::params![frn],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to look up {}: {}", table, e))
+}
+
+// the entry and, for folders, everything below it
+fn remove(conn: &Connection, record: &UsnRecord) -> Result<bool, String> {
+    let Some((id, path)) = path_of(conn, table(record.is_dir()), record.frn)? else {
+        return Ok(false);
+    };
+    if record.is_dir() {
+        let prefix = format!("{}{}", path, MAIN_SEPARATOR);
+        for table in ["files", "folders"] {
+            conn.execute(
+                &format!("DELETE FROM {table} WHERE substr(path, 1, length(?1)) = ?1"),
+                rusqlite::params![prefix],
+            )
+            .map_err(|e| format!("Failed to delete from {}: {}", table, e))?;
+        }
+    }
+    conn.execute(
+        &format!("DELETE FROM {} WHERE id = ?1", table(record.is_dir())),
+        rusqlite::params![id],
+    )
+    .map_err(|e| format!("Failed to delete entry: {}", e))?;
+    Ok(true)
+}
+
+// move an entry (and a folder's contents) to its new path, replacing whatever was there
+fn rename(
+    conn: &Connection,
+    record: &UsnRecord,
+    old: &str,
+    new: &str,
+    parent_id: i64,
+) -> Result<(), String> {
+    let old_prefix = format!("{}{}", old, MAIN_SEPARATOR);
+    let new_prefix = format!("{}{}", new, MAIN_SEPARATOR);
+    if record.is_dir() {
+        for table in ["files", "folders"] {
+            conn.execute(
+                &format!(
+                    "UPDATE OR REPLACE {table} SET path = ?2 || substr(path, length(?1) + 1)
+                     WHERE substr(path, 1, length(?1)) = ?1"
+                ),
+                rusqlite::params![old_prefix, new_prefix],
+            )
+            .map_err(|e| format!("Failed to move {}: {}", table, e))?;
+        }
+        conn.execute(
+            "UPDATE OR REPLACE folders SET path = ?1, parent_id = ?2 WHERE frn = ?3",
+            rusqlite::params![new, parent_id, record.frn],
+        )
+    } else {
+        conn.execute(
+            "UPDATE OR REPLACE files SET path = ?1, folder_id = ?2 WHERE frn = ?3",
+            rusqlite::params![new, parent_id, record.frn],
+        )
+    }
+    .map_err(|e| format!("Failed to move entry: {}", e))?;
+    Ok(())
+}
+
+fn insert(conn: &Connection, record: &UsnRecord, path: &str, parent_id: i64) -> Result<(), String> {
+    if record.is_dir() {
+        conn.execute(
+            "INSERT OR REPLACE INTO folders (path, parent_id, frn) VALUES (?1, ?2, ?3)",
+            rusqlite::params![path, parent_id, record.frn],
+        )
+    } else {
+        let meta = src_lib::file_metadata(Path::new(path));
+        conn.execute(
+            "INSERT OR REPLACE INTO files (path, folder_id, size, modified, extension, frn)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                path,
+                parent_id,
+                meta.size,
+                meta.modified,
+                meta.extension,
+                record.frn
+            ],
+        )
+    }
+    .map_err(|e| format!("Failed to insert entry: {}", e))?;
+    Ok(())
+}
+
+// apply journal records in order, entries are found through their FRN so only MFT indexes can be updated
+pub fn apply(
+    conn: &Connection,
+    index_options: &IndexOptions,
+    records: &[UsnRecord],
+    exclude_counts: &mut HashMap<&'static str, usize>,
+) -> Result<UpdateCounts, String> {
+    let mut counts = UpdateCounts::default();
+    // with return_only_on_close a new folder's record can come after its children's, those wait
+    // for the folder instead of being dropped as outside the index
+    let new_folders: HashSet<u64> = records
+        .iter()
+        .filter(|record| {
+            record.is_dir()
+                && record.reason & (USN_REASON_FILE_CREATE | USN_REASON_RENAME_NEW_NAME) != 0
+        })
+        .map(|record| record.frn)
+        .collect();
+    let mut apply_one = |record: &UsnRecord, can_wait: bool| {
+        apply_record(
+            conn,
+            index_options,
+            record,
+            can_wait,
+            &mut counts,
+            exclude_counts,
+        )
+    };
+
+    let mut waiting: Vec<&UsnRecord> = Vec::new();
+    for record in records {
+        // a later record for the same entry replaces the one that's waiting
+        waiting.retain(|waiting| waiting.frn != record.frn);
+        if !apply_one(record, new_folders.contains(&record.parent))? {
+            waiting.push(record);
+        }
+    }
+    // parents first, then their children, until a round gets nothing more in
+    while !waiting.is_empty() {
+        let before = waiting.len();
+        let mut still_waiting = Vec::new();
+        for record in waiting {
+            if !apply_one(record, true)? {
+                still_waiting.push(record);
+            }
+        }
+        waiting = still_waiting;
+        if waiting.len() == before {
+            // their folders never made it in, so they really are outside the index
+            for record in waiting.drain(..) {
+                apply_one(record, false)?;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+// false when the parent isn't indexed (yet) and the record was told it can wait
+fn apply_record(
+    conn: &Connection,
+    index_options: &IndexOptions,
+    record: &UsnRecord,
+    can_wait: bool,
+    counts: &mut UpdateCounts,
+    exclude_counts: &mut HashMap<&'static str, usize>,
+) -> Result<bool, String> {
+    let created = record.reason & USN_REASON_FILE_CREATE != 0;
+    let deleted = record.reason & USN_REASON_FILE_DELETE != 0;
+    let renamed = record.reason & USN_REASON_RENAME_NEW_NAME != 0;
+
+    if created && deleted {
+        // came and went between two updates
+        counts.ignored += 1;
+        return Ok(true);
+    }
+    if deleted {
+        if remove(conn, record)? {
+            counts.deleted += 1;
+        }
+        return Ok(true);
+    }
+    if !created && !renamed {
+        return Ok(true);
+    }
+    if !record.is_dir() && !index_options.index_files {
+        counts.ignored += 1;
+        return Ok(true);
+    }
+
+    // a parent that isn't indexed means the entry is outside of the index root or excluded,
+    // if it was moved there it's gone from the index's point of view
+    let parent = path_of(conn, "folders", record.parent)?;
+    if parent.is_none() && can_wait {
+        return Ok(false);
+    }
+    let target = parent
+        .map(|(parent_id, parent_path)| (parent_id, Path::new(&parent_path).join(&record.name)))
+        .filter(|(_, path)| {
+            !src_lib::should_exclude(path, index_options.clone(), exclude_counts, true)
+        });
+    let Some((parent_id, path)) = target else {
+        if remove(conn, record)? {
+            counts.deleted += 1;
+        } else {
+            counts.ignored += 1;
+        }
+        return Ok(true);
+    };
+    let Some(path) = path.to_str() else {
+        eprintln!("Path not UTF-8: {:?}", path);
+        counts.ignored += 1;
+        return Ok(true);
+    };
+
+    match path_of(conn, table(record.is_dir()), record.frn)? {
+        Some((_, old)) if old == path => {}
+        Some((_, old)) => {
+            rename(conn, record, &old, path, parent_id)?;
+            counts.moved += 1;
+        }
+        None => {
+            insert(conn, record, path, parent_id)?;
+            counts.created += 1;
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT_FRN: u64 = 5;
+    // file references as the journal hands them out, with a sequence number on top
+    const SEQUENCE: u64 = 0x0003 << 48;
+
+    // laid out like FSCTL_READ_USN_JOURNAL output, see USN_RECORD_V2/V3 in winioctl.h
+    fn record(
+        version: u16,
+        frn: u64,
+        parent: u64,
+        usn: i64,
+        reason: u32,
+        attributes: u32,
+        name: &str,
+    ) -> Vec<u8> {
+        let name: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let mut buf = vec![0u8; 8];
+        buf[4..6].copy_from_slice(&version.to_le_bytes());
+        match version {
+            2 => {
+                buf.extend((frn | SEQUENCE).to_le_bytes());
+                buf.extend((parent | SEQUENCE).to_le_bytes());
+            }
+            _ => {
+                buf.extend((frn | SEQUENCE).to_le_bytes());
+                buf.extend(0u64.to_le_bytes());
+                buf.extend((parent | SEQUENCE).to_le_bytes());
+                buf.extend(0u64.to_le_bytes());
+            }
+        }
+        buf.extend(usn.to_le_bytes());
+        buf.extend(0x01DA_0000_0000_0000i64.to_le_bytes()); // timestamp
+        buf.extend(reason.to_le_bytes());
+        buf.extend(0u32.to_le_bytes()); // source info
+        buf.extend(0u32.to_le_bytes()); // security id
+        buf.extend(attributes.to_le_bytes());
+        buf.extend((name.len() as u16).to_le_bytes());
+        // the name comes right after its offset field
+        buf.extend((buf.len() as u16 + 2).to_le_bytes());
+        buf.extend(name);
+        // records are padded to 8 bytes
+        buf.resize(buf.len().div_ceil(8) * 8, 0);
+        let length = buf.len() as u32;
+        buf[0..4].copy_from_slice(&length.to_le_bytes());
+        buf
+    }
+
+    fn journal_output(next_usn: i64, records: &[Vec<u8>]) -> Vec<u8> {
+        let mut buf = next_usn.to_le_bytes().to_vec();
+        for record in records {
+            buf.extend(record);
+        }
+        buf
+    }
+
+    #[test]
+    fn parses_v2_and_v3_records() {
+        let v2 = record(
+            2,
+            40,
+            ROOT_FRN,
+            1024,
+            USN_REASON_FILE_CREATE | USN_REASON_CLOSE,
+            0x20,
+            "notes.txt",
+        );
+        assert_eq!(v2.len(), 80);
+        let v3 = record(
+            3,
+            41,
+            40,
+            1104,
+            USN_REASON_RENAME_NEW_NAME,
+            FILE_ATTRIBUTE_DIRECTORY,
+            "Fotos ü",
+        );
+
+        let (next_usn, records) = parse_records(&journal_output(1200, &[v2, v3])).unwrap();
+        assert_eq!(next_usn, 1200);
+        assert_eq!(
+            records,
+            vec![
+                UsnRecord {
+                    frn: 40,
+                    parent: ROOT_FRN,
+                    usn: 1024,
+                    reason: USN_REASON_FILE_CREATE | USN_REASON_CLOSE,
+                    attributes: 0x20,
+                    name: "notes.txt".to_string(),
+                },
+                UsnRecord {
+                    frn: 41,
+                    parent: 40,
+                    usn: 1104,
+                    reason: USN_REASON_RENAME_NEW_NAME,
+                    attributes: FILE_ATTRIBUTE_DIRECTORY,
+                    name: "Fotos ü".to_string(),
+                },
+            ]
+        );
+        assert!(records[1].is_dir());
+    }
+
+    #[test]
+    fn parses_journal_data() {
+        // USN_JOURNAL_DATA_V0 is 56 bytes, only the first three fields are read
+        let mut buf = vec![0u8; 56];
+        buf[0..8].copy_from_slice(&0x1234u64.to_le_bytes());
+        buf[8..16].copy_from_slice(&4096i64.to_le_bytes());
+        buf[16..24].copy_from_slice(&8192i64.to_le_bytes());
+        assert_eq!(
+            parse_journal_data(&buf).unwrap(),
+            JournalData {
+                journal_id: 0x1234,
+                first_usn: 4096,
+                next_usn: 8192,
+            }
+        );
+        assert!(parse_journal_data(&buf[..20]).is_err());
+    }
+
+    #[test]
+    fn rejects_broken_buffers() {
+        let v2 = record(2, 40, ROOT_FRN, 1024, USN_REASON_CLOSE, 0, "notes.txt");
+        // cut off in the middle of the name, and before the header is complete
+        assert!(parse_record(&v2[..v2.len() - 8]).is_err());
+        assert!(parse_record(&v2[..20]).is_err());
+        assert!(parse_records(&journal_output(0, &[v2[..v2.len() - 8].to_vec()])).is_err());
+        assert!(parse_records(&[0; 4]).is_err());
+
+        // the name has to stay inside the record
+        let mut bad_name = v2.clone();
+        bad_name[56..58].copy_from_slice(&200u16.to_le_bytes());
+        assert!(parse_record(&bad_name).is_err());
+
+        let mut bad_version = v2.clone();
+        bad_version[4..6].copy_from_slice(&4u16.to_le_bytes());
+        assert!(parse_record(&bad_version).is_err());
+
+        // a zero length would never move on to the next record
+        let mut zero_length = journal_output(0, &[v2]);
+        zero_length[8..12].copy_from_slice(&0u32.to_le_bytes());
+        assert!(parse_records(&zero_length).is_err());
+
+        // nothing after the next USN is fine, the journal had nothing new
+        assert_eq!(
+            parse_records(&journal_output(77, &[])).unwrap(),
+            (77, vec![])
+        );
+    }
+
+    fn index_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        src_lib::init_db(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO folders (path, frn) VALUES (?1, ?2)",
+            rusqlite::params!["/index", ROOT_FRN],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn options() -> IndexOptions {
+        IndexOptions {
+            path: "/index".to_string(),
+            index_directories: true,
+            index_files: true,
+            ..Default::default()
+        }
+    }
+
+    fn change(frn: u64, parent: u64, reason: u32, is_dir: bool, name: &str) -> UsnRecord {
+        UsnRecord {
+            frn,
+            parent,
+            usn: 0,
+            reason: reason | USN_REASON_CLOSE,
+            attributes: if is_dir {
+                FILE_ATTRIBUTE_DIRECTORY
+            } else {
+                0x20
+            },
+            name: name.to_string(),
+        }
+    }
+
+    fn paths(conn: &Connection) -> Vec<String> {
+        conn.prepare("SELECT path FROM folders UNION ALL SELECT path FROM files ORDER BY 1")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn apply_all(conn: &Connection, records: &[UsnRecord]) -> UpdateCounts {
+        apply(conn, &options(), records, &mut HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn applies_create_rename_move_and_delete() {
+        let conn = index_db();
+        let counts = apply_all(
+            &conn,
+            &[
+                change(10, ROOT_FRN, USN_REASON_FILE_CREATE, true, "docs"),
+                change(11, 10, USN_REASON_FILE_CREATE, false, "a.txt"),
+                change(12, ROOT_FRN, USN_REASON_FILE_CREATE, false, "b.txt"),
+            ],
+        );
+        assert_eq!(counts.created, 3);
+        assert_eq!(
+            paths(&conn),
+            ["/index", "/index/b.txt", "/index/docs", "/index/docs/a.txt"]
+        );
+
+        // renaming a folder takes its contents along
+        let counts = apply_all(
+            &conn,
+            &[change(
+                10,
+                ROOT_FRN,
+                USN_REASON_RENAME_NEW_NAME,
+                true,
+                "papers",
+            )],
+        );
+        assert_eq!(counts.moved, 1);
+        assert_eq!(
+            paths(&conn),
+            [
+                "/index",
+                "/index/b.txt",
+                "/index/papers",
+                "/index/papers/a.txt"
+            ]
+        );
+
+        // moved somewhere the index doesn't cover, then deleted
+        let counts = apply_all(
+            &conn,
+            &[
+                change(10, 99, USN_REASON_RENAME_NEW_NAME, true, "papers"),
+                change(12, ROOT_FRN, USN_REASON_FILE_DELETE, false, "b.txt"),
+            ],
+        );
+        assert_eq!(counts.deleted, 2);
+        assert_eq!(paths(&conn), ["/index"]);
+
+        // created and deleted before anyone looked
+        let counts = apply_all(
+            &conn,
+            &[change(
+                13,
+                ROOT_FRN,
+                USN_REASON_FILE_CREATE | USN_REASON_FILE_DELETE,
+                false,
+                "tmp",
+            )],
+        );
+        assert_eq!((counts.created, counts.ignored), (0, 1));
+    }
+
+    #[test]
+    fn children_can_close_before_their_new_folder() {
+        let conn = index_db();
+        let counts = apply_all(
+            &conn,
+            &[
+                change(21, 20, USN_REASON_FILE_CREATE, false, "photo.jpg"),
+                change(22, 21, USN_REASON_FILE_CREATE, false, "never.txt"),
+                change(23, 24, USN_REASON_FILE_CREATE, false, "deep.txt"),
+                change(24, 20, USN_REASON_FILE_CREATE, true, "2024"),
+                change(20, ROOT_FRN, USN_REASON_FILE_CREATE, true, "photos"),
+                // a new folder outside the index, its children stay out
+                change(31, 30, USN_REASON_FILE_CREATE, false, "elsewhere.txt"),
+                change(30, 99, USN_REASON_FILE_CREATE, true, "outside"),
+            ],
+        );
+        assert_eq!((counts.created, counts.ignored), (4, 3));
+        assert_eq!(
+            paths(&conn),
+            [
+                "/index",
+                "/index/photos",
+                "/index/photos/2024",
+                "/index/photos/2024/deep.txt",
+                "/index/photos/photo.jpg"
+            ]
+        );
+    }
+}
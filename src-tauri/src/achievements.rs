@@ -0,0 +1,267 @@
+use rusqlite::{Connection, OptionalExtension, Result as RusqliteResult, ToSql};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::game::now_millis;
+use crate::stats::{open_stats_db, query_statistics};
+
+// the definitions are data, new achievements only need an entry in achievements.json
+const DEFINITIONS: &str = include_str!("achievements.json");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Definition {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub rule: Rule,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Rule {
+    // number of recorded games or levels matching the filter
+    #[serde(rename_all = "camelCase")]
+    Count {
+        target: Target,
+        #[serde(default)]
+        filter: Filter,
+        at_least: u64,
+    },
+    // most games won in a row
+    #[serde(rename_all = "camelCase")]
+    Streak { at_least: u64 },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    Games,
+    Levels,
+}
+
+// every field is optional, unset fields match everything
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Filter {
+    pub difficulty: Option<String>,
+    pub game_type: Option<String>,
+    pub outcome: Option<String>, // levels only
+    pub won: Option<bool>,
+    pub finished: Option<bool>, // games only, every level answered rather than abandoned
+    pub perfect: Option<bool>, // every level correct
+    pub max_hints_used: Option<u64>,
+    pub min_depth: Option<u64>, // levels only, components below the index root
+    pub max_time: Option<u64>,  // in seconds
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Achievement {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub progress: u64,
+    pub goal: u64,
+    pub unlocked_at: Option<u64>,
+}
+
+pub fn init_achievements(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS achievements (
+            id TEXT PRIMARY KEY,
+            unlocked_at INTEGER NOT NULL,
+            game_id INTEGER,
+            FOREIGN KEY (game_id) REFERENCES games(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn definitions() -> Result<Vec<Definition>, String> {
+    serde_json::from_str(DEFINITIONS).map_err(|e| format!("Failed to parse achievements: {}", e))
+}
+
+fn count_matching(conn: &Connection, target: Target, filter: &Filter) -> Result<u64, String> {
+    // only solo games count, hot-seat players don't unlock anything for the owner
+    let mut conditions: Vec<String> = vec!["games.player IS NULL".to_string()];
+    let mut params: Vec<Box<dyn ToSql>> = Vec::new();
+    let mut add = |condition: &str, param: Box<dyn ToSql>| {
+        params.push(param);
+        conditions.push(condition.replace('?', &format!("?{}", params.len())));
+    };
+
+    if let Some(difficulty) = &filter.difficulty {
+        add("games.difficulty = ?", Box::new(difficulty.clone()));
+    }
+    if let Some(game_type) = &filter.game_type {
+        add("games.game_type = ?", Box::new(game_type.clone()));
+    }
+    if let Some(won) = filter.won {
+        add("games.won = ?", Box::new(won));
+    }
+    if let Some(finished) = filter.finished {
+        add(
+            "NOT EXISTS (SELECT 1 FROM levels AS unplayed
+                WHERE unplayed.game_id = games.id AND unplayed.outcome IS NULL) = ?",
+            Box::new(finished),
+        );
+    }
+    if let Some(perfect) = filter.perfect {
+        add("(games.correct = games.total_levels) = ?", Box::new(perfect));
+    }
+    if let Some(max_time) = filter.max_time {
+        let column = match target {
+            Target::Games => "games.time_taken",
+            Target::Levels => "levels.time_taken",
+        };
+        add(&format!("{column} <= ?"), Box::new(max_time * 1000));
+    }
+    if let Some(max_hints_used) = filter.max_hints_used {
+        let expression = match target {
+            Target::Games => {
+                "(SELECT COALESCE(SUM(hints_used), 0) FROM levels WHERE levels.game_id = games.id)"
+            }
+            Target::Levels => "levels.hints_used",
+        };
+        add(&format!("{expression} <= ?"), Box::new(max_hints_used));
+    }
+    if let Target::Levels = target {
+        if let Some(outcome) = &filter.outcome {
+            add("levels.outcome = ?", Box::new(outcome.clone()));
+        }
+        if let Some(min_depth) = filter.min_depth {
+            add("levels.depth >= ?", Box::new(min_depth));
+        }
+    } else if filter.outcome.is_some() || filter.min_depth.is_some() {
+        return Err("outcome and minDepth only apply to levels".to_string());
+    }
+
+    let from = match target {
+        Target::Games => "games",
+        Target::Levels => "levels JOIN games ON games.id = levels.game_id",
+    };
+    let query = format!(
+        "SELECT COUNT(*) FROM {from} WHERE {}",
+        conditions.join(" AND ")
+    );
+    conn.query_row(&query, rusqlite::params_from_iter(params.iter()), |row| {
+        row.get(0)
+    })
+    .map_err(|e| format!("Failed to count achievement progress: {}", e))
+}
+
+fn progress(conn: &Connection, rule: &Rule) -> Result<(u64, u64), String> {
+    match rule {
+        Rule::Count {
+            target,
+            filter,
+            at_least,
+        } => Ok((count_matching(conn, *target, filter)?, *at_least)),
+        Rule::Streak { at_least } => {
+            Ok((query_statistics(conn, None, None, None)?.best_streak, *at_least))
+        }
+    }
+}
+
+fn unlocked_at(conn: &Connection, id: &str) -> Result<Option<u64>, String> {
+    conn.query_row(
+        "SELECT unlocked_at FROM achievements WHERE id = ?1",
+        [id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to query achievement: {}", e))
+}
+
+// check every locked achievement against the history, called after each recorded game
+pub fn evaluate(conn: &Connection, game_id: Option<i64>) -> Result<Vec<Achievement>, String> {
+    let mut unlocked = Vec::new();
+    for definition in definitions()? {
+        if unlocked_at(conn, &definition.id)?.is_some() {
+            continue;
+        }
+        let (progress, goal) = progress(conn, &definition.rule)?;
+        if progress < goal {
+            continue;
+        }
+
+        let now = now_millis();
+        conn.execute(
+            "INSERT INTO achievements (id, unlocked_at, game_id) VALUES (?1, ?2, ?3)",
+            rusqlite::params![definition.id, now, game_id],
+        )
+        .map_err(|e| format!("Failed to unlock achievement: {}", e))?;
+        unlocked.push(Achievement {
+            id: definition.id,
+            name: definition.name,
+            description: definition.description,
+            progress,
+            goal,
+            unlocked_at: Some(now),
+        });
+    }
+    Ok(unlocked)
+}
+
+#[tauri::command]
+pub fn get_achievements(app_handle: AppHandle) -> Result<Vec<Achievement>, String> {
+    let db = open_stats_db(&app_handle)?;
+    definitions()?
+        .into_iter()
+        .map(|definition| {
+            let (progress, goal) = progress(&db, &definition.rule)?;
+            Ok(Achievement {
+                unlocked_at: unlocked_at(&db, &definition.id)?,
+                id: definition.id,
+                name: definition.name,
+                description: definition.description,
+                progress: progress.min(goal),
+                goal,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tests::played;
+    use crate::stats::{init_stats_db, record_session};
+
+    fn unlocked(conn: &Connection) -> Vec<String> {
+        evaluate(conn, None)
+            .unwrap()
+            .into_iter()
+            .map(|achievement| achievement.id)
+            .collect()
+    }
+
+    #[test]
+    fn abandoned_games_are_not_finished() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_stats_db(&conn).unwrap();
+
+        record_session(&conn, &mut played(2)).unwrap();
+        assert!(!unlocked(&conn).contains(&"first_game".to_string()));
+
+        record_session(&conn, &mut played(5)).unwrap();
+        assert!(unlocked(&conn).contains(&"first_game".to_string()));
+    }
+
+    #[test]
+    fn depth_comes_from_the_index() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_stats_db(&conn).unwrap();
+        record_session(&conn, &mut played(5)).unwrap();
+
+        // the fixture's files are all dir-N/sub-N/file-N below the root
+        let filter = |min_depth| Filter {
+            min_depth: Some(min_depth),
+            ..Filter::default()
+        };
+        assert_eq!(count_matching(&conn, Target::Levels, &filter(3)).unwrap(), 5);
+        assert_eq!(count_matching(&conn, Target::Levels, &filter(4)).unwrap(), 0);
+    }
+}
@@ -0,0 +1,392 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use src_lib::GuessOutcome;
+use tauri::{AppHandle, State};
+
+use crate::game::{
+    make_level, now_millis, open_index_db, pick_levels, GameInfo, GameSession, GameSettings,
+    GameState, GameType,
+};
+
+// bump this when the layout of challenge files changes, older files must keep importing
+pub const CHALLENGE_VERSION: u32 = 1;
+
+// a finished game that can be sent to someone else to play on their copy of the same folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeFile {
+    pub version: u32,
+    pub root: ChallengeRoot,
+    pub settings: GameSettings,
+    pub seed: u64,
+    // relative to the root, always with '/' separators. Left out to keep the answers secret,
+    // the importer then draws the levels again from the seed
+    #[serde(default)]
+    pub answers: Option<Vec<String>>,
+    #[serde(default)]
+    pub answer_hash: Option<AnswerHash>,
+    // how to draw the levels again when the answers are left out, older files asked for total_levels
+    #[serde(default)]
+    pub draw: Option<LevelDraw>,
+    pub score: ChallengeScore,
+    pub created_at: u64, // unix millis
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeRoot {
+    pub path: String, // where the root was on the exporting machine
+    pub name: String, // its folder name, the same even if the drive is mounted elsewhere
+}
+
+// sha256 over the salt and the relative answers, so answers can be checked without being readable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnswerHash {
+    pub salt: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelDraw {
+    pub asked: u32,  // levels asked of the seed, every player's levels for hot-seat games
+    pub kept: usize, // levels played out of those
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeScore {
+    pub correct: usize,
+    pub close: usize,
+    pub incorrect: usize,
+}
+
+// how the answers go into the file, a hash next to readable answers would only be for show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnswerExport {
+    // left out, the importer draws them again and checks them against the hash
+    #[default]
+    Hashed,
+    // readable, for playing on an index that has changed since
+    Included,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportOptions {
+    pub answers: AnswerExport,
+}
+
+fn hash_answers(salt: &str, answers: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    for answer in answers {
+        hasher.update(b"\n");
+        hasher.update(answer.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn relative_answer(root: &Path, answer: &str) -> Result<String, String> {
+    let relative = Path::new(answer)
+        .strip_prefix(root)
+        .map_err(|_| format!("{} is not inside {}", answer, root.display()))?;
+    Ok(relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+fn absolute_answer(root: &Path, relative: &str) -> Result<String, String> {
+    let mut path = PathBuf::from(root);
+    for part in relative.split('/').filter(|part| !part.is_empty()) {
+        // challenge files come from other people, don't let them point outside the root
+        let mut components = Path::new(part).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            return Err(format!("Invalid answer path in challenge: {}", relative));
+        }
+        path.push(part);
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+fn answer_exists(db: &Connection, game_type: GameType, answer: &str) -> Result<bool, String> {
+    let table = match game_type {
+        GameType::File => "files",
+        GameType::Directory | GameType::Contents => "folders",
+    };
+    db.query_row(
+        &format!("SELECT 1 FROM {table} WHERE path = ?1"),
+        [answer],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|found| found.is_some())
+    .map_err(|e| format!("Failed to look up answer: {}", e))
+}
+
+pub fn read_challenge(path: &Path) -> Result<ChallengeFile, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read challenge: {}", e))?;
+    let value: Value =
+        serde_json::from_str(&contents).map_err(|e| format!("Failed to parse challenge: {}", e))?;
+
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or("Not a challenge file")?;
+    if version > CHALLENGE_VERSION as u64 {
+        return Err(format!(
+            "This challenge was made with a newer version of the game (version {})",
+            version
+        ));
+    }
+    serde_json::from_value(value).map_err(|e| format!("Failed to parse challenge: {}", e))
+}
+
+#[tauri::command]
+pub fn export_challenge(
+    state: State<GameState>,
+    file_path: String,
+    options: Option<ExportOptions>,
+) -> Result<ChallengeFile, String> {
+    let options = options.unwrap_or_default();
+    let game = state.0.lock().unwrap();
+    let session = game.as_ref().ok_or("No game in progress")?;
+    let challenge = make_challenge(session, options)?;
+
+    let contents = serde_json::to_string_pretty(&challenge)
+        .map_err(|e| format!("Failed to serialize challenge: {}", e))?;
+    crate::settings::write_atomic(Path::new(&file_path), &contents)?;
+    Ok(challenge)
+}
+
+fn make_challenge(session: &GameSession, options: ExportOptions) -> Result<ChallengeFile, String> {
+    if !session.is_finished() {
+        return Err("Only finished games can be exported as a challenge".to_string());
+    }
+
+    let root = Path::new(&session.index_path);
+    let answers = session
+        .levels
+        .iter()
+        .map(|level| relative_answer(root, &level.answer))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (answers, answer_hash, draw) = match options.answers {
+        AnswerExport::Included => (Some(answers), None, None),
+        AnswerExport::Hashed => {
+            // the importer can only find the answers again by drawing them from the seed
+            let asked = session.drawn.ok_or(
+                "This game's levels weren't drawn from its seed, export it with the answers included",
+            )?;
+            let salt: [u8; 16] = rand::rng().random();
+            let salt = salt
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>();
+            let answer_hash = AnswerHash {
+                hash: hash_answers(&salt, &answers),
+                salt,
+            };
+            let draw = LevelDraw {
+                asked,
+                kept: session.levels.len(),
+            };
+            (None, Some(answer_hash), Some(draw))
+        }
+    };
+    let count = |wanted: GuessOutcome| {
+        session
+            .levels
+            .iter()
+            .filter(|level| level.outcome == Some(wanted))
+            .count()
+    };
+
+    Ok(ChallengeFile {
+        version: CHALLENGE_VERSION,
+        root: ChallengeRoot {
+            path: session.index_path.clone(),
+            name: root
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| session.index_path.clone()),
+        },
+        settings: session.settings.clone(),
+        seed: session.seed,
+        answers,
+        answer_hash,
+        draw,
+        score: ChallengeScore {
+            correct: count(GuessOutcome::Correct),
+            close: count(GuessOutcome::Close),
+            incorrect: count(GuessOutcome::Incorrect),
+        },
+        created_at: now_millis(),
+    })
+}
+
+// `path_string` is the local index to play on, defaults to the root the challenge was made on
+#[tauri::command]
+pub fn import_challenge(
+    app_handle: AppHandle,
+    state: State<GameState>,
+    file_path: String,
+    path_string: Option<String>,
+) -> Result<GameInfo, String> {
+    let challenge = read_challenge(Path::new(&file_path))?;
+    let path_string = path_string.unwrap_or_else(|| challenge.root.path.clone());
+    let db = open_index_db(&app_handle, &path_string)?;
+    let session = start_challenge(&db, challenge, path_string)?;
+    println!("Starting challenge from {}", file_path);
+
+    let info = session.info();
+    *state.0.lock().unwrap() = Some(session);
+    Ok(info)
+}
+
+fn start_challenge(
+    db: &Connection,
+    challenge: ChallengeFile,
+    path_string: String,
+) -> Result<GameSession, String> {
+    let root = PathBuf::from(&path_string);
+    let asked = challenge
+        .draw
+        .map_or(challenge.settings.total_levels, |draw| draw.asked);
+
+    let answers = match &challenge.answers {
+        Some(relative) => {
+            if let Some(answer_hash) = &challenge.answer_hash {
+                if hash_answers(&answer_hash.salt, relative) != answer_hash.hash {
+                    return Err("The challenge's answers have been changed".to_string());
+                }
+            }
+            let answers = relative
+                .iter()
+                .map(|answer| absolute_answer(&root, answer))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut missing = Vec::new();
+            for (answer, relative) in answers.iter().zip(relative) {
+                if !answer_exists(db, challenge.settings.game_type, answer)? {
+                    missing.push(relative.as_str());
+                }
+            }
+            if !missing.is_empty() {
+                return Err(format!(
+                    "These answers are not in your index: {}",
+                    missing.join(", ")
+                ));
+            }
+            answers
+        }
+        // hidden answers, the same index and seed give the same levels again
+        None => {
+            let drawn = GameSettings {
+                total_levels: asked,
+                ..challenge.settings.clone()
+            };
+            let mut rng = ChaCha8Rng::seed_from_u64(challenge.seed);
+            let mut answers: Vec<String> = pick_levels(db, &drawn, &mut rng)?
+                .into_iter()
+                .map(|level| level.answer)
+                .collect();
+            if let Some(draw) = challenge.draw {
+                answers.truncate(draw.kept);
+            }
+            if let Some(answer_hash) = &challenge.answer_hash {
+                let relative = answers
+                    .iter()
+                    .map(|answer| relative_answer(&root, answer))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if hash_answers(&answer_hash.salt, &relative) != answer_hash.hash {
+                    return Err(
+                        "Your index doesn't match the one this challenge was made on".to_string(),
+                    );
+                }
+            }
+            answers
+        }
+    };
+
+    // hints are drawn from a fresh rng so they come out the same for everyone taking the challenge
+    let mut rng = ChaCha8Rng::seed_from_u64(challenge.seed);
+    let levels = answers
+        .into_iter()
+        .map(|answer| make_level(db, answer, challenge.settings.game_type, &mut rng))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut session =
+        GameSession::with_levels(path_string, challenge.seed, challenge.settings, levels);
+    if challenge.answers.is_none() {
+        session.drawn = Some(asked);
+    }
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tests::{fixture_index, settings};
+
+    fn finished(mut session: GameSession) -> GameSession {
+        session.current_level = session.levels.len();
+        session
+    }
+
+    fn answers(session: &GameSession) -> Vec<String> {
+        session
+            .levels
+            .iter()
+            .map(|level| level.answer.clone())
+            .collect()
+    }
+
+    #[test]
+    fn hotseat_games_import_with_hidden_answers() {
+        let db = fixture_index();
+        let players: Vec<String> = ["a", "b", "c"].map(String::from).to_vec();
+        // 17 folders, 10 levels each asks for 30 and keeps 15
+        let settings = GameSettings {
+            total_levels: 10,
+            ..settings(GameType::Directory)
+        };
+        let session = finished(
+            GameSession::new_hotseat(&db, "/index".to_string(), settings, Some(7), players)
+                .unwrap(),
+        );
+
+        let challenge = make_challenge(&session, ExportOptions::default()).unwrap();
+        assert!(challenge.answers.is_none());
+        let contents = serde_json::to_string(&challenge).unwrap();
+        let challenge: ChallengeFile = serde_json::from_str(&contents).unwrap();
+        let imported = start_challenge(&db, challenge, "/index".to_string()).unwrap();
+        assert_eq!(answers(&imported), answers(&session));
+    }
+
+    #[test]
+    fn only_seeded_games_export_without_answers() {
+        let db = fixture_index();
+        let session = finished(
+            GameSession::new(&db, "/index".to_string(), settings(GameType::File), Some(7)).unwrap(),
+        );
+        let included = ExportOptions {
+            answers: AnswerExport::Included,
+        };
+        let challenge = make_challenge(&session, included).unwrap();
+        let imported = finished(start_challenge(&db, challenge, "/index".to_string()).unwrap());
+        assert!(make_challenge(&imported, ExportOptions::default()).is_err());
+    }
+}
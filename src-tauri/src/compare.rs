@@ -0,0 +1,242 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use src_lib::compare::{CompareEntry, Metric};
+use tauri::{AppHandle, State};
+
+use crate::game::{open_index_db, GameDifficulty};
+
+// "which is deeper / bigger / older / has more children" quick-fire rounds
+pub struct ComparisonSession {
+    pub index_path: String,
+    pub seed: u64,
+    pub metric: Option<Metric>, // None picks a random metric every round
+    metrics: Vec<Metric>,       // the ones the index has data for
+    pub difficulty: GameDifficulty,
+    pub choices: usize,
+    pub total_rounds: Option<u32>, // None keeps going until the player stops
+    pub rounds_played: u32,
+    pub correct: u32,
+    pub streak: u32,
+    pub best_streak: u32,
+    pub total_time: Duration,
+    current: Option<Round>,
+    rng: ChaCha8Rng,
+}
+
+struct Round {
+    metric: Metric,
+    entries: Vec<CompareEntry>,
+    started_at: Instant,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonOptions {
+    pub metric: Option<Metric>,
+    pub difficulty: GameDifficulty,
+    pub choices: Option<usize>, // defaults to 2
+    pub rounds: Option<u32>,
+}
+
+#[derive(Default)]
+pub struct ComparisonState(pub Mutex<Option<ComparisonSession>>);
+
+// a round as the frontend sees it, values are only revealed after answering
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundInfo {
+    pub round: u32,
+    pub metric: Metric,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundResult {
+    pub correct: bool,
+    pub answer: usize, // index into the round's paths
+    pub values: Vec<i64>,
+    pub time_taken: f64, // in seconds
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonScore {
+    pub seed: u64,
+    pub rounds_played: u32,
+    pub correct: u32,
+    pub accuracy: f64,
+    pub streak: u32,
+    pub best_streak: u32,
+    pub average_time: f64, // in seconds, per round
+}
+
+// how close the compared values are allowed to be, see src_lib::compare::pick_round
+fn closeness(difficulty: GameDifficulty) -> f64 {
+    match difficulty {
+        GameDifficulty::Easy => 0.0,
+        GameDifficulty::Medium => 0.4,
+        GameDifficulty::Hard => 0.7,
+        GameDifficulty::Expert => 0.9,
+        GameDifficulty::Custom => 0.5,
+    }
+}
+
+impl ComparisonSession {
+    pub fn is_finished(&self) -> bool {
+        self.total_rounds
+            .is_some_and(|total| self.rounds_played >= total)
+    }
+
+    fn next_round(&mut self, db: &rusqlite::Connection) -> Result<RoundInfo, String> {
+        if self.is_finished() {
+            return Err("All rounds have been played".to_string());
+        }
+        let metric = match self.metric {
+            Some(metric) => metric,
+            None => *self
+                .metrics
+                .choose(&mut self.rng)
+                .ok_or("Nothing in this index to compare, try re-indexing")?,
+        };
+        let entries = src_lib::compare::pick_round(
+            db,
+            metric,
+            self.choices,
+            closeness(self.difficulty),
+            &mut self.rng,
+        )?;
+
+        let info = RoundInfo {
+            round: self.rounds_played,
+            metric,
+            paths: entries.iter().map(|e| e.path.clone()).collect(),
+        };
+        self.current = Some(Round {
+            metric,
+            entries,
+            started_at: Instant::now(),
+        });
+        Ok(info)
+    }
+
+    fn answer(&mut self, choice: usize) -> Result<RoundResult, String> {
+        let round = self.current.take().ok_or("No round in progress")?;
+        if choice >= round.entries.len() {
+            self.current = Some(round);
+            return Err("Invalid choice".to_string());
+        }
+
+        let time_taken = round.started_at.elapsed();
+        let answer = src_lib::compare::winner(round.metric, &round.entries).unwrap_or(0);
+        let correct = choice == answer;
+
+        self.rounds_played += 1;
+        self.total_time += time_taken;
+        if correct {
+            self.correct += 1;
+            self.streak += 1;
+            self.best_streak = self.best_streak.max(self.streak);
+        } else {
+            self.streak = 0;
+        }
+
+        Ok(RoundResult {
+            correct,
+            answer,
+            values: round.entries.iter().map(|e| e.value).collect(),
+            time_taken: time_taken.as_secs_f64(),
+            finished: self.is_finished(),
+        })
+    }
+
+    fn score(&self) -> ComparisonScore {
+        let played = self.rounds_played.max(1) as f64;
+        ComparisonScore {
+            seed: self.seed,
+            rounds_played: self.rounds_played,
+            correct: self.correct,
+            accuracy: self.correct as f64 / played,
+            streak: self.streak,
+            best_streak: self.best_streak,
+            average_time: self.total_time.as_secs_f64() / played,
+        }
+    }
+}
+
+#[tauri::command]
+pub fn start_comparison(
+    app_handle: AppHandle,
+    state: State<ComparisonState>,
+    path_string: String,
+    options: ComparisonOptions,
+    seed: Option<u64>,
+) -> Result<RoundInfo, String> {
+    let choices = options.choices.unwrap_or(2);
+    if choices < 2 {
+        return Err("A comparison needs at least two choices".to_string());
+    }
+    let seed = seed.unwrap_or_else(|| rand::rng().random_range(0..=crate::game::MAX_SEED));
+
+    let db = open_index_db(&app_handle, &path_string)?;
+    let mut session = ComparisonSession {
+        index_path: path_string,
+        seed,
+        metric: options.metric,
+        metrics: src_lib::compare::available_metrics(&db, choices)?,
+        difficulty: options.difficulty,
+        choices,
+        total_rounds: options.rounds,
+        rounds_played: 0,
+        correct: 0,
+        streak: 0,
+        best_streak: 0,
+        total_time: Duration::ZERO,
+        current: None,
+        rng: ChaCha8Rng::seed_from_u64(seed),
+    };
+    let round = session.next_round(&db)?;
+    *state.0.lock().unwrap() = Some(session);
+    Ok(round)
+}
+
+#[tauri::command]
+pub fn answer_comparison(
+    state: State<ComparisonState>,
+    choice: usize,
+) -> Result<RoundResult, String> {
+    let mut comparison = state.0.lock().unwrap();
+    comparison
+        .as_mut()
+        .ok_or("No comparison in progress")?
+        .answer(choice)
+}
+
+#[tauri::command]
+pub fn next_comparison_round(
+    app_handle: AppHandle,
+    state: State<ComparisonState>,
+) -> Result<RoundInfo, String> {
+    let mut comparison = state.0.lock().unwrap();
+    let session = comparison.as_mut().ok_or("No comparison in progress")?;
+    if session.current.is_some() {
+        return Err("Answer the current round first".to_string());
+    }
+    let db = open_index_db(&app_handle, &session.index_path)?;
+    session.next_round(&db)
+}
+
+#[tauri::command]
+pub fn get_comparison_score(state: State<ComparisonState>) -> Result<ComparisonScore, String> {
+    let comparison = state.0.lock().unwrap();
+    comparison
+        .as_ref()
+        .map(|session| session.score())
+        .ok_or("No comparison in progress".to_string())
+}
@@ -0,0 +1,269 @@
+use rusqlite::{Connection, OptionalExtension, Result as RusqliteResult};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+
+use crate::game::{
+    now_millis, open_index_db, GameDifficulty, GameInfo, GameSession, GameSettings, GameState,
+    GameType, MAX_SEED,
+};
+use crate::stats::{open_stats_db, read_game_record, GameRecord, GAME_RECORD_COLUMNS};
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyRecord {
+    pub index_path: String,
+    pub day: i64,
+    pub date: String, // YYYY-MM-DD, in UTC
+    pub game: Option<GameRecord>, // None while the attempt is still being played
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyInfo {
+    pub day: i64,
+    pub date: String,
+    pub settings: GameSettings,
+    pub played: bool,
+    pub game: Option<GameRecord>,
+    pub streak: u64, // days in a row with a daily played, up to today or yesterday
+    pub best_streak: u64,
+}
+
+// everyone gets the same rules for the daily, only the seed changes
+pub fn daily_settings() -> GameSettings {
+    GameSettings {
+        game_type: GameType::Directory,
+        difficulty: GameDifficulty::Medium,
+        hint_count: 3,
+        time_limit: 60,
+        total_levels: 5,
+        close_sensitivity: 50.0,
+        min_depth: None,
+        max_depth: None,
+        extensions: None,
+    }
+}
+
+// days since the unix epoch, the daily rolls over at midnight UTC
+pub fn today() -> i64 {
+    (now_millis() / MILLIS_PER_DAY) as i64
+}
+
+// civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+pub fn format_day(day: i64) -> String {
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+// the same index on the same day always gives the same seed, and with it the same levels
+pub fn daily_seed(index_path: &str, day: i64) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("daily|{day}|{index_path}").as_bytes());
+    let hash = hasher.finalize();
+    u64::from_be_bytes(hash[..8].try_into().unwrap()) & MAX_SEED
+}
+
+pub fn init_daily(conn: &Connection) -> RusqliteResult<()> {
+    // a row is claimed when the daily starts, so quitting halfway still uses up the attempt
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS daily_challenges (
+            index_path TEXT NOT NULL,
+            day INTEGER NOT NULL,
+            seed INTEGER NOT NULL,
+            started_at INTEGER NOT NULL,
+            game_id INTEGER,
+            PRIMARY KEY (index_path, day),
+            FOREIGN KEY (game_id) REFERENCES games(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn query_daily(conn: &Connection, index_path: Option<&str>) -> Result<Vec<DailyRecord>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT daily_challenges.index_path, daily_challenges.day,
+                daily_challenges.game_id IS NOT NULL,
+                {GAME_RECORD_COLUMNS}
+             FROM daily_challenges LEFT JOIN games ON games.id = daily_challenges.game_id
+             WHERE ?1 IS NULL OR daily_challenges.index_path = ?1
+             ORDER BY daily_challenges.day DESC"
+        ))
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let records = stmt
+        .query_map([index_path], |row| {
+            let day: i64 = row.get(1)?;
+            let game = if row.get::<_, bool>(2)? {
+                Some(read_game_record(row, 3)?)
+            } else {
+                None
+            };
+            Ok(DailyRecord {
+                index_path: row.get(0)?,
+                day,
+                date: format_day(day),
+                game,
+            })
+        })
+        .map_err(|e| format!("Failed to execute query: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to get row: {}", e))?;
+    Ok(records)
+}
+
+// days whose attempt was played to the end, abandoned ones and the one in progress break a streak
+fn completed_days(history: &[DailyRecord]) -> Vec<i64> {
+    history
+        .iter()
+        .filter(|record| {
+            record
+                .game
+                .as_ref()
+                .is_some_and(|game| game.correct + game.close + game.incorrect >= game.total_levels)
+        })
+        .map(|record| record.day)
+        .collect()
+}
+
+// (current, best) runs of consecutive days, the current one survives until a whole day is missed
+fn streaks(days: &[i64]) -> (u64, u64) {
+    let mut best = 0;
+    let mut run = 0;
+    let mut previous: Option<i64> = None;
+    // days come newest first
+    for &day in days.iter().rev() {
+        run = match previous {
+            Some(previous) if previous + 1 == day => run + 1,
+            _ => 1,
+        };
+        best = u64::max(best, run);
+        previous = Some(day);
+    }
+    let current = match days.first() {
+        Some(&last) if last >= today() - 1 => run,
+        _ => 0,
+    };
+    (current, best)
+}
+
+#[tauri::command]
+pub fn get_daily(app_handle: AppHandle, path_string: String) -> Result<DailyInfo, String> {
+    let db = open_stats_db(&app_handle)?;
+    let day = today();
+    let history = query_daily(&db, Some(&path_string))?;
+    let (streak, best_streak) = streaks(&completed_days(&history));
+    let todays = history.into_iter().find(|record| record.day == day);
+
+    Ok(DailyInfo {
+        day,
+        date: format_day(day),
+        settings: daily_settings(),
+        played: todays.is_some(),
+        game: todays.and_then(|record| record.game),
+        streak,
+        best_streak,
+    })
+}
+
+#[tauri::command]
+pub fn start_daily(
+    app_handle: AppHandle,
+    state: State<GameState>,
+    path_string: String,
+) -> Result<GameInfo, String> {
+    let stats_db = open_stats_db(&app_handle)?;
+    let day = today();
+    let claimed = stats_db
+        .query_row(
+            "SELECT 1 FROM daily_challenges WHERE index_path = ?1 AND day = ?2",
+            rusqlite::params![path_string, day],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to query daily challenge: {}", e))?;
+    if claimed.is_some() {
+        return Err("Today's daily challenge has already been played".to_string());
+    }
+
+    let seed = daily_seed(&path_string, day);
+    let index_db = open_index_db(&app_handle, &path_string)?;
+    let mut session = GameSession::new(&index_db, path_string, daily_settings(), Some(seed))?;
+    session.daily = Some(day);
+
+    stats_db
+        .execute(
+            "INSERT INTO daily_challenges (index_path, day, seed, started_at) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![session.index_path, day, seed, session.started_at],
+        )
+        .map_err(|e| format!("Failed to claim daily challenge: {}", e))?;
+    println!("Starting daily challenge {} with seed {}", format_day(day), seed);
+
+    let info = session.info();
+    *state.0.lock().unwrap() = Some(session);
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn get_daily_history(
+    app_handle: AppHandle,
+    path_string: Option<String>,
+) -> Result<Vec<DailyRecord>, String> {
+    let db = open_stats_db(&app_handle)?;
+    query_daily(&db, path_string.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(day: i64, answered: Option<u64>) -> DailyRecord {
+        DailyRecord {
+            index_path: "/index".to_string(),
+            day,
+            date: format_day(day),
+            game: answered.map(|answered| GameRecord {
+                id: day,
+                index_path: "/index".to_string(),
+                seed: 0,
+                game_type: "directory".to_string(),
+                difficulty: "medium".to_string(),
+                total_levels: 5,
+                correct: answered,
+                close: 0,
+                incorrect: 0,
+                won: answered == 5,
+                time_taken: 0.0,
+                started_at: 0,
+                ended_at: 0,
+                player: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn only_completed_dailies_count() {
+        let today = today();
+        // newest first: today in progress, yesterday done, an abandoned day before it
+        let history = [
+            record(today, None),
+            record(today - 1, Some(5)),
+            record(today - 2, Some(5)),
+            record(today - 3, Some(2)),
+            record(today - 4, Some(5)),
+        ];
+        assert_eq!(completed_days(&history), [today - 1, today - 2, today - 4]);
+        assert_eq!(streaks(&completed_days(&history)), (2, 2));
+    }
+}
@@ -0,0 +1,1134 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rusqlite::{Connection, OptionalExtension, ToSql};
+use serde::{Deserialize, Serialize};
+use src_lib::contents::{ContentsGuess, ContentsScore, FolderContents};
+use src_lib::GuessOutcome;
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+
+// largest integer JS can represent exactly, so seeds survive the round trip through the webview
+pub const MAX_SEED: u64 = (1 << 53) - 1;
+
+const TICK_INTERVAL: Duration = Duration::from_millis(250);
+// anything longer between two ticks means the app was suspended, that time doesn't count
+const MAX_TICK_GAP: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameType {
+    Directory,
+    File,
+    Contents, // reverse mode, guess what's inside a given folder
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameDifficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+    Custom, // games started from a preset
+}
+
+impl GameType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameType::Directory => "directory",
+            GameType::File => "file",
+            GameType::Contents => "contents",
+        }
+    }
+}
+
+impl GameDifficulty {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameDifficulty::Easy => "easy",
+            GameDifficulty::Medium => "medium",
+            GameDifficulty::Hard => "hard",
+            GameDifficulty::Expert => "expert",
+            GameDifficulty::Custom => "custom",
+        }
+    }
+}
+
+// mirrors GameSettings in src/lib/types/game.ts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSettings {
+    #[serde(rename = "type")]
+    pub game_type: GameType,
+    pub difficulty: GameDifficulty,
+    pub hint_count: u32,
+    pub time_limit: u32, // in seconds
+    pub total_levels: u32,
+    pub close_sensitivity: f64, // percentage of the answer's path the guess has to match
+    // only pick answers this deep below the index root
+    #[serde(default)]
+    pub min_depth: Option<u32>,
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+    // only pick files with these extensions (lowercase, without the dot)
+    #[serde(default)]
+    pub extensions: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HintKind {
+    Depth,
+    FirstLetter,
+    NameLength,
+    ParentName,
+    Extension,
+    FileCountRange,
+    SizeRange,
+    ExtensionFirstLetter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hint {
+    pub kind: HintKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Level {
+    pub answer: String,
+    pub hints: Vec<HintKind>, // in the order they are given out
+    pub hints_used: usize,
+    pub guess: Option<String>,
+    pub outcome: Option<GuessOutcome>,
+    pub started_at: Option<u64>, // unix millis
+    pub ended_at: Option<u64>,
+    pub elapsed: Duration, // time actually spent on the level, pauses excluded
+    pub timed_out: bool,
+    pub contents: Option<FolderContents>, // only for GameType::Contents
+    pub player: Option<usize>,            // whose turn it is in a hot-seat game
+    pub depth: Option<u32>,               // below the index root, from the index
+    // bought from the shop for this level
+    pub extra_hints: usize,
+    pub extra_time: Duration,
+}
+
+#[derive(Debug)]
+pub struct GameSession {
+    pub index_path: String,
+    pub seed: u64,
+    pub settings: GameSettings,
+    pub levels: Vec<Level>,
+    pub current_level: usize,
+    pub started_at: u64, // unix millis
+    pub recorded: bool,  // already written to stats.db
+    pub paused: bool,
+    pub daily: Option<i64>, // the day (since the unix epoch) for daily challenges
+    pub players: Vec<String>, // hot-seat players taking turns, empty for a solo game
+    pub waiting_for_player: bool, // the clock doesn't run until the next player takes the seat
+    // how many levels were asked of pick_levels for the seed, None when the answers came from elsewhere
+    pub drawn: Option<u32>,
+    last_tick: Instant,
+    expired: Option<usize>, // level that ran out of time and hasn't been announced yet
+}
+
+#[derive(Default)]
+pub struct GameState(pub Mutex<Option<GameSession>>);
+
+// what the frontend gets to see about a game, answers stay in the backend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameInfo {
+    pub index_path: String,
+    pub seed: u64,
+    pub settings: GameSettings,
+    pub level: usize,
+    pub total_levels: usize,
+    pub folder: Option<String>, // the folder to guess the contents of, for GameType::Contents
+    pub hints_used: usize,
+    pub time_left: Option<f64>, // in seconds, None without a time limit
+    pub paused: bool,
+    pub finished: bool,
+    pub daily: bool,
+    pub player: Option<String>, // whose turn it is in a hot-seat game
+    pub waiting_for_player: bool,
+}
+
+// one row of a hot-seat game's leaderboard or a LAN match's scoreboard
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerScore {
+    pub name: String,
+    pub rank: usize,
+    pub correct: usize,
+    pub close: usize,
+    pub incorrect: usize,
+    pub hints_used: usize,
+    pub time_taken: f64, // in seconds
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuessResult {
+    pub outcome: GuessOutcome,
+    pub answer: String,
+    pub time_taken: f64, // in seconds
+    pub timed_out: bool,
+    pub finished: bool,
+    // the guess still counts when the finished game couldn't be written to the history
+    pub save_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentsGuessResult {
+    #[serde(flatten)]
+    pub result: GuessResult,
+    pub score: ContentsScore,
+    pub actual: FolderContents,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeExpired {
+    pub level: usize,
+}
+
+impl GameSession {
+    pub fn new(
+        db: &Connection,
+        index_path: String,
+        settings: GameSettings,
+        seed: Option<u64>,
+    ) -> Result<Self, String> {
+        let seed = seed.unwrap_or_else(|| rand::rng().random_range(0..=MAX_SEED));
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let levels = pick_levels(db, &settings, &mut rng)?;
+        let drawn = settings.total_levels;
+        let mut session = Self::with_levels(index_path, seed, settings, levels);
+        session.drawn = Some(drawn);
+        Ok(session)
+    }
+
+    // players take turns level by level, everyone gets `total_levels` of their own
+    pub fn new_hotseat(
+        db: &Connection,
+        index_path: String,
+        settings: GameSettings,
+        seed: Option<u64>,
+        players: Vec<String>,
+    ) -> Result<Self, String> {
+        let seed = seed.unwrap_or_else(|| rand::rng().random_range(0..=MAX_SEED));
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut settings = settings;
+        let mut all_levels = settings.clone();
+        all_levels.total_levels = settings
+            .total_levels
+            .checked_mul(players.len() as u32)
+            .ok_or("Too many levels for this many players")?;
+        let mut levels = pick_levels(db, &all_levels, &mut rng)?;
+        // a small index can run out of answers, everyone still gets the same number of turns
+        levels.truncate(levels.len() - levels.len() % players.len());
+        if levels.is_empty() {
+            return Err(
+                "Not enough entries in the index for every player to get a level".to_string(),
+            );
+        }
+        settings.total_levels = (levels.len() / players.len()) as u32;
+        for (i, level) in levels.iter_mut().enumerate() {
+            level.player = Some(i % players.len());
+        }
+
+        let mut session = Self::with_levels(index_path, seed, settings, levels);
+        session.players = players;
+        session.waiting_for_player = true;
+        session.drawn = Some(all_levels.total_levels);
+        Ok(session)
+    }
+
+    // a game over answers that were picked elsewhere, like an imported challenge
+    pub fn with_levels(
+        index_path: String,
+        seed: u64,
+        settings: GameSettings,
+        mut levels: Vec<Level>,
+    ) -> Self {
+        let started_at = now_millis();
+        if let Some(level) = levels.first_mut() {
+            level.started_at = Some(started_at);
+        }
+
+        GameSession {
+            index_path,
+            seed,
+            settings,
+            levels,
+            current_level: 0,
+            started_at,
+            recorded: false,
+            paused: false,
+            daily: None,
+            players: Vec::new(),
+            waiting_for_player: false,
+            drawn: None,
+            last_tick: Instant::now(),
+            expired: None,
+        }
+    }
+
+    pub fn info(&self) -> GameInfo {
+        GameInfo {
+            index_path: self.index_path.clone(),
+            seed: self.seed,
+            settings: self.settings.clone(),
+            level: self.current_level,
+            total_levels: self.levels.len(),
+            folder: self
+                .levels
+                .get(self.current_level)
+                .filter(|level| level.contents.is_some())
+                .map(|level| level.answer.clone()),
+            hints_used: self
+                .levels
+                .get(self.current_level)
+                .map_or(0, |level| level.hints_used),
+            time_left: self.time_left().map(|left| left.as_secs_f64()),
+            paused: self.paused,
+            finished: self.is_finished(),
+            daily: self.daily.is_some(),
+            player: self.current_player().map(str::to_string),
+            waiting_for_player: self.waiting_for_player,
+        }
+    }
+
+    pub fn current_player(&self) -> Option<&str> {
+        let player = self.levels.get(self.current_level)?.player?;
+        self.players.get(player).map(String::as_str)
+    }
+
+    // the next player has taken the seat, start their clock
+    pub fn begin_turn(&mut self) -> Result<(), String> {
+        if !self.waiting_for_player {
+            return Err("It is already someone's turn".to_string());
+        }
+        let level = self
+            .levels
+            .get_mut(self.current_level)
+            .ok_or("Game is already finished")?;
+        level.started_at = Some(now_millis());
+        self.waiting_for_player = false;
+        self.last_tick = Instant::now();
+        Ok(())
+    }
+
+    pub fn leaderboard(&self) -> Vec<PlayerScore> {
+        let mut scores: Vec<PlayerScore> = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let levels = || self.levels.iter().filter(move |l| l.player == Some(i));
+                let count = |wanted: GuessOutcome| {
+                    levels().filter(|l| l.outcome == Some(wanted)).count()
+                };
+                PlayerScore {
+                    name: name.clone(),
+                    rank: 0,
+                    correct: count(GuessOutcome::Correct),
+                    close: count(GuessOutcome::Close),
+                    incorrect: count(GuessOutcome::Incorrect),
+                    hints_used: levels().map(|l| l.hints_used).sum(),
+                    time_taken: levels().map(|l| l.elapsed.as_secs_f64()).sum(),
+                }
+            })
+            .collect();
+        rank_scores(&mut scores);
+        scores
+    }
+
+    fn time_limit(&self) -> Option<Duration> {
+        match self.settings.time_limit {
+            0 => None,
+            secs => Some(Duration::from_secs(secs as u64)),
+        }
+    }
+
+    pub fn time_left(&self) -> Option<Duration> {
+        let limit = self.time_limit()?;
+        let level = self.levels.get(self.current_level)?;
+        Some((limit + level.extra_time).saturating_sub(level.elapsed))
+    }
+
+    // add the time since the last tick to the current level
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        let delta = now.duration_since(self.last_tick).min(MAX_TICK_GAP);
+        self.last_tick = now;
+
+        if self.paused || self.waiting_for_player {
+            return;
+        }
+        let limit = self.time_limit();
+        let Some(level) = self.levels.get_mut(self.current_level) else {
+            return;
+        };
+        level.elapsed += delta;
+
+        if let Some(limit) = limit {
+            if !level.timed_out && level.elapsed >= limit + level.extra_time {
+                level.timed_out = true;
+                self.expired = Some(self.current_level);
+            }
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.tick();
+            self.paused = true;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.last_tick = Instant::now();
+            self.paused = false;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current_level >= self.levels.len()
+    }
+
+    pub fn use_hint(&mut self) -> Result<Hint, String> {
+        if self.waiting_for_player {
+            return Err("Waiting for the next player".to_string());
+        }
+        self.tick();
+        let max_hints = self.settings.hint_count as usize;
+        let level = self
+            .levels
+            .get_mut(self.current_level)
+            .ok_or("Game is already finished")?;
+        if level.timed_out {
+            return Err("This level already ran out of time".to_string());
+        }
+        if level.hints_used >= max_hints + level.extra_hints || level.hints_used >= level.hints.len() {
+            return Err("No hints left for this level".to_string());
+        }
+
+        let kind = level.hints[level.hints_used];
+        level.hints_used += 1;
+        Ok(Hint {
+            kind,
+            text: hint_text(kind, level),
+        })
+    }
+
+    // the checks run before a shop item is spent, so applying it afterwards can't fail
+    pub fn check_extra_hint(&self) -> Result<(), String> {
+        let level = self
+            .levels
+            .get(self.current_level)
+            .ok_or("Game is already finished")?;
+        if level.timed_out {
+            return Err("This level already ran out of time".to_string());
+        }
+        // every hint the level has is already unlocked
+        if self.settings.hint_count as usize + level.extra_hints >= level.hints.len() {
+            return Err("There are no more hints for this level".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn add_extra_hint(&mut self) -> Result<(), String> {
+        self.check_extra_hint()?;
+        self.levels[self.current_level].extra_hints += 1;
+        Ok(())
+    }
+
+    pub fn check_extend_time(&self) -> Result<(), String> {
+        if self.time_limit().is_none() {
+            return Err("This game has no time limit".to_string());
+        }
+        let level = self
+            .levels
+            .get(self.current_level)
+            .ok_or("Game is already finished")?;
+        if level.timed_out {
+            return Err("This level already ran out of time".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn extend_time(&mut self, extra: Duration) -> Result<(), String> {
+        self.check_extend_time()?;
+        self.levels[self.current_level].extra_time += extra;
+        Ok(())
+    }
+
+    pub fn submit_guess(&mut self, guess: String) -> Result<GuessResult, String> {
+        if self.settings.game_type == GameType::Contents {
+            return Err("This game expects a contents guess".to_string());
+        }
+        let close_sensitivity = self.settings.close_sensitivity;
+        let outcome = |level: &Level| src_lib::score_guess(&guess, &level.answer, close_sensitivity);
+        self.finish_level(guess.clone(), outcome)
+    }
+
+    pub fn submit_contents_guess(
+        &mut self,
+        guess: ContentsGuess,
+    ) -> Result<ContentsGuessResult, String> {
+        let actual = self
+            .levels
+            .get(self.current_level)
+            .ok_or("Game is already finished")?
+            .contents
+            .clone()
+            .ok_or("This game expects a path guess")?;
+        let score = src_lib::contents::score_contents_guess(
+            &guess,
+            &actual,
+            self.settings.close_sensitivity,
+        );
+
+        let guess_text =
+            serde_json::to_string(&guess).map_err(|e| format!("Failed to serialize guess: {}", e))?;
+        let result = self.finish_level(guess_text, |_| score.outcome)?;
+        Ok(ContentsGuessResult {
+            result,
+            score,
+            actual,
+        })
+    }
+
+    // score the current level and move on to the next one
+    fn finish_level(
+        &mut self,
+        guess: String,
+        score: impl FnOnce(&Level) -> GuessOutcome,
+    ) -> Result<GuessResult, String> {
+        if self.paused {
+            return Err("Game is paused".to_string());
+        }
+        if self.waiting_for_player {
+            return Err("Waiting for the next player".to_string());
+        }
+        self.tick();
+
+        let level = self
+            .levels
+            .get_mut(self.current_level)
+            .ok_or("Game is already finished")?;
+
+        // guesses after the time limit still move the game along, but never score
+        let outcome = if level.timed_out {
+            GuessOutcome::Incorrect
+        } else {
+            score(level)
+        };
+        let now = now_millis();
+        level.guess = Some(guess);
+        level.outcome = Some(outcome);
+        level.ended_at = Some(now);
+        let answer = level.answer.clone();
+        let time_taken = level.elapsed.as_secs_f64();
+        let timed_out = level.timed_out;
+
+        self.current_level += 1;
+        if let Some(next) = self.levels.get_mut(self.current_level) {
+            next.started_at = Some(now);
+        }
+        // hand the seat over, the next level starts when the next player is ready
+        if !self.players.is_empty() && !self.is_finished() {
+            self.waiting_for_player = true;
+        }
+        Ok(GuessResult {
+            outcome,
+            answer,
+            time_taken,
+            timed_out,
+            finished: self.is_finished(),
+            save_error: None,
+        })
+    }
+}
+
+// draw the answers for every level from the index
+// ordered by path so the same index + seed always gives the same levels, no matter the insert order
+pub fn pick_levels(
+    db: &Connection,
+    settings: &GameSettings,
+    rng: &mut ChaCha8Rng,
+) -> Result<Vec<Level>, String> {
+    // NULL filters match everything, archive members can't be opened to check a guess
+    let depth_filter =
+        "NOT in_archive AND (?1 IS NULL OR depth >= ?1) AND (?2 IS NULL OR depth <= ?2)";
+    let from = match settings.game_type {
+        GameType::Directory => format!("folders WHERE {depth_filter}"),
+        GameType::File => format!(
+            "files WHERE {depth_filter}
+             AND (?3 IS NULL OR extension IN (SELECT value FROM json_each(?3)))"
+        ),
+        // only folders that actually have files in them
+        GameType::Contents => format!(
+            "folder_stats JOIN folders ON folders.id = folder_stats.folder_id WHERE {depth_filter}"
+        ),
+    };
+    let extensions = match &settings.extensions {
+        Some(extensions) if !extensions.is_empty() => Some(
+            serde_json::to_string(
+                &extensions
+                    .iter()
+                    .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                    .collect::<Vec<_>>(),
+            )
+            .map_err(|e| format!("Failed to serialize extensions: {}", e))?,
+        ),
+        _ => None,
+    };
+    let mut filter_params: Vec<Box<dyn ToSql>> =
+        vec![Box::new(settings.min_depth), Box::new(settings.max_depth)];
+    if settings.game_type == GameType::File {
+        filter_params.push(Box::new(extensions));
+    }
+
+    let count: u64 = db
+        .query_row(
+            &format!("SELECT COUNT(*) FROM {from}"),
+            rusqlite::params_from_iter(filter_params.iter()),
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to count entries: {}", e))?;
+    if count == 0 {
+        return Err(format!(
+            "Nothing to play with in DB for game type: {}",
+            settings.game_type.as_str()
+        ));
+    }
+
+    let mut stmt = db
+        .prepare(&format!(
+            "SELECT path FROM {from} ORDER BY path LIMIT 1 OFFSET ?{}",
+            filter_params.len() + 1
+        ))
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+
+    let mut picked = HashSet::new();
+    let mut levels = Vec::new();
+    while levels.len() < settings.total_levels as usize && (picked.len() as u64) < count {
+        let offset = rng.random_range(0..count);
+        if !picked.insert(offset) {
+            continue;
+        }
+        let answer: String = stmt
+            .query_row(
+                rusqlite::params_from_iter(
+                    filter_params
+                        .iter()
+                        .map(|p| p as &dyn ToSql)
+                        .chain(std::iter::once(&offset as &dyn ToSql)),
+                ),
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to get path from row: {}", e))?;
+
+        levels.push(make_level(db, answer, settings.game_type, rng)?);
+    }
+    Ok(levels)
+}
+
+// a level for a known answer, with its hints in a seeded random order
+pub fn make_level(
+    db: &Connection,
+    answer: String,
+    game_type: GameType,
+    rng: &mut ChaCha8Rng,
+) -> Result<Level, String> {
+    let mut hints = match game_type {
+        GameType::Directory => vec![
+            HintKind::Depth,
+            HintKind::FirstLetter,
+            HintKind::NameLength,
+            HintKind::ParentName,
+        ],
+        GameType::File => vec![
+            HintKind::Depth,
+            HintKind::FirstLetter,
+            HintKind::NameLength,
+            HintKind::ParentName,
+            HintKind::Extension,
+        ],
+        GameType::Contents => vec![
+            HintKind::FileCountRange,
+            HintKind::SizeRange,
+            HintKind::ExtensionFirstLetter,
+        ],
+    };
+    hints.shuffle(rng);
+
+    let contents = match game_type {
+        GameType::Contents => Some(src_lib::contents::get_folder_contents(db, &answer)?),
+        _ => None,
+    };
+    let table = match game_type {
+        GameType::File => "files",
+        GameType::Directory | GameType::Contents => "folders",
+    };
+    let depth = db
+        .query_row(
+            &format!("SELECT depth FROM {table} WHERE path = ?1"),
+            [&answer],
+            |row| row.get::<_, Option<u32>>(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to get depth: {}", e))?
+        .flatten();
+
+    Ok(Level {
+        answer,
+        hints,
+        hints_used: 0,
+        guess: None,
+        outcome: None,
+        started_at: None,
+        ended_at: None,
+        elapsed: Duration::ZERO,
+        timed_out: false,
+        contents,
+        player: None,
+        depth,
+        extra_hints: 0,
+        extra_time: Duration::ZERO,
+    })
+}
+
+// best first: most correct, then most close, then the fastest
+pub fn rank_scores(scores: &mut [PlayerScore]) {
+    scores.sort_by(|a, b| {
+        b.correct
+            .cmp(&a.correct)
+            .then(b.close.cmp(&a.close))
+            .then(a.time_taken.total_cmp(&b.time_taken))
+    });
+    for (i, score) in scores.iter_mut().enumerate() {
+        score.rank = i + 1;
+    }
+}
+
+pub fn hint_text(kind: HintKind, level: &Level) -> String {
+    let path = Path::new(&level.answer);
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match kind {
+        // counted from the index root, not the root of the drive
+        HintKind::Depth => match level.depth {
+            Some(depth) => format!("It is {} levels deep", depth),
+            None => "Its depth isn't known".to_string(),
+        },
+        HintKind::FirstLetter => format!(
+            "Its name starts with \"{}\"",
+            name.chars().next().unwrap_or_default()
+        ),
+        HintKind::NameLength => format!("Its name is {} characters long", name.chars().count()),
+        HintKind::ParentName => format!(
+            "It is inside a folder called \"{}\"",
+            path.parent()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default()
+        ),
+        HintKind::Extension => match path.extension() {
+            Some(ext) => format!("It is a .{} file", ext.to_string_lossy()),
+            None => "It has no extension".to_string(),
+        },
+        HintKind::FileCountRange => {
+            let count = level.contents.as_ref().map_or(0, |c| c.file_count);
+            format!(
+                "It has between {} and {} files",
+                count - count / 4,
+                count + count / 4 + 1
+            )
+        }
+        HintKind::SizeRange => {
+            let size = level.contents.as_ref().map_or(0, |c| c.total_size);
+            format!("Its files add up to roughly {}", format_size(size))
+        }
+        HintKind::ExtensionFirstLetter => {
+            match level
+                .contents
+                .as_ref()
+                .and_then(|c| c.dominant_extension.as_ref())
+            {
+                Some(ext) => format!(
+                    "The most common extension starts with \"{}\"",
+                    ext.chars().next().unwrap_or_default()
+                ),
+                None => "None of its files have an extension".to_string(),
+            }
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.0} {}", size, units[unit])
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub fn open_index_db(app_handle: &AppHandle, path_string: &str) -> Result<Connection, String> {
+    let app_data_dir = crate::app_data_dir(app_handle);
+    let db_path = src_lib::get_index_db_path(&app_data_dir, path_string)?;
+    if !db_path.exists() {
+        return Err(format!("No index found for: {}", path_string));
+    }
+    let db = Connection::open(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    // brings indexes from older versions up to the current schema
+    src_lib::init_db(&db).map_err(|e| format!("Failed to initialize database: {}", e))?;
+    Ok(db)
+}
+
+// ticks the running game so time limits are enforced even if the frontend never asks
+pub fn spawn_timer(app_handle: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(TICK_INTERVAL);
+
+        let state = app_handle.state::<GameState>();
+        let mut game = state.0.lock().unwrap();
+        if let Some(session) = game.as_mut() {
+            session.tick();
+            if let Some(level) = session.expired.take() {
+                if let Err(e) = app_handle.emit("game-time-expired", TimeExpired { level }) {
+                    eprintln!("Failed to emit time expired event: {e}");
+                }
+            }
+        }
+    });
+}
+
+// the clock stops while the window is minimized or hidden
+pub fn handle_window_visibility(window: &Window) {
+    // switching to another window to look the answer up counts as leaving the game too
+    let hidden = window.is_minimized().unwrap_or(false)
+        || !window.is_visible().unwrap_or(true)
+        || !window.is_focused().unwrap_or(true);
+
+    let state = window.state::<GameState>();
+    let mut game = state.0.lock().unwrap();
+    if let Some(session) = game.as_mut() {
+        if hidden {
+            session.pause();
+        } else {
+            session.resume();
+        }
+    }
+}
+
+#[tauri::command]
+pub fn start_game(
+    app_handle: AppHandle,
+    state: State<GameState>,
+    path_string: String,
+    settings: GameSettings,
+    seed: Option<u64>,
+) -> Result<GameInfo, String> {
+    begin_game(&app_handle, &state, path_string, settings, seed)
+}
+
+pub fn begin_game(
+    app_handle: &AppHandle,
+    state: &GameState,
+    path_string: String,
+    settings: GameSettings,
+    seed: Option<u64>,
+) -> Result<GameInfo, String> {
+    let db = open_index_db(app_handle, &path_string)?;
+    let session = GameSession::new(&db, path_string, settings, seed)?;
+    println!("Starting game with seed {}", session.seed);
+
+    let info = session.info();
+    *state.0.lock().unwrap() = Some(session);
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn get_game(state: State<GameState>) -> Result<GameInfo, String> {
+    let mut game = state.0.lock().unwrap();
+    let session = game.as_mut().ok_or("No game in progress")?;
+    session.tick();
+    Ok(session.info())
+}
+
+#[tauri::command]
+pub fn use_hint(state: State<GameState>) -> Result<Hint, String> {
+    let mut game = state.0.lock().unwrap();
+    game.as_mut().ok_or("No game in progress")?.use_hint()
+}
+
+#[tauri::command]
+pub fn submit_guess(
+    app_handle: AppHandle,
+    state: State<GameState>,
+    guess: String,
+) -> Result<GuessResult, String> {
+    let mut game = state.0.lock().unwrap();
+    let session = game.as_mut().ok_or("No game in progress")?;
+    let mut result = session.submit_guess(guess)?;
+
+    // finished games go straight into the history
+    if result.finished {
+        result.save_error = save_finished_game(&app_handle, session);
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn submit_contents_guess(
+    app_handle: AppHandle,
+    state: State<GameState>,
+    guess: ContentsGuess,
+) -> Result<ContentsGuessResult, String> {
+    let mut game = state.0.lock().unwrap();
+    let session = game.as_mut().ok_or("No game in progress")?;
+    let mut result = session.submit_contents_guess(guess)?;
+
+    if result.result.finished {
+        result.result.save_error = save_finished_game(&app_handle, session);
+    }
+    Ok(result)
+}
+
+// the guess has already been applied by now, so a failed save is reported with its result
+fn save_finished_game(app_handle: &AppHandle, session: &mut GameSession) -> Option<String> {
+    let error = crate::stats::finish_game(app_handle, session).err()?;
+    eprintln!("Failed to save finished game: {error}");
+    Some(error)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use src_lib::source::{IndexEntry, IndexSink, VecSource};
+    use src_lib::{FileMetadata, IndexOptions};
+    use std::path::PathBuf;
+
+    pub(crate) fn index_options() -> IndexOptions {
+        IndexOptions {
+            path: "/index".to_string(),
+            index_directories: true,
+            index_files: true,
+            ..Default::default()
+        }
+    }
+
+    // an in-memory index of /index/dir-N/sub-M/file-K.ext, built the way the sidecar builds one
+    pub(crate) fn fixture_index() -> Connection {
+        let root = PathBuf::from("/index");
+        let mut entries = vec![IndexEntry::root(&root)];
+        for dir in 0..4 {
+            let dir = root.join(format!("dir-{dir}"));
+            entries.push(IndexEntry::folder(dir.clone()));
+            for sub in 0..3 {
+                let sub = dir.join(format!("sub-{sub}"));
+                entries.push(IndexEntry::folder(sub.clone()));
+                for (file, extension) in ["txt", "jpg", "rs"].iter().enumerate() {
+                    let metadata = FileMetadata {
+                        size: Some(100 * (file as u64 + 1)),
+                        modified: Some(1_700_000_000 + file as i64 * 86_400),
+                        extension: Some(extension.to_string()),
+                    };
+                    entries.push(IndexEntry::file(
+                        sub.join(format!("file-{file}.{extension}")),
+                        metadata,
+                    ));
+                }
+            }
+        }
+
+        let conn = Connection::open_in_memory().unwrap();
+        src_lib::init_db(&conn).unwrap();
+        let options = index_options();
+        IndexSink::new(&conn, &options)
+            .write_all(&mut VecSource(entries))
+            .unwrap();
+        src_lib::resolve_parent_ids(&conn).unwrap();
+        src_lib::compare::build_entry_metadata(&conn).unwrap();
+        src_lib::contents::build_folder_stats(&conn).unwrap();
+        conn
+    }
+
+    // `/index` joined with the parts of a relative path, the way the index stores it on this OS
+    pub(crate) fn fixture_path(relative: &str) -> String {
+        relative
+            .split('/')
+            .fold(PathBuf::from("/index"), |path, part| path.join(part))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    pub(crate) fn settings(game_type: GameType) -> GameSettings {
+        GameSettings {
+            game_type,
+            difficulty: GameDifficulty::Easy,
+            hint_count: 3,
+            time_limit: 0,
+            total_levels: 5,
+            close_sensitivity: 50.0,
+            min_depth: None,
+            max_depth: None,
+            extensions: None,
+        }
+    }
+
+    // a solo file game with the first `levels` levels guessed right, the rest abandoned
+    pub(crate) fn played(levels: usize) -> GameSession {
+        let index = fixture_index();
+        let mut session = GameSession::new(
+            &index,
+            "/index".to_string(),
+            settings(GameType::File),
+            Some(7),
+        )
+        .unwrap();
+        for _ in 0..levels {
+            let answer = session.levels[session.current_level].answer.clone();
+            session.submit_guess(answer).unwrap();
+        }
+        session
+    }
+
+    fn levels(db: &Connection, settings: &GameSettings, seed: u64) -> Vec<(String, Vec<HintKind>)> {
+        pick_levels(db, settings, &mut ChaCha8Rng::seed_from_u64(seed))
+            .unwrap()
+            .into_iter()
+            .map(|level| (level.answer, level.hints))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_levels() {
+        let db = fixture_index();
+        for game_type in [GameType::Directory, GameType::File, GameType::Contents] {
+            let settings = settings(game_type);
+            let first = levels(&db, &settings, 1234);
+            assert_eq!(first.len(), 5);
+            assert_eq!(first, levels(&db, &settings, 1234));
+            assert_ne!(first, levels(&db, &settings, 4321));
+        }
+    }
+
+    // ChaCha8 output is fixed across rand releases, so challenges and shared seeds keep working
+    #[test]
+    fn seeded_levels_do_not_change() {
+        let db = fixture_index();
+        let answers: Vec<String> = levels(&db, &settings(GameType::File), 7)
+            .into_iter()
+            .map(|(answer, _)| answer)
+            .collect();
+        let expected: Vec<String> = [
+            "dir-0/sub-1/file-2.rs",
+            "dir-1/sub-0/file-0.txt",
+            "dir-2/sub-2/file-2.rs",
+            "dir-1/sub-1/file-0.txt",
+            "dir-1/sub-2/file-2.rs",
+        ]
+        .iter()
+        .map(|answer| fixture_path(answer))
+        .collect();
+        assert_eq!(answers, expected);
+    }
+
+    #[test]
+    fn hotseat_players_get_the_same_number_of_levels() {
+        let db = fixture_index();
+        let players: Vec<String> = ["a", "b", "c"].map(String::from).to_vec();
+        // 17 folders for 3 players wanting 10 levels each
+        let settings = GameSettings {
+            total_levels: 10,
+            ..settings(GameType::Directory)
+        };
+        let session =
+            GameSession::new_hotseat(&db, "/index".to_string(), settings, Some(7), players)
+                .unwrap();
+        assert_eq!(session.levels.len(), 15);
+        assert_eq!(session.settings.total_levels, 5);
+        for player in 0..3 {
+            let turns = session.levels.iter().filter(|level| level.player == Some(player));
+            assert_eq!(turns.count(), 5);
+        }
+
+        // more levels than a u32 can count
+        let too_many = GameSettings {
+            total_levels: u32::MAX,
+            ..session.settings
+        };
+        let players = ["a", "b"].map(String::from).to_vec();
+        assert!(
+            GameSession::new_hotseat(&db, "/index".to_string(), too_many, Some(7), players)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn depth_hints_count_from_the_index_root() {
+        let db = fixture_index();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let answer = fixture_path("dir-0/sub-0/file-0.txt");
+        let level = make_level(&db, answer, GameType::File, &mut rng).unwrap();
+        assert_eq!(hint_text(HintKind::Depth, &level), "It is 3 levels deep");
+    }
+
+    #[test]
+    fn timed_out_levels_give_no_hints() {
+        let db = fixture_index();
+        let mut session =
+            GameSession::new(&db, "/index".to_string(), settings(GameType::File), Some(7)).unwrap();
+        session.use_hint().unwrap();
+        session.levels[0].timed_out = true;
+        assert!(session.use_hint().is_err());
+        assert!(session.check_extra_hint().is_err());
+    }
+
+    #[test]
+    fn archive_members_are_never_answers() {
+        let db = fixture_index();
+        // dir-1..3 and everything below them pretend to be inside archives
+        let archived = ["dir-1", "dir-2", "dir-3"].map(fixture_path);
+        for table in ["folders", "files"] {
+            for folder in &archived {
+                db.execute(
+                    &format!("UPDATE {table} SET in_archive = 1 WHERE path LIKE ?1"),
+                    rusqlite::params![format!("{folder}%")],
+                )
+                .unwrap();
+            }
+        }
+        for game_type in [GameType::Directory, GameType::File, GameType::Contents] {
+            let answers = levels(&db, &settings(game_type), 99);
+            assert!(!answers.is_empty());
+            for (answer, _) in answers {
+                assert!(!archived.iter().any(|folder| answer.starts_with(folder)), "{answer}");
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use rusqlite::Connection;
+use tauri::{AppHandle, State};
+
+use crate::game::{
+    now_millis, open_index_db, GameInfo, GameSession, GameSettings, GameState, PlayerScore,
+};
+use crate::stats::open_stats_db;
+
+// player names double as their profile in the history, so they are trimmed and must be unique
+fn validate_players(players: Vec<String>) -> Result<Vec<String>, String> {
+    let players: Vec<String> = players
+        .into_iter()
+        .map(|name| name.trim().to_string())
+        .collect();
+    if players.len() < 2 {
+        return Err("A hot-seat game needs at least two players".to_string());
+    }
+    for (i, name) in players.iter().enumerate() {
+        if name.is_empty() {
+            return Err("Player names can't be empty".to_string());
+        }
+        if players[..i].iter().any(|other| other.eq_ignore_ascii_case(name)) {
+            return Err(format!("There is already a player called \"{}\"", name));
+        }
+    }
+    Ok(players)
+}
+
+fn save_profiles(conn: &Connection, players: &[String]) -> Result<(), String> {
+    for name in players {
+        conn.execute(
+            "INSERT OR IGNORE INTO players (name, created_at) VALUES (?1, ?2)",
+            rusqlite::params![name, now_millis()],
+        )
+        .map_err(|e| format!("Failed to save player: {}", e))?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn start_hotseat_game(
+    app_handle: AppHandle,
+    state: State<GameState>,
+    path_string: String,
+    settings: GameSettings,
+    players: Vec<String>,
+    seed: Option<u64>,
+) -> Result<GameInfo, String> {
+    let players = validate_players(players)?;
+    let db = open_index_db(&app_handle, &path_string)?;
+    let session = GameSession::new_hotseat(&db, path_string, settings, seed, players)?;
+    save_profiles(&open_stats_db(&app_handle)?, &session.players)?;
+    println!(
+        "Starting hot-seat game for {} players with seed {}",
+        session.players.len(),
+        session.seed
+    );
+
+    let info = session.info();
+    *state.0.lock().unwrap() = Some(session);
+    Ok(info)
+}
+
+// the player whose turn it is has taken the seat
+#[tauri::command]
+pub fn begin_turn(state: State<GameState>) -> Result<GameInfo, String> {
+    let mut game = state.0.lock().unwrap();
+    let session = game.as_mut().ok_or("No game in progress")?;
+    session.begin_turn()?;
+    Ok(session.info())
+}
+
+#[tauri::command]
+pub fn get_leaderboard(state: State<GameState>) -> Result<Vec<PlayerScore>, String> {
+    let game = state.0.lock().unwrap();
+    let session = game.as_ref().ok_or("No game in progress")?;
+    if session.players.is_empty() {
+        return Err("This is not a hot-seat game".to_string());
+    }
+    Ok(session.leaderboard())
+}
+
+// every profile that has played a hot-seat game, for picking players again
+#[tauri::command]
+pub fn get_players(app_handle: AppHandle) -> Result<Vec<String>, String> {
+    let db = open_stats_db(&app_handle)?;
+    let mut stmt = db
+        .prepare("SELECT name FROM players ORDER BY name COLLATE NOCASE")
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let players = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Failed to execute query: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to get row: {}", e))?;
+    Ok(players)
+}
@@ -0,0 +1,792 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use src_lib::GuessOutcome;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::game::{
+    hint_text, open_index_db, pick_levels, rank_scores, GameSettings, GameType, Hint, Level,
+    PlayerScore, MAX_SEED,
+};
+
+// LAN matches: the host draws the levels from its own index and runs the clock, players connect
+// over TCP and talk newline-delimited JSON, one ClientMessage or ServerMessage per line
+
+const DEFAULT_PORT: u16 = 47_300;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// between the answer reveal and the next level
+const INTERMISSION: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ClientMessage {
+    Join { name: String },
+    Guess { guess: String },
+    Hint,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ServerMessage {
+    #[serde(rename_all = "camelCase")]
+    Welcome {
+        name: String,
+        settings: GameSettings,
+        total_levels: usize,
+    },
+    // the answer stays on the host, only the level number and clock go out
+    #[serde(rename_all = "camelCase")]
+    Level { level: usize, time_limit: u32 },
+    Hint { level: usize, hint: Hint },
+    #[serde(rename_all = "camelCase")]
+    Result {
+        level: usize,
+        outcome: GuessOutcome,
+        time_taken: f64, // in seconds, measured by the host
+        timed_out: bool,
+    },
+    LevelEnd { level: usize, answer: String },
+    Scoreboard { players: Vec<PlayerScore> },
+    Finished { players: Vec<PlayerScore> },
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LanResult {
+    outcome: GuessOutcome,
+    time_taken: Duration,
+}
+
+struct LanPlayer {
+    name: String,
+    stream: TcpStream,
+    outbox: mpsc::Sender<String>, // lines for the player's writer thread
+    connected: bool,
+    results: Vec<Option<LanResult>>,
+    hints_used: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    Lobby,
+    Playing { level: usize, started: Instant },
+    Intermission { next: usize, until: Instant },
+    Finished,
+}
+
+pub type ScoreboardCallback = Arc<dyn Fn(&[PlayerScore]) + Send + Sync>;
+
+// everything the connection and timer threads share, always behind the server's mutex
+struct Match {
+    settings: GameSettings,
+    levels: Vec<Level>,
+    players: Vec<LanPlayer>,
+    phase: Phase,
+    on_scoreboard: ScoreboardCallback,
+}
+
+impl Match {
+    fn send(&mut self, player: usize, message: &ServerMessage) {
+        let player = &mut self.players[player];
+        if !player.connected {
+            return;
+        }
+        let sent = serde_json::to_string(message)
+            .map_err(|e| e.to_string())
+            .and_then(|line| player.outbox.send(line).map_err(|e| e.to_string()));
+        if let Err(e) = sent {
+            eprintln!("Lost connection to {}: {}", player.name, e);
+            player.connected = false;
+        }
+    }
+
+    fn broadcast(&mut self, message: &ServerMessage) {
+        for player in 0..self.players.len() {
+            self.send(player, message);
+        }
+    }
+
+    fn time_limit(&self) -> Option<Duration> {
+        match self.settings.time_limit {
+            0 => None,
+            secs => Some(Duration::from_secs(secs as u64)),
+        }
+    }
+
+    fn scoreboard(&self) -> Vec<PlayerScore> {
+        let mut scores: Vec<PlayerScore> = self
+            .players
+            .iter()
+            .map(|player| {
+                let results = || player.results.iter().flatten();
+                let count = |wanted: GuessOutcome| {
+                    results().filter(|result| result.outcome == wanted).count()
+                };
+                PlayerScore {
+                    name: player.name.clone(),
+                    rank: 0,
+                    correct: count(GuessOutcome::Correct),
+                    close: count(GuessOutcome::Close),
+                    incorrect: count(GuessOutcome::Incorrect),
+                    hints_used: player.hints_used.iter().sum(),
+                    time_taken: results().map(|r| r.time_taken.as_secs_f64()).sum(),
+                }
+            })
+            .collect();
+        rank_scores(&mut scores);
+        scores
+    }
+
+    fn push_scoreboard(&mut self) {
+        let players = self.scoreboard();
+        (self.on_scoreboard)(&players);
+        self.broadcast(&ServerMessage::Scoreboard { players });
+    }
+
+    fn start_level(&mut self, level: usize) {
+        self.phase = Phase::Playing {
+            level,
+            started: Instant::now(),
+        };
+        let time_limit = self.settings.time_limit;
+        self.broadcast(&ServerMessage::Level { level, time_limit });
+    }
+
+    fn end_level(&mut self, level: usize) {
+        let answer = self.levels[level].answer.clone();
+        self.broadcast(&ServerMessage::LevelEnd { level, answer });
+        self.push_scoreboard();
+        self.phase = Phase::Intermission {
+            next: level + 1,
+            until: Instant::now() + INTERMISSION,
+        };
+    }
+
+    fn finish(&mut self) {
+        self.phase = Phase::Finished;
+        let players = self.scoreboard();
+        (self.on_scoreboard)(&players);
+        self.broadcast(&ServerMessage::Finished { players });
+    }
+
+    // driven by the timer thread, moves the match along on the host's clock
+    fn update(&mut self) {
+        // everyone left, don't play the remaining levels to an empty room
+        let running = matches!(
+            self.phase,
+            Phase::Playing { .. } | Phase::Intermission { .. }
+        );
+        if running && !self.players.iter().any(|player| player.connected) {
+            println!("Everyone left, ending the LAN match");
+            self.finish();
+            return;
+        }
+        match self.phase {
+            Phase::Playing { level, started } => {
+                let out_of_time = self
+                    .time_limit()
+                    .is_some_and(|limit| started.elapsed() >= limit);
+                let everyone_guessed = self
+                    .players
+                    .iter()
+                    .filter(|player| player.connected)
+                    .all(|player| player.results[level].is_some());
+                if out_of_time || everyone_guessed {
+                    self.end_level(level);
+                }
+            }
+            Phase::Intermission { next, until } if Instant::now() >= until => {
+                if next < self.levels.len() {
+                    self.start_level(next);
+                } else {
+                    self.finish();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn join(&mut self, name: String, stream: TcpStream) -> Result<usize, String> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err("Player names can't be empty".to_string());
+        }
+        if matches!(self.phase, Phase::Finished) {
+            return Err("This match is already over".to_string());
+        }
+        if self
+            .players
+            .iter()
+            .any(|player| player.name.eq_ignore_ascii_case(&name))
+        {
+            return Err(format!("There is already a player called \"{}\"", name));
+        }
+
+        let writer = stream
+            .try_clone()
+            .map_err(|e| format!("Failed to set up connection: {}", e))?;
+        let id = self.players.len();
+        self.players.push(LanPlayer {
+            name: name.clone(),
+            stream,
+            outbox: spawn_writer(writer),
+            connected: true,
+            results: vec![None; self.levels.len()],
+            hints_used: vec![0; self.levels.len()],
+        });
+        let welcome = ServerMessage::Welcome {
+            name,
+            settings: self.settings.clone(),
+            total_levels: self.levels.len(),
+        };
+        self.send(id, &welcome);
+        // late joiners start on the level in progress
+        if let Phase::Playing { level, .. } = self.phase {
+            let time_limit = self.settings.time_limit;
+            self.send(id, &ServerMessage::Level { level, time_limit });
+        }
+        self.push_scoreboard();
+        Ok(id)
+    }
+
+    fn handle(&mut self, player: usize, message: ClientMessage) -> Result<(), String> {
+        let Phase::Playing { level, started } = self.phase else {
+            return Err("No level is being played".to_string());
+        };
+        match message {
+            ClientMessage::Join { .. } => Err("Already joined".to_string()),
+            ClientMessage::Hint => {
+                let used = self.players[player].hints_used[level];
+                let hints = &self.levels[level].hints;
+                if used >= self.settings.hint_count as usize || used >= hints.len() {
+                    return Err("No hints left for this level".to_string());
+                }
+                let kind = hints[used];
+                let hint = Hint {
+                    kind,
+                    text: hint_text(kind, &self.levels[level]),
+                };
+                self.players[player].hints_used[level] += 1;
+                self.send(player, &ServerMessage::Hint { level, hint });
+                Ok(())
+            }
+            ClientMessage::Guess { guess } => {
+                if self.players[player].results[level].is_some() {
+                    return Err("You already guessed this level".to_string());
+                }
+                let time_taken = started.elapsed();
+                // same rule as solo games, late guesses never score
+                let timed_out = self.time_limit().is_some_and(|limit| time_taken > limit);
+                let outcome = if timed_out {
+                    GuessOutcome::Incorrect
+                } else {
+                    src_lib::score_guess(
+                        &guess,
+                        &self.levels[level].answer,
+                        self.settings.close_sensitivity,
+                    )
+                };
+                self.players[player].results[level] = Some(LanResult {
+                    outcome,
+                    time_taken,
+                });
+                self.send(
+                    player,
+                    &ServerMessage::Result {
+                        level,
+                        outcome,
+                        time_taken: time_taken.as_secs_f64(),
+                        timed_out,
+                    },
+                );
+                self.push_scoreboard();
+                Ok(())
+            }
+        }
+    }
+
+    fn disconnect(&mut self, player: usize) {
+        let player = &mut self.players[player];
+        let _ = player.stream.shutdown(Shutdown::Both);
+        if player.connected {
+            player.connected = false;
+            println!("{} left the match", player.name);
+        }
+    }
+}
+
+// the hosting side, usable without Tauri so it can be driven from plain TCP clients on loopback
+pub struct LanServer {
+    pub address: SocketAddr,
+    shared: Arc<Mutex<Match>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl LanServer {
+    pub fn start(
+        address: SocketAddr,
+        settings: GameSettings,
+        levels: Vec<Level>,
+        on_scoreboard: ScoreboardCallback,
+    ) -> Result<Self, String> {
+        if settings.game_type == GameType::Contents {
+            return Err("Contents games can't be played over LAN".to_string());
+        }
+        let listener = TcpListener::bind(address)
+            .map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure listener: {}", e))?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to get listening address: {}", e))?;
+
+        let shared = Arc::new(Mutex::new(Match {
+            settings,
+            levels,
+            players: Vec::new(),
+            phase: Phase::Lobby,
+            on_scoreboard,
+        }));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let (accept_shared, accept_shutdown) = (shared.clone(), shutdown.clone());
+        thread::spawn(move || {
+            while !accept_shutdown.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, peer)) => {
+                        println!("LAN connection from {}", peer);
+                        let shared = accept_shared.clone();
+                        thread::spawn(move || serve_connection(stream, shared));
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        thread::sleep(POLL_INTERVAL)
+                    }
+                    Err(e) => eprintln!("Failed to accept LAN connection: {}", e),
+                }
+            }
+        });
+
+        let (timer_shared, timer_shutdown) = (shared.clone(), shutdown.clone());
+        thread::spawn(move || {
+            while !timer_shutdown.load(Ordering::Relaxed) {
+                timer_shared.lock().unwrap().update();
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        println!("Hosting LAN match on {}", address);
+        Ok(LanServer {
+            address,
+            shared,
+            shutdown,
+        })
+    }
+
+    // lobby is over, send out the first level
+    pub fn begin(&self) -> Result<(), String> {
+        let mut game = self.shared.lock().unwrap();
+        if !matches!(game.phase, Phase::Lobby) {
+            return Err("The match has already started".to_string());
+        }
+        if !game.players.iter().any(|player| player.connected) {
+            return Err("Nobody has joined yet".to_string());
+        }
+        game.start_level(0);
+        Ok(())
+    }
+
+    pub fn scoreboard(&self) -> Vec<PlayerScore> {
+        self.shared.lock().unwrap().scoreboard()
+    }
+
+    pub fn stop(&self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        let mut game = self.shared.lock().unwrap();
+        if !matches!(game.phase, Phase::Finished) {
+            game.finish();
+        }
+        for player in 0..game.players.len() {
+            game.disconnect(player);
+        }
+    }
+}
+
+impl Drop for LanServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// writes happen on a thread per player, never under the match's lock, so a client that stops
+// reading can't hold up the timer or the other players
+fn spawn_writer(mut stream: TcpStream) -> mpsc::Sender<String> {
+    let (outbox, lines) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for line in lines {
+            if let Err(e) = writeln!(stream, "{line}").and_then(|_| stream.flush()) {
+                eprintln!("Failed to write to LAN player: {}", e);
+                // the reading side sees the connection close and disconnects the player
+                let _ = stream.shutdown(Shutdown::Both);
+                break;
+            }
+        }
+    });
+    outbox
+}
+
+fn serve_connection(stream: TcpStream, shared: Arc<Mutex<Match>>) {
+    let reply_error = |mut stream: &TcpStream, message: String| {
+        if let Ok(line) = serde_json::to_string(&ServerMessage::Error { message }) {
+            let _ = writeln!(stream, "{line}");
+        }
+    };
+    let mut lines = BufReader::new(&stream).lines();
+
+    // the first message has to be a join
+    let player = loop {
+        let Some(Ok(line)) = lines.next() else {
+            return;
+        };
+        match serde_json::from_str(&line) {
+            Ok(ClientMessage::Join { name }) => {
+                let Ok(writer) = stream.try_clone() else {
+                    return;
+                };
+                let joined = shared.lock().unwrap().join(name, writer);
+                match joined {
+                    Ok(player) => break player,
+                    Err(message) => reply_error(&stream, message),
+                }
+            }
+            Ok(_) => reply_error(&stream, "Join the match first".to_string()),
+            Err(e) => reply_error(&stream, format!("Invalid message: {}", e)),
+        }
+    };
+
+    for line in lines {
+        let Ok(line) = line else {
+            break;
+        };
+        let mut game = shared.lock().unwrap();
+        let handled = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid message: {}", e))
+            .and_then(|message| game.handle(player, message));
+        if let Err(message) = handled {
+            game.send(player, &ServerMessage::Error { message });
+        }
+    }
+    shared.lock().unwrap().disconnect(player);
+}
+
+// the joining side, forwards every server message to the frontend as a "lan-message" event
+pub struct LanClient {
+    stream: TcpStream,
+}
+
+impl LanClient {
+    pub fn send(&mut self, message: &serde_json::Value) -> Result<(), String> {
+        writeln!(self.stream, "{message}")
+            .and_then(|_| self.stream.flush())
+            .map_err(|e| format!("Failed to send to the host: {}", e))
+    }
+}
+
+impl Drop for LanClient {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[derive(Default)]
+pub struct LanState {
+    pub server: Mutex<Option<LanServer>>,
+    pub client: Mutex<Option<LanClient>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanHostInfo {
+    pub port: u16,
+    pub seed: u64,
+    pub total_levels: usize,
+}
+
+#[tauri::command]
+pub fn host_lan_match(
+    app_handle: AppHandle,
+    state: State<LanState>,
+    path_string: String,
+    settings: GameSettings,
+    port: Option<u16>,
+    seed: Option<u64>,
+) -> Result<LanHostInfo, String> {
+    let mut server = state.server.lock().unwrap();
+    if server.is_some() {
+        return Err("Already hosting a match".to_string());
+    }
+
+    let seed = seed.unwrap_or_else(|| rand::rng().random_range(0..=MAX_SEED));
+    let db = open_index_db(&app_handle, &path_string)?;
+    let levels = pick_levels(&db, &settings, &mut ChaCha8Rng::seed_from_u64(seed))?;
+    let total_levels = levels.len();
+
+    let emitter = app_handle.clone();
+    let on_scoreboard: ScoreboardCallback = Arc::new(move |players: &[PlayerScore]| {
+        if let Err(e) = emitter.emit("lan-scoreboard", players) {
+            eprintln!("Failed to emit LAN scoreboard: {e}");
+        }
+    });
+    let address = SocketAddr::from(([0, 0, 0, 0], port.unwrap_or(DEFAULT_PORT)));
+    let hosted = LanServer::start(address, settings, levels, on_scoreboard)?;
+    let port = hosted.address.port();
+    *server = Some(hosted);
+
+    Ok(LanHostInfo {
+        port,
+        seed,
+        total_levels,
+    })
+}
+
+#[tauri::command]
+pub fn begin_lan_match(state: State<LanState>) -> Result<(), String> {
+    let server = state.server.lock().unwrap();
+    server.as_ref().ok_or("Not hosting a match")?.begin()
+}
+
+#[tauri::command]
+pub fn get_lan_scoreboard(state: State<LanState>) -> Result<Vec<PlayerScore>, String> {
+    let server = state.server.lock().unwrap();
+    Ok(server.as_ref().ok_or("Not hosting a match")?.scoreboard())
+}
+
+#[tauri::command]
+pub fn stop_lan_match(state: State<LanState>) -> Result<(), String> {
+    // dropping the server stops it and disconnects everyone
+    state
+        .server
+        .lock()
+        .unwrap()
+        .take()
+        .map(|_| ())
+        .ok_or("Not hosting a match".to_string())
+}
+
+#[tauri::command]
+pub fn join_lan_match(
+    app_handle: AppHandle,
+    state: State<LanState>,
+    address: String,
+    name: String,
+) -> Result<(), String> {
+    let address = if address.contains(':') {
+        address
+    } else {
+        format!("{}:{}", address, DEFAULT_PORT)
+    };
+    let stream = TcpStream::connect(&address)
+        .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+    let reader = stream
+        .try_clone()
+        .map_err(|e| format!("Failed to set up connection: {}", e))?;
+
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            match serde_json::from_str::<serde_json::Value>(&line) {
+                Ok(message) => {
+                    if let Err(e) = app_handle.emit("lan-message", message) {
+                        eprintln!("Failed to emit LAN message: {e}");
+                    }
+                }
+                Err(e) => eprintln!("Ignoring invalid message from host: {}", e),
+            }
+        }
+        let _ = app_handle.emit("lan-disconnected", ());
+    });
+
+    let mut client = LanClient { stream };
+    client.send(&serde_json::json!({ "type": "join", "name": name }))?;
+    *state.client.lock().unwrap() = Some(client);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn lan_guess(state: State<LanState>, guess: String) -> Result<(), String> {
+    let mut client = state.client.lock().unwrap();
+    client
+        .as_mut()
+        .ok_or("Not in a LAN match")?
+        .send(&serde_json::json!({ "type": "guess", "guess": guess }))
+}
+
+#[tauri::command]
+pub fn lan_hint(state: State<LanState>) -> Result<(), String> {
+    let mut client = state.client.lock().unwrap();
+    client
+        .as_mut()
+        .ok_or("Not in a LAN match")?
+        .send(&serde_json::json!({ "type": "hint" }))
+}
+
+#[tauri::command]
+pub fn leave_lan_match(state: State<LanState>) -> Result<(), String> {
+    state
+        .client
+        .lock()
+        .unwrap()
+        .take()
+        .map(|_| ())
+        .ok_or("Not in a LAN match".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameDifficulty;
+
+    fn settings() -> GameSettings {
+        GameSettings {
+            game_type: GameType::Directory,
+            difficulty: GameDifficulty::Easy,
+            hint_count: 1,
+            time_limit: 0,
+            total_levels: 1,
+            close_sensitivity: 50.0,
+            min_depth: None,
+            max_depth: None,
+            extensions: None,
+        }
+    }
+
+    fn level(answer: &str) -> Level {
+        Level {
+            answer: answer.to_string(),
+            hints: vec![crate::game::HintKind::FirstLetter],
+            hints_used: 0,
+            guess: None,
+            outcome: None,
+            started_at: None,
+            ended_at: None,
+            elapsed: Duration::ZERO,
+            timed_out: false,
+            contents: None,
+            player: None,
+            depth: None,
+            extra_hints: 0,
+            extra_time: Duration::ZERO,
+        }
+    }
+
+    struct TestClient {
+        client: LanClient,
+        lines: std::io::Lines<BufReader<TcpStream>>,
+    }
+
+    impl TestClient {
+        fn join(address: SocketAddr, name: &str) -> Self {
+            let stream = TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            let lines = BufReader::new(stream.try_clone().unwrap()).lines();
+            let mut client = TestClient {
+                client: LanClient { stream },
+                lines,
+            };
+            client
+                .client
+                .send(&serde_json::json!({ "type": "join", "name": name }))
+                .unwrap();
+            client.wait_for("welcome");
+            client
+        }
+
+        // skips everything else the host sends until a message of this type
+        fn wait_for(&mut self, kind: &str) -> serde_json::Value {
+            loop {
+                let line = self.lines.next().expect("host hung up").unwrap();
+                let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+                if message["type"] == kind {
+                    return message;
+                }
+            }
+        }
+    }
+
+    fn start(levels: Vec<Level>) -> LanServer {
+        let address = SocketAddr::from(([127, 0, 0, 1], 0));
+        LanServer::start(
+            address,
+            settings(),
+            levels,
+            Arc::new(|_: &[PlayerScore]| {}),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn two_loopback_clients_play_a_level() {
+        let server = start(vec![level("/index/photos/2019")]);
+        let mut alice = TestClient::join(server.address, "alice");
+        let mut bob = TestClient::join(server.address, "bob");
+
+        server.begin().unwrap();
+        assert_eq!(alice.wait_for("level")["level"], 0);
+        assert_eq!(bob.wait_for("level")["level"], 0);
+
+        alice
+            .client
+            .send(&serde_json::json!({ "type": "guess", "guess": "/index/photos/2019" }))
+            .unwrap();
+        bob.client
+            .send(&serde_json::json!({ "type": "guess", "guess": "/somewhere/else" }))
+            .unwrap();
+        assert_eq!(alice.wait_for("result")["outcome"], "correct");
+        assert_eq!(bob.wait_for("result")["outcome"], "incorrect");
+
+        // both guessed, so the host reveals the answer without waiting for a clock
+        assert_eq!(alice.wait_for("levelEnd")["answer"], "/index/photos/2019");
+        let scoreboard = server.scoreboard();
+        assert_eq!(scoreboard.len(), 2);
+        assert_eq!(scoreboard[0].name, "alice");
+        assert_eq!((scoreboard[0].rank, scoreboard[0].correct), (1, 1));
+        assert_eq!(scoreboard[1].name, "bob");
+        assert_eq!((scoreboard[1].rank, scoreboard[1].incorrect), (2, 1));
+    }
+
+    #[test]
+    fn match_ends_when_everyone_leaves() {
+        let server = start(vec![
+            level("/index/a"),
+            level("/index/b"),
+            level("/index/c"),
+        ]);
+        let mut alice = TestClient::join(server.address, "alice");
+        server.begin().unwrap();
+        alice.wait_for("level");
+        drop(alice);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !matches!(server.shared.lock().unwrap().phase, Phase::Finished) {
+            assert!(
+                Instant::now() < deadline,
+                "match kept going without players"
+            );
+            thread::sleep(POLL_INTERVAL);
+        }
+        // nobody got to play the later levels
+        let scoreboard = server.scoreboard();
+        assert_eq!(scoreboard[0].correct + scoreboard[0].incorrect, 0);
+    }
+}
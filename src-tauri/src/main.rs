@@ -1,10 +1,23 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::path::Path;
+mod achievements;
+mod challenge;
+mod compare;
+mod daily;
+mod game;
+mod hotseat;
+mod lan;
+mod presets;
+mod settings;
+mod shop;
+mod stats;
+
+use std::path::{Path, PathBuf};
 use rusqlite::Connection;
-use src_lib::IndexOptions;
-use tauri::{AppHandle, Manager};
+use src_lib::search::{SearchOptions, SearchResult};
+use src_lib::{ChildrenPage, IndexOptions, ListOptions};
+use tauri::{AppHandle, Manager, WindowEvent};
 use tauri_plugin_shell::{process::CommandEvent, ShellExt};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -12,20 +25,94 @@ fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_opener::init())
+        .manage(game::GameState::default())
+        .manage(compare::ComparisonState::default())
+        .manage(lan::LanState::default())
+        .setup(|app| {
+            app.manage(DataDir(src_lib::data_dir(None)?));
+            app.manage(settings::init_settings(app.handle()));
+            game::spawn_timer(app.handle().clone());
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            // minimizing shows up as a resize, hiding and switching away as a focus change
+            if let WindowEvent::Resized(_) | WindowEvent::Focused(_) = event {
+                game::handle_window_visibility(window);
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             start_indexing,
             stop_indexing,
             get_random_dir,
-            get_random_file
+            get_random_file,
+            list_children,
+            search_paths,
+            game::start_game,
+            game::get_game,
+            game::use_hint,
+            game::submit_guess,
+            game::submit_contents_guess,
+            stats::record_game,
+            stats::get_statistics,
+            stats::get_statistics_by_difficulty,
+            stats::get_statistics_by_index,
+            stats::get_game_history,
+            compare::start_comparison,
+            compare::answer_comparison,
+            compare::next_comparison_round,
+            compare::get_comparison_score,
+            presets::get_presets,
+            presets::create_preset,
+            presets::update_preset,
+            presets::delete_preset,
+            presets::start_preset_game,
+            settings::get_settings,
+            settings::update_settings,
+            shop::get_shop_items,
+            shop::get_balance,
+            shop::get_inventory,
+            shop::purchase_item,
+            shop::use_item,
+            achievements::get_achievements,
+            daily::get_daily,
+            daily::start_daily,
+            daily::get_daily_history,
+            challenge::export_challenge,
+            challenge::import_challenge,
+            hotseat::start_hotseat_game,
+            hotseat::begin_turn,
+            hotseat::get_leaderboard,
+            hotseat::get_players,
+            lan::host_lan_match,
+            lan::begin_lan_match,
+            lan::get_lan_scoreboard,
+            lan::stop_lan_match,
+            lan::join_lan_match,
+            lan::lan_guess,
+            lan::lan_hint,
+            lan::leave_lan_match
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
+// resolved once by src-lib (honoring $GUESSFS_DATA_DIR) and handed to the sidecar, so both
+// always read and write the same indexes
+pub struct DataDir(pub PathBuf);
+
+pub fn app_data_dir(app_handle: &AppHandle) -> PathBuf {
+    app_handle.state::<DataDir>().0.clone()
+}
+
 #[tauri::command]
 fn start_indexing(app_handle: AppHandle, index_options: IndexOptions) -> Result<String, String> {
     // start indexing
-    let mut args = vec!["--path".to_string(), index_options.path.clone()];
+    let mut args = vec![
+        "index".to_string(),
+        "--path".to_string(),
+        index_options.path.clone(),
+    ];
+    push_arg(&mut args, "--data-dir", Some(app_data_dir(&app_handle).display()));
 
     // Combine index_files and index_directories into a single --index arg, comma-separated if both
     let mut index_values = Vec::new();
@@ -82,6 +169,16 @@ fn start_indexing(app_handle: AppHandle, index_options: IndexOptions) -> Result<
         push_arg(&mut args, "--exclude", Some(exclude_values.join(",")));
     }
 
+    if index_options.index_archives.unwrap_or(false) {
+        push_arg(&mut args, "--archives", None::<String>);
+        if let Some(max_size) = index_options.archive_max_size {
+            push_arg(&mut args, "--archive-max-size", Some(max_size));
+        }
+        if let Some(max_depth) = index_options.archive_max_depth {
+            push_arg(&mut args, "--archive-max-depth", Some(max_depth));
+        }
+    }
+
     // run sidecar binary
     let sidecar_command = app_handle.shell().sidecar("src-sidecar").unwrap();
 
@@ -159,7 +256,7 @@ fn stop_indexing() {
 
 #[tauri::command]
 fn get_random_dir(app_handle: AppHandle, path_string: String) -> Result<String, String> {
-    let app_data_dir = app_handle.path().app_data_dir().unwrap();
+    let app_data_dir = app_data_dir(&app_handle);
     let db_path = src_lib::get_index_db_path(&app_data_dir, &path_string).unwrap();
     let db = Connection::open(&db_path).unwrap();
 
@@ -184,7 +281,7 @@ fn get_random_dir(app_handle: AppHandle, path_string: String) -> Result<String,
 
 #[tauri::command]
 fn get_random_file(app_handle: AppHandle, path_string: String) -> Result<String, String> {
-    let app_data_dir = app_handle.path().app_data_dir().unwrap();
+    let app_data_dir = app_data_dir(&app_handle);
     let db_path = src_lib::get_index_db_path(&app_data_dir, &path_string).unwrap();
     let db = Connection::open(&db_path).unwrap();
 
@@ -206,3 +303,62 @@ fn get_random_file(app_handle: AppHandle, path_string: String) -> Result<String,
     }
     Err("No files found in DB".to_string())
 }
+
+#[tauri::command]
+fn list_children(
+    app_handle: AppHandle,
+    path_string: String,
+    folder: Option<String>,
+    options: Option<ListOptions>,
+) -> Result<ChildrenPage, String> {
+    let app_data_dir = app_data_dir(&app_handle);
+    let db_path = src_lib::get_index_db_path(&app_data_dir, &path_string)?;
+    if !db_path.exists() {
+        return Err(format!("No index found for: {}", path_string));
+    }
+    let db = Connection::open(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    // older indexes get their parent links added here
+    src_lib::init_db(&db).map_err(|e| format!("Failed to initialize database: {}", e))?;
+
+    // default to the root of the index
+    let folder = folder.unwrap_or(path_string);
+    src_lib::list_children(&db, &folder, &options.unwrap_or_default())
+}
+
+#[tauri::command]
+fn search_paths(
+    app_handle: AppHandle,
+    path_string: String,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<Vec<SearchResult>, String> {
+    let app_data_dir = app_data_dir(&app_handle);
+    let db_path = src_lib::get_index_db_path(&app_data_dir, &path_string)?;
+    if !db_path.exists() {
+        return Err(format!("No index found for: {}", path_string));
+    }
+    let db = Connection::open(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    src_lib::init_db(&db).map_err(|e| format!("Failed to initialize database: {}", e))?;
+
+    // indexes made before search existed get it built on first use
+    let is_empty: bool = db
+        .query_row("SELECT NOT EXISTS (SELECT 1 FROM search)", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to check search index: {}", e))?;
+    if is_empty {
+        src_lib::search::build_search_index(&db)
+            .map_err(|e| format!("Failed to build search index: {}", e))?;
+    }
+
+    src_lib::search::search_paths(&db, &query, &options.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    // the sidecar finds the app's data dir with src-lib's copy of the identifier
+    #[test]
+    fn identifier_matches_the_tauri_config() {
+        let config: serde_json::Value =
+            serde_json::from_str(include_str!("../tauri.conf.json")).unwrap();
+        assert_eq!(config["identifier"], src_lib::APP_IDENTIFIER);
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::game::{GameDifficulty, GameInfo, GameSettings, GameState};
+
+// named "Custom" difficulty settings, kept in presets.json in the app data dir
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    pub name: String,
+    pub settings: GameSettings,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PresetFile {
+    pub presets: Vec<Preset>,
+}
+
+fn presets_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::app_data_dir(app_handle).join("presets.json"))
+}
+
+pub fn load_presets(app_handle: &AppHandle) -> Result<PresetFile, String> {
+    let path = presets_path(app_handle)?;
+    if !path.exists() {
+        return Ok(PresetFile::default());
+    }
+    let contents =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read presets: {}", e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Failed to parse presets: {}", e))
+}
+
+fn save_presets(app_handle: &AppHandle, presets: &PresetFile) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(presets)
+        .map_err(|e| format!("Failed to serialize presets: {}", e))?;
+    crate::settings::write_atomic(&presets_path(app_handle)?, &contents)
+}
+
+fn validate(preset: &mut Preset) -> Result<(), String> {
+    preset.name = preset.name.trim().to_string();
+    if preset.name.is_empty() {
+        return Err("Preset name can't be empty".to_string());
+    }
+
+    let settings = &mut preset.settings;
+    // presets always count as custom games in the statistics
+    settings.difficulty = GameDifficulty::Custom;
+    if settings.total_levels == 0 {
+        return Err("A preset needs at least one level".to_string());
+    }
+    if !(0.0..=100.0).contains(&settings.close_sensitivity) {
+        return Err("Close sensitivity must be between 0 and 100".to_string());
+    }
+    if let (Some(min), Some(max)) = (settings.min_depth, settings.max_depth) {
+        if min > max {
+            return Err("Minimum depth can't be more than the maximum depth".to_string());
+        }
+    }
+    if let Some(extensions) = &mut settings.extensions {
+        *extensions = extensions
+            .iter()
+            .map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase())
+            .filter(|e| !e.is_empty())
+            .collect();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_presets(app_handle: AppHandle) -> Result<Vec<Preset>, String> {
+    Ok(load_presets(&app_handle)?.presets)
+}
+
+#[tauri::command]
+pub fn create_preset(app_handle: AppHandle, mut preset: Preset) -> Result<Preset, String> {
+    validate(&mut preset)?;
+    let mut presets = load_presets(&app_handle)?;
+    if presets.presets.iter().any(|p| p.name == preset.name) {
+        return Err(format!("A preset called \"{}\" already exists", preset.name));
+    }
+    presets.presets.push(preset.clone());
+    save_presets(&app_handle, &presets)?;
+    Ok(preset)
+}
+
+// `name` is the preset's current name, the new one can differ to rename it
+#[tauri::command]
+pub fn update_preset(
+    app_handle: AppHandle,
+    name: String,
+    mut preset: Preset,
+) -> Result<Preset, String> {
+    validate(&mut preset)?;
+    let mut presets = load_presets(&app_handle)?;
+    if preset.name != name && presets.presets.iter().any(|p| p.name == preset.name) {
+        return Err(format!("A preset called \"{}\" already exists", preset.name));
+    }
+    let existing = presets
+        .presets
+        .iter_mut()
+        .find(|p| p.name == name)
+        .ok_or(format!("No preset called \"{}\"", name))?;
+    *existing = preset.clone();
+    save_presets(&app_handle, &presets)?;
+    Ok(preset)
+}
+
+#[tauri::command]
+pub fn delete_preset(app_handle: AppHandle, name: String) -> Result<(), String> {
+    let mut presets = load_presets(&app_handle)?;
+    let count = presets.presets.len();
+    presets.presets.retain(|p| p.name != name);
+    if presets.presets.len() == count {
+        return Err(format!("No preset called \"{}\"", name));
+    }
+    save_presets(&app_handle, &presets)
+}
+
+#[tauri::command]
+pub fn start_preset_game(
+    app_handle: AppHandle,
+    state: State<GameState>,
+    path_string: String,
+    name: String,
+    seed: Option<u64>,
+) -> Result<GameInfo, String> {
+    let preset = load_presets(&app_handle)?
+        .presets
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or(format!("No preset called \"{}\"", name))?;
+    crate::game::begin_game(&app_handle, &state, path_string, preset.settings, seed)
+}
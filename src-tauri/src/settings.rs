@@ -0,0 +1,269 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::game::{GameDifficulty, GameType};
+
+// bump this and add a step to `migrate` whenever the layout of settings.json changes
+pub const SETTINGS_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+// pre-filled exclusions for new indexes, same meaning as in IndexOptions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExclusionDefaults {
+    pub hidden: bool,
+    pub system: bool,
+    pub temporary: bool,
+    pub empty: bool,
+    pub admin: bool,
+    pub regex: Option<String>,
+    pub paths: Vec<String>,
+    pub files: Vec<String>,
+}
+
+impl Default for ExclusionDefaults {
+    fn default() -> Self {
+        ExclusionDefaults {
+            hidden: true,
+            system: true,
+            temporary: true,
+            empty: true,
+            admin: false,
+            regex: None,
+            paths: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameplayDefaults {
+    pub game_type: GameType,
+    pub difficulty: GameDifficulty,
+    pub hint_count: u32,
+    pub time_limit: u32, // in seconds, 0 for no limit
+    pub total_levels: u32,
+    pub close_sensitivity: f64,
+}
+
+impl Default for GameplayDefaults {
+    fn default() -> Self {
+        GameplayDefaults {
+            game_type: GameType::Directory,
+            difficulty: GameDifficulty::Easy,
+            hint_count: 3,
+            time_limit: 60,
+            total_levels: 10,
+            close_sensitivity: 50.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub version: u32,
+    pub index_roots: Vec<String>, // paths offered when starting a game
+    pub exclusions: ExclusionDefaults,
+    pub theme: Theme,
+    pub gameplay: GameplayDefaults,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SETTINGS_VERSION,
+            index_roots: Vec::new(),
+            exclusions: ExclusionDefaults::default(),
+            theme: Theme::default(),
+            gameplay: GameplayDefaults::default(),
+        }
+    }
+}
+
+pub struct SettingsState(pub Mutex<Settings>);
+
+fn settings_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_config_dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to get app config directory: {}", e))?;
+    Ok(app_config_dir.join("settings.json"))
+}
+
+// write to a temp file next to the target and rename it over, so a crash never leaves half a file
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
+    fs::rename(&temp_path, path)
+        .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+// bring an older settings.json up to SETTINGS_VERSION, one version at a time
+fn migrate(mut value: Value) -> Value {
+    // v1 is the first layout, steps for later versions go here
+
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), Value::from(SETTINGS_VERSION));
+    }
+    value
+}
+
+fn file_version(value: &Value) -> u64 {
+    value
+        .get("version")
+        .and_then(Value::as_u64)
+        .unwrap_or(SETTINGS_VERSION as u64)
+}
+
+pub fn load_settings(app_handle: &AppHandle) -> Result<Settings, String> {
+    let path = settings_path(app_handle)?;
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+
+    let contents =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read settings: {}", e))?;
+    let value: Value =
+        serde_json::from_str(&contents).map_err(|e| format!("Failed to parse settings: {}", e))?;
+    let version = file_version(&value);
+    if version > SETTINGS_VERSION as u64 {
+        // read what this version knows, the file itself is left alone (see write_settings)
+        eprintln!("settings.json is version {version}, newer than this build's {SETTINGS_VERSION}");
+        return serde_json::from_value(value)
+            .map_err(|e| format!("Failed to parse settings: {}", e));
+    }
+    let needs_migration = version < SETTINGS_VERSION as u64;
+
+    let settings: Settings = serde_json::from_value(migrate(value))
+        .map_err(|e| format!("Failed to parse settings: {}", e))?;
+    if needs_migration {
+        println!("Migrated settings to version {}", SETTINGS_VERSION);
+        save_settings(app_handle, &settings)?;
+    }
+    Ok(settings)
+}
+
+fn save_settings(app_handle: &AppHandle, settings: &Settings) -> Result<(), String> {
+    write_settings(&settings_path(app_handle)?, settings)
+}
+
+// a newer build's settings.json is never rewritten, that would throw away whatever it added
+fn write_settings(path: &Path, settings: &Settings) -> Result<(), String> {
+    let existing = fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Value>(&contents).ok());
+    if existing.is_some_and(|value| file_version(&value) > SETTINGS_VERSION as u64) {
+        return Err(
+            "settings.json was saved by a newer version of GuessFS, update to change settings"
+                .to_string(),
+        );
+    }
+    let contents = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    write_atomic(path, &contents)
+}
+
+fn validate(settings: &mut Settings) -> Result<(), String> {
+    settings.version = SETTINGS_VERSION;
+    settings.index_roots.retain(|root| !root.trim().is_empty());
+    settings.index_roots.dedup();
+
+    if let Some(regex) = &settings.exclusions.regex {
+        regex::Regex::new(regex).map_err(|e| format!("Invalid exclusion regex: {}", e))?;
+    }
+    let gameplay = &settings.gameplay;
+    if gameplay.total_levels == 0 {
+        return Err("A game needs at least one level".to_string());
+    }
+    if !(0.0..=100.0).contains(&gameplay.close_sensitivity) {
+        return Err("Close sensitivity must be between 0 and 100".to_string());
+    }
+    Ok(())
+}
+
+// falls back to the defaults when settings.json is unreadable, so the app still starts
+pub fn init_settings(app_handle: &AppHandle) -> SettingsState {
+    let settings = load_settings(app_handle).unwrap_or_else(|e| {
+        eprintln!("{e}, using default settings");
+        Settings::default()
+    });
+    SettingsState(Mutex::new(settings))
+}
+
+#[tauri::command]
+pub fn get_settings(state: State<SettingsState>) -> Settings {
+    state.0.lock().unwrap().clone()
+}
+
+#[tauri::command]
+pub fn update_settings(
+    app_handle: AppHandle,
+    state: State<SettingsState>,
+    mut settings: Settings,
+) -> Result<Settings, String> {
+    validate(&mut settings)?;
+    save_settings(&app_handle, &settings)?;
+    *state.0.lock().unwrap() = settings.clone();
+
+    if let Err(e) = app_handle.emit("settings-changed", &settings) {
+        eprintln!("Failed to emit settings changed event: {e}");
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("guessfs-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("settings.json")
+    }
+
+    #[test]
+    fn newer_settings_are_not_overwritten() {
+        let path = scratch("newer-settings");
+        let newer = r#"{ "version": 99, "theme": "dark", "fromTheFuture": true }"#;
+        fs::write(&path, newer).unwrap();
+
+        assert!(write_settings(&path, &Settings::default()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn current_settings_are_written() {
+        let path = scratch("current-settings");
+        write_settings(&path, &Settings::default()).unwrap();
+        let settings = Settings {
+            theme: Theme::Dark,
+            ..Settings::default()
+        };
+        write_settings(&path, &settings).unwrap();
+
+        let value: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(file_version(&value), SETTINGS_VERSION as u64);
+        assert_eq!(value["theme"], "dark");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rusqlite::{Connection, Result as RusqliteResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use src_lib::GuessOutcome;
+use tauri::{AppHandle, State};
+
+use crate::game::{now_millis, GameDifficulty, GameSession, GameState};
+use crate::stats::open_stats_db;
+
+// mixed into every ledger hash, so entries can't be forged by just recomputing sha256 in a DB editor
+const LEDGER_KEY: &str = "guessfs-ledger-v1";
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+const TIME_EXTENSION: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Item {
+    ExtraHint,     // one more hint on the current level
+    TimeExtension, // 30 more seconds on the current level
+    ThemeMidnight,
+    ThemeTerminal,
+}
+
+impl Item {
+    pub const ALL: [Item; 4] = [
+        Item::ExtraHint,
+        Item::TimeExtension,
+        Item::ThemeMidnight,
+        Item::ThemeTerminal,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Item::ExtraHint => "extraHint",
+            Item::TimeExtension => "timeExtension",
+            Item::ThemeMidnight => "themeMidnight",
+            Item::ThemeTerminal => "themeTerminal",
+        }
+    }
+
+    fn from_name(item: &str) -> Option<Item> {
+        Item::ALL.into_iter().find(|i| i.as_str() == item)
+    }
+
+    pub fn price(&self) -> i64 {
+        match self {
+            Item::ExtraHint => 25,
+            Item::TimeExtension => 40,
+            Item::ThemeMidnight => 300,
+            Item::ThemeTerminal => 300,
+        }
+    }
+
+    // cosmetics are kept forever, the rest is used up in games
+    pub fn is_consumable(&self) -> bool {
+        matches!(self, Item::ExtraHint | Item::TimeExtension)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShopItem {
+    pub item: Item,
+    pub price: i64,
+    pub consumable: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InventoryItem {
+    pub item: Item,
+    pub count: i64,
+}
+
+pub fn init_ledger(conn: &Connection) -> RusqliteResult<()> {
+    // amount is positive for earnings, negative for purchases and 0 for using an item
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ledger (
+            id INTEGER PRIMARY KEY,
+            kind TEXT NOT NULL,
+            amount INTEGER NOT NULL,
+            item TEXT,
+            game_id INTEGER,
+            created_at INTEGER NOT NULL,
+            prev_hash TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            FOREIGN KEY (game_id) REFERENCES games(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS ledger_no_update BEFORE UPDATE ON ledger
+         BEGIN SELECT RAISE(ABORT, 'ledger is append-only'); END",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS ledger_no_delete BEFORE DELETE ON ledger
+         BEGIN SELECT RAISE(ABORT, 'ledger is append-only'); END",
+        [],
+    )?;
+    Ok(())
+}
+
+fn entry_hash(
+    prev_hash: &str,
+    kind: &str,
+    amount: i64,
+    item: Option<&str>,
+    game_id: Option<i64>,
+    created_at: u64,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(LEDGER_KEY.as_bytes());
+    hasher.update(
+        format!(
+            "{prev_hash}|{kind}|{amount}|{}|{}|{created_at}",
+            item.unwrap_or(""),
+            game_id.map(|id| id.to_string()).unwrap_or_default()
+        )
+        .as_bytes(),
+    );
+    format!("{:x}", hasher.finalize())
+}
+
+fn append_entry(
+    conn: &Connection,
+    kind: &str,
+    amount: i64,
+    item: Option<Item>,
+    game_id: Option<i64>,
+) -> Result<(), String> {
+    let prev_hash: String = conn
+        .query_row(
+            "SELECT hash FROM ledger ORDER BY id DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or_else(|_| GENESIS_HASH.to_string());
+    let item = item.map(|i| i.as_str());
+    let created_at = now_millis();
+    let hash = entry_hash(&prev_hash, kind, amount, item, game_id, created_at);
+
+    conn.execute(
+        "INSERT INTO ledger (kind, amount, item, game_id, created_at, prev_hash, hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![kind, amount, item, game_id, created_at, prev_hash, hash],
+    )
+    .map_err(|e| format!("Failed to write ledger entry: {}", e))?;
+    Ok(())
+}
+
+// walk the whole chain, any edited, removed or inserted row breaks it
+pub fn verify_ledger(conn: &Connection) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, kind, amount, item, game_id, created_at, prev_hash, hash
+             FROM ledger ORDER BY id",
+        )
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let mut rows = stmt
+        .query([])
+        .map_err(|e| format!("Failed to execute query: {}", e))?;
+
+    let mut expected_prev = GENESIS_HASH.to_string();
+    while let Some(row) = rows
+        .next()
+        .map_err(|e| format!("Failed to get next row: {}", e))?
+    {
+        let read = |row: &rusqlite::Row| -> RusqliteResult<_> {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+                row.get::<_, u64>(5)?,
+                row.get::<_, String>(6)?,
+                row.get::<_, String>(7)?,
+            ))
+        };
+        let (id, kind, amount, item, game_id, created_at, prev_hash, hash) =
+            read(row).map_err(|e| format!("Failed to read ledger entry: {}", e))?;
+
+        let computed = entry_hash(&prev_hash, &kind, amount, item.as_deref(), game_id, created_at);
+        if prev_hash != expected_prev || computed != hash {
+            return Err(format!("Ledger has been tampered with at entry {}", id));
+        }
+        expected_prev = hash;
+    }
+    Ok(())
+}
+
+pub fn balance(conn: &Connection) -> Result<i64, String> {
+    verify_ledger(conn)?;
+    conn.query_row("SELECT COALESCE(SUM(amount), 0) FROM ledger", [], |row| {
+        row.get(0)
+    })
+    .map_err(|e| format!("Failed to get balance: {}", e))
+}
+
+pub fn inventory(conn: &Connection) -> Result<HashMap<Item, i64>, String> {
+    verify_ledger(conn)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT item,
+                SUM(CASE kind WHEN 'purchase' THEN 1 WHEN 'use' THEN -1 ELSE 0 END)
+             FROM ledger WHERE item IS NOT NULL GROUP BY item",
+        )
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let counts = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+        .map_err(|e| format!("Failed to execute query: {}", e))?
+        .collect::<RusqliteResult<Vec<_>>>()
+        .map_err(|e| format!("Failed to get row: {}", e))?;
+
+    Ok(counts
+        .into_iter()
+        .filter_map(|(item, count)| Some((Item::from_name(&item)?, count)))
+        .filter(|(_, count)| *count > 0)
+        .collect())
+}
+
+// earnings for a finished game: points per answer, plus bonuses for unused hints and time left,
+// scaled by difficulty
+pub fn game_reward(session: &GameSession) -> i64 {
+    let hint_count = session.settings.hint_count as usize;
+    let time_limit = session.settings.time_limit as f64;
+
+    let mut points = 0.0;
+    for level in &session.levels {
+        points += match level.outcome {
+            Some(GuessOutcome::Correct) => 10.0,
+            Some(GuessOutcome::Close) => 4.0,
+            _ => 0.0,
+        };
+        if level.outcome.is_some() {
+            points += 2.0 * hint_count.saturating_sub(level.hints_used) as f64;
+            if time_limit > 0.0 && !level.timed_out {
+                let left = (time_limit - level.elapsed.as_secs_f64()).max(0.0);
+                points += 5.0 * left / time_limit;
+            }
+        }
+    }
+
+    let multiplier = match session.settings.difficulty {
+        GameDifficulty::Easy | GameDifficulty::Custom => 1.0,
+        GameDifficulty::Medium => 1.5,
+        GameDifficulty::Hard => 2.0,
+        GameDifficulty::Expert => 3.0,
+    };
+    (points * multiplier).round() as i64
+}
+
+// called from stats::record_session, inside the same transaction as the game
+pub fn award_game(conn: &Connection, game_id: i64, session: &GameSession) -> Result<i64, String> {
+    // abandoned games are still recorded, but only finished ones pay out
+    if !session.is_finished() {
+        return Ok(0);
+    }
+    let reward = game_reward(session);
+    if reward > 0 {
+        append_entry(conn, "earn", reward, None, Some(game_id))?;
+    }
+    Ok(reward)
+}
+
+#[tauri::command]
+pub fn get_shop_items() -> Vec<ShopItem> {
+    Item::ALL
+        .into_iter()
+        .map(|item| ShopItem {
+            item,
+            price: item.price(),
+            consumable: item.is_consumable(),
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_balance(app_handle: AppHandle) -> Result<i64, String> {
+    let db = open_stats_db(&app_handle)?;
+    balance(&db)
+}
+
+#[tauri::command]
+pub fn get_inventory(app_handle: AppHandle) -> Result<Vec<InventoryItem>, String> {
+    let db = open_stats_db(&app_handle)?;
+    Ok(inventory(&db)?
+        .into_iter()
+        .map(|(item, count)| InventoryItem { item, count })
+        .collect())
+}
+
+#[tauri::command]
+pub fn purchase_item(app_handle: AppHandle, item: Item) -> Result<i64, String> {
+    let db = open_stats_db(&app_handle)?;
+    let tx = db
+        .unchecked_transaction()
+        .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+
+    if !item.is_consumable() && inventory(&tx)?.contains_key(&item) {
+        return Err("You already own this item".to_string());
+    }
+    let balance = balance(&tx)?;
+    if balance < item.price() {
+        return Err("Not enough currency".to_string());
+    }
+    append_entry(&tx, "purchase", -item.price(), Some(item), None)?;
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    Ok(balance - item.price())
+}
+
+// spend a consumable on the game in progress
+#[tauri::command]
+pub fn use_item(
+    app_handle: AppHandle,
+    state: State<GameState>,
+    item: Item,
+) -> Result<(), String> {
+    if !item.is_consumable() {
+        return Err("This item can't be used in a game".to_string());
+    }
+    let db = open_stats_db(&app_handle)?;
+    let tx = db
+        .unchecked_transaction()
+        .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+    if inventory(&tx)?.get(&item).copied().unwrap_or(0) <= 0 {
+        return Err("You don't have this item".to_string());
+    }
+
+    // nothing is spent if the game refuses the item, and the game only gets it once it's spent
+    let mut game = state.0.lock().unwrap();
+    let session = game.as_mut().ok_or("No game in progress")?;
+    // the inventory belongs to whoever owns this machine, not to every player in the seat
+    if !session.players.is_empty() {
+        return Err("Items can't be used in hot-seat games".to_string());
+    }
+    // catch a level that ran out of time since the timer last looked
+    session.tick();
+    match item {
+        Item::ExtraHint => session.check_extra_hint()?,
+        Item::TimeExtension => session.check_extend_time()?,
+        _ => unreachable!(),
+    }
+    append_entry(&tx, "use", 0, Some(item), None)?;
+    tx.commit()
+        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+    match item {
+        Item::ExtraHint => session.add_extra_hint(),
+        Item::TimeExtension => session.extend_time(TIME_EXTENSION),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tests::played;
+
+    #[test]
+    fn only_finished_games_pay_out() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::stats::init_stats_db(&conn).unwrap();
+
+        crate::stats::record_session(&conn, &mut played(2)).unwrap();
+        assert_eq!(balance(&conn).unwrap(), 0);
+
+        crate::stats::record_session(&conn, &mut played(5)).unwrap();
+        assert!(balance(&conn).unwrap() > 0);
+    }
+}
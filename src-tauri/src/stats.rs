@@ -0,0 +1,591 @@
+use rusqlite::{Connection, Result as RusqliteResult};
+use serde::Serialize;
+use src_lib::GuessOutcome;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::game::{GameDifficulty, GameSession, GameState, Level};
+
+// game history lives in its own DB next to the indexes, so deleting an index doesn't wipe it
+pub fn open_stats_db(app_handle: &AppHandle) -> Result<Connection, String> {
+    let app_data_dir = crate::app_data_dir(app_handle);
+    if !app_data_dir.exists() {
+        std::fs::create_dir_all(&app_data_dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+
+    let db = Connection::open(app_data_dir.join("stats.db"))
+        .map_err(|e| format!("Failed to open stats database: {}", e))?;
+    init_stats_db(&db).map_err(|e| format!("Failed to initialize stats database: {}", e))?;
+    Ok(db)
+}
+
+pub fn init_stats_db(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS games (
+            id INTEGER PRIMARY KEY,
+            index_path TEXT NOT NULL,
+            seed INTEGER NOT NULL,
+            game_type TEXT NOT NULL,
+            difficulty TEXT NOT NULL,
+            hint_count INTEGER NOT NULL,
+            time_limit INTEGER NOT NULL,
+            total_levels INTEGER NOT NULL,
+            correct INTEGER NOT NULL,
+            close INTEGER NOT NULL,
+            incorrect INTEGER NOT NULL,
+            won INTEGER NOT NULL,
+            time_taken INTEGER NOT NULL,
+            started_at INTEGER NOT NULL,
+            ended_at INTEGER NOT NULL,
+            player TEXT
+        )",
+        [],
+    )?;
+    // NULL for solo games, the profile name for hot-seat players
+    if !src_lib::has_column(conn, "games", "player")? {
+        conn.execute("ALTER TABLE games ADD COLUMN player TEXT", [])?;
+    }
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS players (
+            name TEXT PRIMARY KEY,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS levels (
+            id INTEGER PRIMARY KEY,
+            game_id INTEGER NOT NULL,
+            level INTEGER NOT NULL,
+            answer TEXT NOT NULL,
+            outcome TEXT,
+            hints_used INTEGER NOT NULL,
+            time_taken INTEGER NOT NULL,
+            timed_out INTEGER NOT NULL,
+            started_at INTEGER,
+            ended_at INTEGER,
+            depth INTEGER,
+            FOREIGN KEY (game_id) REFERENCES games(id)
+        )",
+        [],
+    )?;
+    // the answer's depth below the index root, NULL for levels recorded before it was kept
+    if !src_lib::has_column(conn, "levels", "depth")? {
+        conn.execute("ALTER TABLE levels ADD COLUMN depth INTEGER", [])?;
+    }
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS guesses (
+            id INTEGER PRIMARY KEY,
+            level_id INTEGER NOT NULL,
+            guess TEXT NOT NULL,
+            outcome TEXT NOT NULL,
+            guessed_at INTEGER NOT NULL,
+            FOREIGN KEY (level_id) REFERENCES levels(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS hints (
+            id INTEGER PRIMARY KEY,
+            level_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            FOREIGN KEY (level_id) REFERENCES levels(id)
+        )",
+        [],
+    )?;
+    // hints used to be saved by their Rust name ("FirstLetter"), now by the one the frontend sees
+    conn.execute(
+        "UPDATE hints SET kind = lower(substr(kind, 1, 1)) || substr(kind, 2) WHERE kind GLOB '[A-Z]*'",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_games_started_at ON games (started_at);",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_levels_game_id ON levels (game_id);",
+        [],
+    )?;
+    crate::shop::init_ledger(conn)?;
+    crate::achievements::init_achievements(conn)?;
+    crate::daily::init_daily(conn)?;
+    Ok(())
+}
+
+pub fn outcome_str(outcome: GuessOutcome) -> &'static str {
+    match outcome {
+        GuessOutcome::Correct => "correct",
+        GuessOutcome::Close => "close",
+        GuessOutcome::Incorrect => "incorrect",
+    }
+}
+
+// write a (finished or abandoned) game session and all of its levels to the history
+// hot-seat games are written as one game per player, returns the ids of the games written
+pub fn record_session(conn: &Connection, session: &mut GameSession) -> Result<Vec<i64>, String> {
+    if session.recorded {
+        return Err("Game has already been recorded".to_string());
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+    let mut game_ids = Vec::new();
+    if session.players.is_empty() {
+        let levels: Vec<_> = session.levels.iter().enumerate().collect();
+        game_ids.push(insert_game(&tx, session, None, &levels)?);
+    } else {
+        for (player, name) in session.players.iter().enumerate() {
+            let levels: Vec<_> = session
+                .levels
+                .iter()
+                .enumerate()
+                .filter(|(_, level)| level.player == Some(player))
+                .collect();
+            game_ids.push(insert_game(&tx, session, Some(name), &levels)?);
+        }
+    }
+
+    if let Some(day) = session.daily {
+        tx.execute(
+            "UPDATE daily_challenges SET game_id = ?1 WHERE index_path = ?2 AND day = ?3",
+            rusqlite::params![game_ids[0], session.index_path, day],
+        )
+        .map_err(|e| format!("Failed to link daily challenge: {}", e))?;
+    }
+
+    // currency belongs to whoever owns this machine, hot-seat games don't earn any
+    if session.players.is_empty() {
+        let reward = crate::shop::award_game(&tx, game_ids[0], session)?;
+        println!("Game {} earned {} currency", game_ids[0], reward);
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    session.recorded = true;
+    Ok(game_ids)
+}
+
+// `levels` are (level number in the session, level) pairs
+fn insert_game(
+    tx: &Connection,
+    session: &GameSession,
+    player: Option<&str>,
+    levels: &[(usize, &Level)],
+) -> Result<i64, String> {
+    let count = |wanted: GuessOutcome| {
+        levels
+            .iter()
+            .filter(|(_, level)| level.outcome == Some(wanted))
+            .count()
+    };
+    let correct = count(GuessOutcome::Correct);
+    let close = count(GuessOutcome::Close);
+    let incorrect = count(GuessOutcome::Incorrect);
+    // a "win" is more than 50% correct answers
+    let won = correct * 2 > levels.len();
+    let started_at = levels
+        .iter()
+        .filter_map(|(_, level)| level.started_at)
+        .min()
+        .unwrap_or(session.started_at);
+    let ended_at = levels
+        .iter()
+        .filter_map(|(_, level)| level.ended_at)
+        .max()
+        .unwrap_or(started_at);
+    // active time in millis, pauses don't count
+    let time_taken: u64 = levels
+        .iter()
+        .map(|(_, level)| level.elapsed.as_millis() as u64)
+        .sum();
+
+    tx.execute(
+        "INSERT INTO games (index_path, seed, game_type, difficulty, hint_count, time_limit,
+            total_levels, correct, close, incorrect, won, time_taken, started_at, ended_at, player)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        rusqlite::params![
+            session.index_path,
+            session.seed,
+            session.settings.game_type.as_str(),
+            session.settings.difficulty.as_str(),
+            session.settings.hint_count,
+            session.settings.time_limit,
+            levels.len(),
+            correct,
+            close,
+            incorrect,
+            won,
+            time_taken,
+            started_at,
+            ended_at,
+            player
+        ],
+    )
+    .map_err(|e| format!("Failed to insert game: {}", e))?;
+    let game_id = tx.last_insert_rowid();
+
+    for (i, level) in levels {
+        tx.execute(
+            "INSERT INTO levels (game_id, level, answer, outcome, hints_used, time_taken, timed_out,
+                started_at, ended_at, depth)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            rusqlite::params![
+                game_id,
+                i,
+                level.answer,
+                level.outcome.map(outcome_str),
+                level.hints_used,
+                level.elapsed.as_millis() as u64,
+                level.timed_out,
+                level.started_at,
+                level.ended_at,
+                level.depth
+            ],
+        )
+        .map_err(|e| format!("Failed to insert level: {}", e))?;
+        let level_id = tx.last_insert_rowid();
+
+        if let (Some(guess), Some(outcome)) = (&level.guess, level.outcome) {
+            tx.execute(
+                "INSERT INTO guesses (level_id, guess, outcome, guessed_at) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![level_id, guess, outcome_str(outcome), level.ended_at],
+            )
+            .map_err(|e| format!("Failed to insert guess: {}", e))?;
+        }
+
+        for (position, kind) in level.hints.iter().take(level.hints_used).enumerate() {
+            let kind = serde_json::to_value(kind)
+                .map_err(|e| format!("Failed to serialize hint: {}", e))?;
+            tx.execute(
+                "INSERT INTO hints (level_id, position, kind) VALUES (?1, ?2, ?3)",
+                rusqlite::params![level_id, position, kind.as_str()],
+            )
+            .map_err(|e| format!("Failed to insert hint: {}", e))?;
+        }
+    }
+    Ok(game_id)
+}
+
+// record a session and let the frontend know about any achievements it unlocked
+pub fn finish_game(app_handle: &AppHandle, session: &mut GameSession) -> Result<Vec<i64>, String> {
+    let db = open_stats_db(app_handle)?;
+    let game_ids = record_session(&db, session)?;
+    // achievements only follow solo games
+    if !session.players.is_empty() {
+        return Ok(game_ids);
+    }
+
+    for achievement in crate::achievements::evaluate(&db, game_ids.first().copied())? {
+        println!("Unlocked achievement {}", achievement.id);
+        if let Err(e) = app_handle.emit("achievement-unlocked", &achievement) {
+            eprintln!("Failed to emit achievement unlocked event: {e}");
+        }
+    }
+    Ok(game_ids)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Statistics {
+    pub games: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub win_rate: f64,
+    pub levels: u64,
+    pub correct: u64,
+    pub close: u64,
+    pub incorrect: u64,
+    pub close_ratio: f64, // close answers out of all levels played
+    pub hints_used: u64,
+    pub average_time: f64, // in seconds, per game
+    pub best_streak: u64,   // most games won in a row
+    pub current_streak: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupedStatistics {
+    pub key: String,
+    pub statistics: Statistics,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameRecord {
+    pub id: i64,
+    pub index_path: String,
+    pub seed: u64,
+    pub game_type: String,
+    pub difficulty: String,
+    pub total_levels: u64,
+    pub correct: u64,
+    pub close: u64,
+    pub incorrect: u64,
+    pub won: bool,
+    pub time_taken: f64, // in seconds
+    pub started_at: u64,
+    pub ended_at: u64,
+    pub player: Option<String>, // None for solo games
+}
+
+// the columns read_game_record expects, in order, starting at `first`
+pub const GAME_RECORD_COLUMNS: &str = "games.id, games.index_path, games.seed, games.game_type,
+    games.difficulty, games.total_levels, games.correct, games.close, games.incorrect, games.won,
+    games.time_taken, games.started_at, games.ended_at, games.player";
+
+pub fn read_game_record(row: &rusqlite::Row, first: usize) -> RusqliteResult<GameRecord> {
+    Ok(GameRecord {
+        id: row.get(first)?,
+        index_path: row.get(first + 1)?,
+        seed: row.get(first + 2)?,
+        game_type: row.get(first + 3)?,
+        difficulty: row.get(first + 4)?,
+        total_levels: row.get(first + 5)?,
+        correct: row.get(first + 6)?,
+        close: row.get(first + 7)?,
+        incorrect: row.get(first + 8)?,
+        won: row.get(first + 9)?,
+        time_taken: row.get::<_, f64>(first + 10)? / 1000.0,
+        started_at: row.get(first + 11)?,
+        ended_at: row.get(first + 12)?,
+        player: row.get(first + 13)?,
+    })
+}
+
+pub fn query_statistics(
+    conn: &Connection,
+    difficulty: Option<&str>,
+    index_path: Option<&str>,
+    player: Option<&str>,
+) -> Result<Statistics, String> {
+    // NULL filters match everything, except for the player where NULL means solo games
+    let filter =
+        "(?1 IS NULL OR difficulty = ?1) AND (?2 IS NULL OR index_path = ?2) AND player IS ?3";
+
+    let mut stats = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(won), 0), COALESCE(SUM(total_levels), 0),
+                    COALESCE(SUM(correct), 0), COALESCE(SUM(close), 0), COALESCE(SUM(incorrect), 0),
+                    COALESCE(AVG(time_taken), 0)
+                 FROM games WHERE {filter}"
+            ),
+            rusqlite::params![difficulty, index_path, player],
+            |row| {
+                Ok(Statistics {
+                    games: row.get(0)?,
+                    wins: row.get(1)?,
+                    levels: row.get(2)?,
+                    correct: row.get(3)?,
+                    close: row.get(4)?,
+                    incorrect: row.get(5)?,
+                    average_time: row.get::<_, f64>(6)? / 1000.0,
+                    ..Default::default()
+                })
+            },
+        )
+        .map_err(|e| format!("Failed to query statistics: {}", e))?;
+
+    stats.losses = stats.games - stats.wins;
+    if stats.games > 0 {
+        stats.win_rate = stats.wins as f64 / stats.games as f64;
+    }
+    if stats.levels > 0 {
+        stats.close_ratio = stats.close as f64 / stats.levels as f64;
+    }
+
+    stats.hints_used = conn
+        .query_row(
+            &format!(
+                "SELECT COALESCE(SUM(levels.hints_used), 0) FROM levels
+                 JOIN games ON games.id = levels.game_id WHERE {filter}"
+            ),
+            rusqlite::params![difficulty, index_path, player],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to query hints used: {}", e))?;
+
+    // streaks are easier to walk through in order than to do in SQL
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT won FROM games WHERE {filter} ORDER BY started_at"
+        ))
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let results = stmt
+        .query_map(rusqlite::params![difficulty, index_path, player], |row| {
+            row.get::<_, bool>(0)
+        })
+        .map_err(|e| format!("Failed to execute query: {}", e))?;
+    for won in results {
+        if won.map_err(|e| format!("Failed to get row: {}", e))? {
+            stats.current_streak += 1;
+            stats.best_streak = stats.best_streak.max(stats.current_streak);
+        } else {
+            stats.current_streak = 0;
+        }
+    }
+
+    Ok(stats)
+}
+
+fn query_grouped(
+    conn: &Connection,
+    column: &str,
+    difficulty: Option<&str>,
+    index_path: Option<&str>,
+    player: Option<&str>,
+) -> Result<Vec<GroupedStatistics>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT DISTINCT {column} FROM games
+             WHERE (?1 IS NULL OR difficulty = ?1) AND (?2 IS NULL OR index_path = ?2)
+                AND player IS ?3
+             ORDER BY {column}"
+        ))
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let keys = stmt
+        .query_map(rusqlite::params![difficulty, index_path, player], |row| {
+            row.get::<_, String>(0)
+        })
+        .map_err(|e| format!("Failed to execute query: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to get row: {}", e))?;
+
+    keys.into_iter()
+        .map(|key| {
+            let statistics = match column {
+                "difficulty" => query_statistics(conn, Some(&key), index_path, player)?,
+                _ => query_statistics(conn, difficulty, Some(&key), player)?,
+            };
+            Ok(GroupedStatistics { key, statistics })
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn record_game(app_handle: AppHandle, state: State<GameState>) -> Result<Vec<i64>, String> {
+    // used when a game is abandoned before the last level, finished games are recorded automatically
+    let mut game = state.0.lock().unwrap();
+    let session = game.as_mut().ok_or("No game in progress")?;
+    finish_game(&app_handle, session)
+}
+
+#[tauri::command]
+pub fn get_statistics(
+    app_handle: AppHandle,
+    difficulty: Option<GameDifficulty>,
+    index_path: Option<String>,
+    player: Option<String>,
+) -> Result<Statistics, String> {
+    let db = open_stats_db(&app_handle)?;
+    query_statistics(
+        &db,
+        difficulty.as_ref().map(GameDifficulty::as_str),
+        index_path.as_deref(),
+        player.as_deref(),
+    )
+}
+
+#[tauri::command]
+pub fn get_statistics_by_difficulty(
+    app_handle: AppHandle,
+    index_path: Option<String>,
+    player: Option<String>,
+) -> Result<Vec<GroupedStatistics>, String> {
+    let db = open_stats_db(&app_handle)?;
+    query_grouped(
+        &db,
+        "difficulty",
+        None,
+        index_path.as_deref(),
+        player.as_deref(),
+    )
+}
+
+#[tauri::command]
+pub fn get_statistics_by_index(
+    app_handle: AppHandle,
+    difficulty: Option<GameDifficulty>,
+    player: Option<String>,
+) -> Result<Vec<GroupedStatistics>, String> {
+    let db = open_stats_db(&app_handle)?;
+    query_grouped(
+        &db,
+        "index_path",
+        difficulty.as_ref().map(GameDifficulty::as_str),
+        None,
+        player.as_deref(),
+    )
+}
+
+#[tauri::command]
+pub fn get_game_history(
+    app_handle: AppHandle,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    player: Option<String>, // only this hot-seat player's games, all games when None
+) -> Result<Vec<GameRecord>, String> {
+    let db = open_stats_db(&app_handle)?;
+    let mut stmt = db
+        .prepare(&format!(
+            "SELECT {GAME_RECORD_COLUMNS} FROM games WHERE ?3 IS NULL OR player = ?3
+             ORDER BY started_at DESC LIMIT ?1 OFFSET ?2"
+        ))
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let games = stmt
+        .query_map(
+            rusqlite::params![limit.unwrap_or(50), offset.unwrap_or(0), player],
+            |row| read_game_record(row, 0),
+        )
+        .map_err(|e| format!("Failed to execute query: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to get row: {}", e))?;
+    Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tests::{fixture_index, settings};
+    use crate::game::{GameType, HintKind};
+
+    fn saved_hints(conn: &Connection) -> Vec<HintKind> {
+        let mut stmt = conn.prepare("SELECT kind FROM hints ORDER BY id").unwrap();
+        stmt.query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .map(|kind| serde_json::from_value(serde_json::Value::from(kind.unwrap())).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn hints_are_saved_by_their_serde_name() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_stats_db(&conn).unwrap();
+
+        let index = fixture_index();
+        let mut session = GameSession::new(
+            &index,
+            "/index".to_string(),
+            settings(GameType::File),
+            Some(7),
+        )
+        .unwrap();
+        let used = [
+            session.use_hint().unwrap().kind,
+            session.use_hint().unwrap().kind,
+        ];
+        record_session(&conn, &mut session).unwrap();
+        assert_eq!(saved_hints(&conn), used);
+
+        // rows saved by the Rust name are renamed when the db is opened
+        conn.execute(
+            "UPDATE hints SET kind = 'FirstLetter' WHERE position = 0",
+            [],
+        )
+        .unwrap();
+        init_stats_db(&conn).unwrap();
+        assert_eq!(saved_hints(&conn)[0], HintKind::FirstLetter);
+    }
+}
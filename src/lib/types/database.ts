@@ -16,4 +16,33 @@ export interface IndexOptions {
     exclude_temporary?: boolean; // TEMP, %TEMP%, etc.
     exclude_empty?: boolean; // empty files/folders
     exclude_admin?: boolean; // files not accessible by the current user
+
+    // zip/tar/tar.gz contents indexed as virtual folders
+    index_archives?: boolean;
+    archive_max_size?: number; // bytes
+    archive_max_depth?: number; // 1 = only archives on disk
+}
+
+// a row of list_children
+export interface ChildEntry {
+    name: string;
+    path: string;
+    isDir: boolean;
+    inArchive: boolean; // inside a zip/tar, can't be opened on disk
+}
+
+export interface ChildrenPage {
+    folder: string;
+    parent: string | null; // null at the index root
+    total: number; // entries matching the filter, ignoring offset/limit
+    entries: ChildEntry[];
+}
+
+// a row of search_paths
+export interface SearchResult {
+    path: string;
+    name: string;
+    isDir: boolean;
+    inArchive: boolean;
+    score: number;
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 export type GameDifficulty = "easy" | "medium" | "hard" | "expert" | "custom";
-export type GameType = "directory" | "file";
+export type GameType = "directory" | "file" | "contents";
 
 export interface Game {
 	id: number;
@@ -9,6 +9,7 @@ export interface Game {
 	timeLimit: number; // in seconds
 	hintCount: number;
 	totalLevels: number;
+	seed?: number; // same index + seed always gives the same levels
 	gameData?: GameData;
 }
 
@@ -32,4 +33,81 @@ export interface GameSettings {
 	timeLimit: number; // in seconds
 	totalLevels: number;
 	closeSensitivity: number; // percentage?
+	minDepth?: number; // below the index root
+	maxDepth?: number;
+	extensions?: string[]; // file games only, without the dot
 }
+
+export interface Preset {
+	name: string;
+	settings: Omit<GameSettings, "difficulty"> & { difficulty: GameDifficulty };
+}
+
+// returned by get_daily, one attempt per index per day (UTC)
+export interface DailyInfo {
+	day: number; // days since the unix epoch
+	date: string; // YYYY-MM-DD
+	settings: GameSettings;
+	played: boolean;
+	game: GameRecord | null; // null until today's attempt is finished
+	streak: number;
+	bestStreak: number;
+}
+
+// a row of get_game_history
+export interface GameRecord {
+	id: number;
+	indexPath: string;
+	seed: number;
+	gameType: GameType;
+	difficulty: GameDifficulty;
+	totalLevels: number;
+	correct: number;
+	close: number;
+	incorrect: number;
+	won: boolean;
+	timeTaken: number; // in seconds
+	startedAt: number; // unix millis
+	endedAt: number;
+	player: string | null; // null for solo games
+}
+
+// a shareable challenge file, see export_challenge / import_challenge
+export interface ChallengeFile {
+	version: number;
+	root: { path: string; name: string };
+	settings: GameSettings;
+	seed: number;
+	answers?: string[]; // relative to the root, left out when the answers are hidden
+	answerHash?: { salt: string; hash: string };
+	draw?: { asked: number; kept: number }; // how the hidden answers are drawn again from the seed
+	score: { correct: number; close: number; incorrect: number };
+	createdAt: number; // unix millis
+}
+
+// export_challenge options, answers are hidden behind a hash unless included
+export interface ExportOptions {
+	answers?: "hashed" | "included";
+}
+
+// one row of get_leaderboard in a hot-seat game
+export interface PlayerScore {
+	name: string;
+	rank: number;
+	correct: number;
+	close: number;
+	incorrect: number;
+	hintsUsed: number;
+	timeTaken: number; // in seconds
+}
+
+// sent by the host of a LAN match, arrives as "lan-message" events after join_lan_match
+export type LanMessage =
+	| { type: "welcome"; name: string; settings: GameSettings; totalLevels: number }
+	| { type: "level"; level: number; timeLimit: number }
+	| { type: "hint"; level: number; hint: { kind: string; text: string } }
+	| { type: "result"; level: number; outcome: "correct" | "close" | "incorrect"; timeTaken: number; timedOut: boolean }
+	| { type: "levelEnd"; level: number; answer: string }
+	| { type: "scoreboard"; players: PlayerScore[] }
+	| { type: "finished"; players: PlayerScore[] }
+	| { type: "error"; message: string };
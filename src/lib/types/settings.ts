@@ -0,0 +1,26 @@
+import type { GameDifficulty, GameType } from "./game";
+
+// mirrors Settings in src-tauri/src/settings.rs
+export interface Settings {
+	version: number;
+	indexRoots: string[];
+	exclusions: {
+		hidden: boolean;
+		system: boolean;
+		temporary: boolean;
+		empty: boolean;
+		admin: boolean;
+		regex?: string;
+		paths: string[];
+		files: string[];
+	};
+	theme: "system" | "light" | "dark";
+	gameplay: {
+		gameType: GameType;
+		difficulty: GameDifficulty;
+		hintCount: number;
+		timeLimit: number; // in seconds, 0 for no limit
+		totalLevels: number;
+		closeSensitivity: number;
+	};
+}
@@ -13,4 +13,31 @@ export interface LevelStatistics {
     closeGuesses: number;
     hintsUsed: number;
     timeTaken: number;
-}
\ No newline at end of file
+}
+
+// aggregates returned by the get_statistics commands (stats.db)
+export interface Statistics {
+    games: number;
+    wins: number;
+    losses: number;
+    winRate: number;
+    levels: number;
+    correct: number;
+    close: number;
+    incorrect: number;
+    closeRatio: number; // close answers out of all levels played
+    hintsUsed: number;
+    averageTime: number; // in seconds, per game
+    bestStreak: number;
+    currentStreak: number;
+}
+
+// returned by get_achievements and sent with the "achievement-unlocked" event
+export interface Achievement {
+    id: string;
+    name: string;
+    description: string;
+    progress: number;
+    goal: number;
+    unlockedAt: number | null; // unix millis, null while locked
+}